@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use bigdecimal::{BigDecimal, ToPrimitive};
+use sql_one_flexi_engine::{distinct::HashDistinct, eval, row::StoredRow};
+use sql_one_parser::{expr::{AggregateFunc, Expr}, value::Value};
+
+use crate::{error::QueryExecutionError, functions};
+
+/// The running state of one aggregate within a group. NULLs are skipped by every
+/// aggregate except COUNT(*), which counts rows
+#[derive(Debug, Clone)]
+enum Accumulator {
+    Count(u64),
+    Sum(Option<BigDecimal>),
+    Avg(BigDecimal, u64),
+    Min(Option<Value>),
+    Max(Option<Value>)
+}
+
+impl Accumulator {
+    fn new(func : AggregateFunc) -> Self {
+        match func {
+            AggregateFunc::Count => Accumulator::Count(0),
+            AggregateFunc::Sum => Accumulator::Sum(None),
+            AggregateFunc::Avg => Accumulator::Avg(BigDecimal::from(0), 0),
+            AggregateFunc::Min => Accumulator::Min(None),
+            AggregateFunc::Max => Accumulator::Max(None),
+        }
+    }
+
+    fn add(&mut self, aggregate : &Expr, value : Value) -> Result<(), QueryExecutionError> {
+        if value.is_null() {
+            return Ok(());
+        }
+        match (self, value) {
+            (Accumulator::Count(count), _) => *count += 1,
+            (Accumulator::Sum(sum), Value::Number(n)) => *sum = Some(sum.take().map_or(n.clone(), |sum| sum + n)),
+            (Accumulator::Avg(sum, count), Value::Number(n)) => {
+                *sum += n;
+                *count += 1;
+            },
+            (Accumulator::Min(min), value) => if min.as_ref().is_none_or(|min| value < *min) {
+                *min = Some(value);
+            },
+            (Accumulator::Max(max), value) => if max.as_ref().is_none_or(|max| value > *max) {
+                *max = Some(value);
+            },
+            (_, value) => return Err(QueryExecutionError::InvalidAggregate(format!("{} over {}", aggregate, value))),
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Value {
+        match self {
+            Accumulator::Count(count) => Value::Number(BigDecimal::from(count)),
+            Accumulator::Avg(_, 0) => Value::Null,
+            // rounded to the precision of a double, the type of the AVG column
+            Accumulator::Avg(sum, count) => Value::Number((sum / BigDecimal::from(count)).with_prec(15).normalized()),
+            Accumulator::Sum(value) => value.map_or(Value::Null, Value::Number),
+            Accumulator::Min(value) | Accumulator::Max(value) => value.unwrap_or(Value::Null),
+        }
+    }
+}
+
+/// A hash aggregate operator. Rows are grouped on the values of the GROUP BY columns,
+/// NULLs forming a group of their own, and every group keeps one accumulator per aggregate.
+/// A distinct aggregate first drops the repeated values of its group with a hash distinct,
+/// its accumulators only taking the distinct values once every row was pushed
+pub struct HashAggregate {
+    group_by : Vec<String>,
+    aggregates : Vec<Expr>,
+    // the index of every group's accumulators
+    groups : HashMap<Vec<Value>, usize>,
+    // the key and accumulators of the groups in the order they were first seen, so the output does not depend on the hasher
+    accumulators : Vec<(Vec<Value>, Vec<Accumulator>)>,
+    // for every distinct aggregate, the pairs of group index and value
+    distinct : Vec<Option<HashDistinct>>
+}
+
+impl HashAggregate {
+    pub fn new(group_by : Vec<String>, aggregates : Vec<Expr>) -> Self {
+        let distinct = aggregates.iter()
+            .map(|aggregate| matches!(aggregate, Expr::Aggregate { distinct : true, .. }).then(HashDistinct::new))
+            .collect();
+        Self { group_by, aggregates, groups : HashMap::new(), accumulators : Vec::new(), distinct }
+    }
+
+    pub fn push(&mut self, row : &StoredRow) -> Result<(), QueryExecutionError> {
+        let key : Vec<Value> = self.group_by.iter()
+            .map(|col| row.row.get(col).cloned().unwrap_or(Value::Null))
+            .collect();
+        let index = match self.groups.get(&key) {
+            Some(index) => *index,
+            None => {
+                self.accumulators.push((key.clone(), Self::accumulators(&self.aggregates)));
+                self.groups.insert(key, self.accumulators.len() - 1);
+                self.accumulators.len() - 1
+            },
+        };
+        for (position, aggregate) in self.aggregates.iter().enumerate() {
+            let Expr::Aggregate { arg, .. } = aggregate else {
+                continue;
+            };
+            // COUNT(*) counts the row itself
+            let value = match arg {
+                Some(arg) => eval::evaluate(arg, row, functions::registry()).ok_or_else(|| QueryExecutionError::InvalidExpression(arg.to_string()))?,
+                None => Value::Boolean(true),
+            };
+            match &mut self.distinct[position] {
+                Some(distinct) if !value.is_null() => distinct.push(vec![Value::Number(BigDecimal::from(index as u64)), value])?,
+                Some(_) => {},
+                None => self.accumulators[index].1[position].add(aggregate, value)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn accumulators(aggregates : &[Expr]) -> Vec<Accumulator> {
+        aggregates.iter()
+            .map(|aggregate| match aggregate {
+                Expr::Aggregate { func, .. } => Accumulator::new(*func),
+                _ => Accumulator::Count(0),
+            })
+            .collect()
+    }
+
+    /// One row per group holding the GROUP BY columns and the aggregates, stored under their text.
+    /// Without GROUP BY the whole input is a single group, even when it is empty
+    pub fn finish(mut self) -> Result<Vec<StoredRow>, QueryExecutionError> {
+        if self.group_by.is_empty() && self.accumulators.is_empty() {
+            self.accumulators.push((Vec::new(), Self::accumulators(&self.aggregates)));
+        }
+        for (position, distinct) in self.distinct.iter_mut().enumerate() {
+            let Some(distinct) = distinct.take() else {
+                continue;
+            };
+            for pair in distinct.finish()? {
+                let pair = pair?;
+                let [Value::Number(index), value] = pair.as_slice() else {
+                    continue;
+                };
+                if let Some((_, accumulators)) = index.to_usize().and_then(|index| self.accumulators.get_mut(index)) {
+                    accumulators[position].add(&self.aggregates[position], value.clone())?;
+                }
+            }
+        }
+        let mut rows = Vec::new();
+        for (key, accumulators) in self.accumulators {
+            let mut row : HashMap<String, Value> = self.group_by.iter().cloned().zip(key).collect();
+            for (aggregate, accumulator) in self.aggregates.iter().zip(accumulators) {
+                row.insert(aggregate.to_string(), accumulator.finish());
+            }
+            rows.push(StoredRow::new(row));
+        }
+        Ok(rows)
+    }
+}
+
+/// The first column the expression reads outside of an aggregate that is not one of the
+/// GROUP BY columns, such a column has no single value per group
+pub fn ungrouped<'a>(expr : &'a Expr, group_by : &[String]) -> Option<&'a String> {
+    match expr {
+        Expr::Column(name) => (!group_by.contains(name)).then_some(name),
+        Expr::Aggregate { .. } => None,
+        expr => expr.children().into_iter().find_map(|child| ungrouped(child, group_by)),
+    }
+}
@@ -0,0 +1,59 @@
+use sql_one_flexi_engine::row::StoredRow;
+use sql_one_parser::{commands::{compound::SetOperator, create::Column, select_condition::SelectStatementCondition, with::CommonTableExpr}, value::Value};
+
+use crate::{error::QueryExecutionError, select, set_operation, table::ColumnInfo};
+
+/// The number of times the recursive selects of a common table expression are run before it is stopped
+pub const RECURSION_LIMIT : usize = 1000;
+
+/// The rows of a common table expression, computed before the selects reading them, which read
+/// the view like a table without a primary key
+#[derive(Debug, Clone, Default)]
+pub struct View {
+    pub columns : ColumnInfo,
+    pub rows : Vec<StoredRow>
+}
+
+impl View {
+    /// The view of the output of the expression's query, its columns renamed by the names the
+    /// expression gives them
+    pub fn new(cte : &CommonTableExpr, columns : ColumnInfo, rows : Vec<StoredRow>) -> Result<Self, QueryExecutionError> {
+        if cte.columns.is_empty() {
+            return Ok(Self { columns, rows });
+        }
+        if cte.columns.len() != columns.columns.len() {
+            return Err(QueryExecutionError::CteColumns(cte.name.clone(), columns.columns.len(), cte.columns.len()));
+        }
+        let renamed = ColumnInfo::new(columns.iter().zip(cte.columns.iter()).map(|(col, name)| Column { name : name.clone(), ..col.clone() }).collect());
+        Ok(Self::from_values(renamed, set_operation::values(&columns, rows).collect()))
+    }
+
+    /// The view of rows given as values in the order of the columns
+    pub fn from_values(columns : ColumnInfo, rows : Vec<Vec<Value>>) -> Self {
+        let names : Vec<String> = columns.iter().map(|col| col.name.clone()).collect();
+        let rows = rows.into_iter().map(|values| StoredRow::new(names.iter().cloned().zip(values).collect())).collect();
+        Self { columns, rows }
+    }
+}
+
+/// Checks the query of a recursive common table expression is a select that does not read the
+/// expression followed by UNION [ALL] selects that may, without ordering or limiting the rows.
+/// Gives whether every set operation is UNION ALL, keeping rows produced more than once
+pub fn recursion(cte : &CommonTableExpr) -> Result<bool, QueryExecutionError> {
+    let query = &cte.query;
+    let unions = query.rest.iter().all(|(op, _)| matches!(op, SetOperator::Union | SetOperator::UnionAll));
+    let ordered = !query.order_by.is_empty() || query.limit.is_some() || query.offset.is_some();
+    if query.rest.is_empty() || !unions || ordered || reads(cte, &query.first) {
+        return Err(QueryExecutionError::InvalidRecursion(cte.name.clone()));
+    }
+    Ok(query.rest.iter().all(|(op, _)| *op == SetOperator::UnionAll))
+}
+
+/// Whether the query of the common table expression reads the expression itself
+pub fn is_recursive(cte : &CommonTableExpr) -> bool {
+    std::iter::once(&cte.query.first).chain(cte.query.rest.iter().map(|(_, select)| select)).any(|select| reads(cte, select))
+}
+
+fn reads(cte : &CommonTableExpr, select : &SelectStatementCondition) -> bool {
+    select::tables(select).contains(&&cte.name)
+}
@@ -1,6 +1,6 @@
 use miette::Diagnostic;
-use derive_more::Display;
-use sql_one_parser::{commands::create::SqlTypeInfo, error::FormattedError, value::Value};
+use sql_one_flexi_engine::page::error::InternalStorageError;
+use sql_one_parser::{commands::{compound::SetOperator, create::SqlTypeInfo}, error::FormattedError, value::Value};
 use thiserror::Error;
 
 
@@ -16,6 +16,78 @@ pub enum QueryExecutionError {
     ColumnNotFound(String),
     #[error("Value {1} can not be inserted into a {0} column")]
     InsertTypeMismatch(SqlTypeInfo, Value),
+    #[error("Value {1} is out of range for a {0} column")]
+    OutOfRange(SqlTypeInfo, Value),
+    #[error("expression {0} can not be evaluated")]
+    InvalidExpression(String),
+    #[error("storage error : {0}")]
+    StorageError(String),
+    #[error("column {0} already exists")]
+    ColumnAlreadyExists(String),
+    #[error("column {0} is the primary key and can not be dropped")]
+    PrimaryKeyColumn(String),
+    #[error("duplicate key {1} violates the primary key of table {0}")]
+    PrimaryKeyViolation(String, Value),
+    #[error("ON CONFLICT ({0}) does not match the primary key")]
+    InvalidConflictTarget(String),
+    #[error("table {0} declares more than one primary key")]
+    MultiplePrimaryKeys(String),
+    #[error("invalid constraint : {0}")]
+    InvalidConstraint(String),
+    #[error("column {0} violates its NOT NULL constraint")]
+    NotNullViolation(String),
+    #[error("duplicate value ({1}) violates the unique constraint on ({0})")]
+    UniqueViolation(String, String),
+    #[error("aggregate functions are not allowed in {0}")]
+    AggregateNotAllowed(String),
+    #[error("column {0} must appear in the GROUP BY clause or be used in an aggregate function")]
+    NotGrouped(String),
+    #[error("for SELECT DISTINCT, ORDER BY column {0} must appear in the select list")]
+    DistinctOrderBy(String),
+    #[error("invalid aggregate : {0}")]
+    InvalidAggregate(String),
+    #[error("column reference {0} is ambiguous")]
+    AmbiguousColumn(String),
+    #[error("table name {0} is specified more than once")]
+    DuplicateTableName(String),
+    #[error("column {0} is specified more than once")]
+    DuplicateColumn(String),
+    #[error("INSERT has {1} values for {0} columns")]
+    ValueCountMismatch(usize, usize),
+    #[error("function {name} does not exist")]
+    #[diagnostic(help("the known functions are {known}"))]
+    UnknownFunction { name : String, known : String },
+    #[error("function {name} takes {expected} arguments but got {given}")]
+    #[diagnostic(help("call it as {signature}"))]
+    FunctionArity { name : String, expected : String, given : usize, signature : String },
+    #[error("function {name} can not be applied to ({types})")]
+    #[diagnostic(help("call it as {signature}"))]
+    FunctionArgument { name : String, types : String, signature : String },
+    #[error("subqueries are not allowed in {0}")]
+    SubqueryNotAllowed(String),
+    #[error("subquery {0} must return a single column")]
+    SubqueryColumns(String),
+    #[error("subquery {0} used as a value returned more than one row")]
+    SubqueryRows(String),
+    #[error("{0} combines selects of {1} and {2} columns")]
+    #[diagnostic(help("every select of a set operation must give the same number of columns"))]
+    SetOperationColumns(SetOperator, usize, usize),
+    #[error("{0} can not combine column {1} of type {2} with a {3} column")]
+    SetOperationTypes(SetOperator, String, SqlTypeInfo, SqlTypeInfo),
+    #[error("common table expression {0} gives {1} columns but {2} column names were given")]
+    CteColumns(String, usize, usize),
+    #[error("recursive common table expression {0} is not a select UNION [ALL] selects reading it")]
+    #[diagnostic(help("the first select must not read the expression, and the rows can only be ordered or limited by the select reading the expression"))]
+    InvalidRecursion(String),
+    #[error("recursive common table expression {0} did not finish within {1} iterations")]
+    #[diagnostic(help("make sure the recursion ends, e.g. with UNION or a WHERE condition, or raise the execution's recursion limit"))]
+    RecursionLimit(String, usize),
+}
+
+impl From<InternalStorageError> for QueryExecutionError {
+    fn from(value: InternalStorageError) -> Self {
+        QueryExecutionError::StorageError(format!("{:?}", value))
+    }
 }
 
 
@@ -1,40 +1,94 @@
 
 
-use std::{collections::HashMap, fs::{self, File}, hash::Hash, io::Write};
+use std::{collections::{BTreeMap, HashMap, HashSet}, fs::{self, File}, io::Write, rc::Rc, sync::Arc};
 
-use miette::Diagnostic;
 use serde::{Deserialize, Serialize};
-use sql_one_flexi_engine::{page::table::{key_type, TableMetaData}, storage::Storage};
-use sql_one_parser::{ast::{parse_sql_query, SqlQuery}, commands::create::SqlTypeInfo};
+use sql_one_flexi_engine::{buffer_pool::BufferPool, eval, row::StoredRow};
+use sql_one_parser::{ast::{parse_sql_query, SqlQuery}, commands::{alter::AlterAction, compound::CompoundSelect, create::{Column, CreateStatement}, insert::{InsertSource, OnConflict}, select_condition::SelectStatementCondition, with::{CommonTableExpr, WithQuery}}, expr::Expr, value::Value};
 
-use crate::{error::{QueryExecutionError, SQLError}, table::{table, ColumnInfo, Row, TableIter}};
+use crate::{cte::{self, View}, error::{QueryExecutionError, SQLError}, functions, join::{self, Relation, Scope, Source}, select, set_operation, subquery::{self, Subquery}, table::{table, ColumnInfo, TableIter}};
 use derive_more::Display;
 use std::io::Read;
-use thiserror::Error;
 
 #[derive(Debug,Display )]
 pub enum ExecResponse<'a> { 
     #[display(fmt = "{_0:?}")] 
     Select(TableIter<'a>),
-    Insert,
-    Crete
+    #[display(fmt = "{rows_affected} rows inserted")]
+    Insert { rows_affected : usize },
+    Crete,
+    #[display(fmt = "{rows_affected} rows deleted")]
+    Delete { rows_affected : usize },
+    #[display(fmt = "{rows_affected} rows updated")]
+    Update { rows_affected : usize },
+    Drop,
+    #[display(fmt = "{rows_affected} rows truncated")]
+    Truncate { rows_affected : usize },
+    Alter
 }
 
 
 
 
+// the values of the output rows of a select, read as they are taken
+type OutputRows<'a> = Box<dyn Iterator<Item = Result<Vec<Value>, QueryExecutionError>> + 'a>;
+
+// what a select over joined tables is run with, checked before any row is read
+struct Plan<'a> { 
+    sources : Vec<Source<'a>>,
+    scope : Scope,
+    // the columns the select reads, those of the scope along with one for every scalar subquery
+    columns : ColumnInfo,
+    outputs : Vec<(String, Expr)>,
+    // the subqueries of the WHERE clause and those of the select list
+    filters : Vec<Subquery>,
+    fields : Vec<Subquery>
+}
+
+impl Plan<'_> { 
+    fn output_columns(&self) -> Result<ColumnInfo, QueryExecutionError> { 
+        select::project(&self.columns, &self.outputs, Vec::new()).map(|(columns, _)| columns)
+    }
+}
+
 #[derive(Clone, Serialize , Deserialize, Debug)]
 pub struct Execution { 
-    pub tables : HashMap<String, table>
+    pub tables : HashMap<String, table>,
+    // the rows of the last select over joined tables, which belong to none of the tables
+    #[serde(skip)]
+    results : BTreeMap<usize, StoredRow>,
+    // the common table expressions of the WITH query being run, read before the tables
+    #[serde(skip)]
+    views : HashMap<String, View>,
+    /// the number of times the recursive selects of a common table expression may run
+    #[serde(skip, default = "recursion_limit")]
+    pub recursion_limit : usize,
+    // the pool the pages of every table are read and changed through
+    #[serde(skip, default = "BufferPool::shared")]
+    pool : Arc<BufferPool>
+}
+
+fn recursion_limit() -> usize { 
+    cte::RECURSION_LIMIT
 }
 
 impl Execution { 
     pub fn new() -> Self { 
-        match Self::retrieve_from_json() {
+        Self::with_pool(BufferPool::shared())
+    }
+
+    /// An execution reading the pages of its tables through a buffer pool of its own with the
+    /// number of frames, instead of the pool shared by every storage
+    pub fn with_pool_frames(frames : usize) -> Self { 
+        Self::with_pool(Arc::new(BufferPool::new(frames)))
+    }
+
+    fn with_pool(pool : Arc<BufferPool>) -> Self { 
+        match Self::retrieve_from_json(&pool) {
             Ok(s) => s,
             Err(err) =>  { 
                 println!("error : {}", err);
-                Self{tables: HashMap::new()}
+                Self{tables: HashMap::new(), results: BTreeMap::new(), views: HashMap::new(), recursion_limit: cte::RECURSION_LIMIT, pool}
             },
         }
         
@@ -46,16 +100,12 @@ impl Execution {
 
     pub fn invoke_storage_metadata(&mut self)  { 
       
-        for (name, _) in self.tables.clone() { 
-            let storage = Storage::new(None, format!("{}_storage.json", name));
-       
-            let mut table = self.tables.get_mut(&name).unwrap();
-            table.storage = storage;
-           
+        for (name, table) in self.tables.iter_mut() { 
+            table.open_storage(name, self.pool.clone());
         }
     } 
 
-    fn retrieve_from_json() -> Result<Self, String>  {
+    fn retrieve_from_json(pool : &Arc<BufferPool>) -> Result<Self, String>  {
         match fs::metadata("./execution.json") {
             Ok(metadata) => { 
                 if metadata.is_file() {
@@ -65,6 +115,7 @@ impl Execution {
                             match file.read_to_string(&mut str) {
                                 Ok(_) => { 
                                     let mut s : Self = serde_json::from_str(&str).unwrap();
+                                    s.pool = pool.clone();
                                     s.invoke_storage_metadata();
                                 
                                     Ok(s)
@@ -101,7 +152,7 @@ impl Execution {
         }
     } 
 
-    pub fn parse_and_run<'a>(&mut self, query_str : &'a str) -> Result<ExecResponse, SQLError<'a>> { 
+    pub fn parse_and_run<'a>(&mut self, query_str : &'a str) -> Result<ExecResponse<'_>, SQLError<'a>> { 
         let query = parse_sql_query(&query_str);
         match query { 
             Ok(q) => { 
@@ -114,51 +165,600 @@ impl Execution {
         }
     }
 
-    pub fn run(&mut self, query : SqlQuery) -> Result<ExecResponse, QueryExecutionError> { 
+    // checks a select over joined tables against the scope of their qualified columns, `outer`
+    // being the columns of the select a subquery is nested in. A common table expression hides
+    // the table of the same name
+    fn plan(&self, select : &SelectStatementCondition, outer : Option<&ColumnInfo>) -> Result<Plan<'_>, QueryExecutionError> { 
+        let from = std::iter::once((&select.table, &select.alias))
+            .chain(select.joins.iter().map(|join| (&join.table, &join.alias)));
+        let sources = from
+            .map(|(table_name, alias)| { 
+                let relation = match self.views.get(table_name) { 
+                    Some(view) => Relation::View(view),
+                    None => Relation::Table(self.tables.get(table_name).ok_or_else(|| QueryExecutionError::TableNotFound(table_name.clone()))?),
+                };
+                Ok(Source { relation, qualifier : alias.clone().unwrap_or_else(|| table_name.clone()) })
+            })
+            .collect::<Result<Vec<_>, QueryExecutionError>>()?;
+        let mut scope = Scope::new(&sources)?;
+        if let Some(outer) = outer { 
+            scope.add_outer(outer);
+        }
+        if select.having.as_ref().is_some_and(|having| !having.subqueries().is_empty()) { 
+            return Err(QueryExecutionError::SubqueryNotAllowed("HAVING".to_string()));
+        }
+        if select.joins.iter().any(|join| !join.on.subqueries().is_empty()) { 
+            return Err(QueryExecutionError::SubqueryNotAllowed("JOIN conditions".to_string()));
+        }
+        let qualified : Vec<(&String, &ColumnInfo)> = sources.iter().map(|source| (&source.qualifier, source.columns())).collect();
+        let outputs = select::outputs(select, &qualified)?;
+
+        // the select list of a grouped select is computed from rows holding only the GROUP BY columns
+        let output = |query : &SelectStatementCondition, outer : Option<&ColumnInfo>| self.plan(query, outer)?.output_columns();
+        let filters = subquery::plan(select.where_clause.iter(), &scope.columns, &output)?;
+        let grouped_columns;
+        let field_columns = if select::is_grouped(select) { 
+            grouped_columns = ColumnInfo::new(scope.columns.iter().filter(|col| select.group_by.contains(&col.name)).cloned().collect());
+            &grouped_columns
+        } else { 
+            &scope.columns
+        };
+        let fields = subquery::plan(outputs.iter().map(|(_, expr)| expr), field_columns, &output)?;
+
+        let mut columns = scope.columns.clone();
+        columns.columns.extend(filters.iter().chain(fields.iter()).filter_map(Subquery::column).cloned());
+        select::validate(select, &columns, &outputs, &|col_name| scope.find_column(col_name).map(|_| ()))?;
+        Ok(Plan { sources, scope, columns, outputs, filters, fields })
+    }
+
+    // runs a select over joined tables, over an aliased table or view or using subqueries. A subquery is
+    // run with the columns and current row of the select it is nested in as `outer`
+    fn select_joined(&self, select : &SelectStatementCondition, outer : Option<(&ColumnInfo, &StoredRow)>) -> Result<(ColumnInfo, Vec<StoredRow>), QueryExecutionError> { 
+        let mut plan = self.plan(select, outer.map(|(columns, _)| columns))?;
+        let run = |query : &SelectStatementCondition, outer : Option<(&ColumnInfo, &StoredRow)>| self.subquery_values(query, outer);
+        for subquery in plan.filters.iter_mut().chain(plan.fields.iter_mut()) { 
+            subquery.prepare(&run)?;
+        }
+        let annotate = |subqueries : &[Subquery], mut row : StoredRow| { 
+            subqueries.iter().try_for_each(|subquery| subquery.annotate(&mut row, &run)).map(|_| row)
+        };
+
+        let outer_values = outer.map(|(_, row)| plan.scope.outer_values(row)).unwrap_or_default();
+        let mut rows : Vec<StoredRow> = plan.sources[0].scan()
+            .map(|row| { 
+                let mut values = plan.scope.qualify(0, Some(&row));
+                values.extend(outer_values.clone());
+                StoredRow::new(values)
+            })
+            .collect();
+        for (index, join) in select.joins.iter().enumerate() { 
+            rows = join::join(&plan.scope, rows, &plan.sources, index + 1, join)?;
+        }
+        if !plan.filters.is_empty() { 
+            rows = rows.into_iter().map(|row| annotate(&plan.filters, row)).collect::<Result<_, _>>()?;
+        }
+        let clause = &select.where_clause;
+        let rows = rows.into_iter().filter(|row| clause.as_ref().is_none_or(|condition| eval::matches(condition, row, functions::registry())));
+        let rows = if select::is_grouped(select) { 
+            select::group(&plan.columns, rows, select, &plan.outputs)?
+        } else if select.distinct { 
+            rows.collect()
+        } else { 
+            select::sort(rows, select)?
+        };
+        let rows : Vec<StoredRow> = rows.into_iter().map(|row| annotate(&plan.fields, row)).collect::<Result<_, _>>()?;
+        if select.distinct { 
+            return select::distinct(&plan.columns, &plan.outputs, rows.into_iter(), select);
+        }
+        select::project(&plan.columns, &plan.outputs, rows)
+    }
+
+    // the values of the first column of every row of a subquery
+    fn subquery_values(&self, query : &SelectStatementCondition, outer : Option<(&ColumnInfo, &StoredRow)>) -> Result<Vec<Value>, QueryExecutionError> { 
+        let (columns, rows) = self.select_joined(query, outer)?;
+        let Some(first) = columns.iter().next() else { 
+            return Ok(Vec::new());
+        };
+        Ok(rows.into_iter().map(|mut row| row.row.remove(&first.name).unwrap_or(Value::Null)).collect())
+    }
+
+    // whether the select is run over joined tables rather than by the table it reads
+    fn is_joined(&self, select : &SelectStatementCondition) -> bool { 
+        !select.joins.is_empty() || select.alias.is_some() || select::has_subqueries(select) || self.views.contains_key(&select.table)
+    }
+
+    // runs the select, its rows are held by the table it reads or, over joined tables, by the execution
+    fn select(&mut self, select : SelectStatementCondition) -> Result<TableIter<'_>, QueryExecutionError> { 
+        if self.is_joined(&select) { 
+            let (columns, rows) = self.select_joined(&select, None)?;
+            self.results = rows.into_iter().enumerate().collect();
+            return Ok(TableIter::new(self.results.iter(), Rc::new(columns)));
+        }
+        let table = self.tables.get_mut(&select.table).ok_or_else(|| QueryExecutionError::TableNotFound(select.table.clone()))?;
+        table.select(select)
+    }
+
+    // runs the compound, its rows are held by the execution like those of a select over joined tables
+    fn compound(&mut self, compound : CompoundSelect) -> Result<TableIter<'_>, QueryExecutionError> { 
+        let (columns, rows) = self.compound_rows(&compound)?;
+        self.results = rows.into_iter().enumerate().collect();
+        Ok(TableIter::new(self.results.iter(), Rc::new(columns)))
+    }
+
+    // runs every select of the compound, combining their rows from left to right before ordering
+    // and limiting them
+    fn compound_rows(&self, compound : &CompoundSelect) -> Result<(ColumnInfo, Vec<StoredRow>), QueryExecutionError> { 
+        if compound.rest.is_empty() { 
+            return self.select_joined(&compound.first, None);
+        }
+        let (mut columns, rows) = self.select_joined(&compound.first, None)?;
+        let mut rows : Box<dyn Iterator<Item = Vec<Value>>> = Box::new(set_operation::values(&columns, rows));
+        for (op, select) in compound.rest.iter() { 
+            let (select_columns, select_rows) = self.select_joined(select, None)?;
+            columns = set_operation::columns(*op, &columns, &select_columns)?;
+            rows = set_operation::combine(*op, rows, set_operation::values(&select_columns, select_rows));
+        }
+        for key in compound.order_by.iter() { 
+            columns.find_column(&key.column)?;
+        }
+        let names : Vec<String> = columns.iter().map(|col| col.name.clone()).collect();
+        let rows = rows.map(|values| StoredRow::new(names.iter().cloned().zip(values).collect()));
+        let rows = select::sort_by(rows, &compound.order_by, compound.offset, compound.limit)?;
+        Ok((columns, rows))
+    }
+
+    // runs the select of a WITH query, its common table expressions computed in order beforehand
+    // as views only visible to the expressions after them and to the select
+    fn with(&mut self, with : WithQuery) -> Result<TableIter<'_>, QueryExecutionError> { 
+        let result = self.with_rows(&with);
+        self.views.clear();
+        let (columns, rows) = result?;
+        self.results = rows.into_iter().enumerate().collect();
+        Ok(TableIter::new(self.results.iter(), Rc::new(columns)))
+    }
+
+    fn with_rows(&mut self, with : &WithQuery) -> Result<(ColumnInfo, Vec<StoredRow>), QueryExecutionError> { 
+        for cte in with.ctes.iter() { 
+            if self.views.contains_key(&cte.name) { 
+                return Err(QueryExecutionError::DuplicateTableName(cte.name.clone()));
+            }
+            let view = if with.recursive && cte::is_recursive(cte) { 
+                self.recursive_view(cte)?
+            } else { 
+                let (columns, rows) = self.compound_rows(&cte.query)?;
+                View::new(cte, columns, rows)?
+            };
+            self.views.insert(cte.name.clone(), view);
+        }
+        self.compound_rows(&with.query)
+    }
+
+    // computes a recursive common table expression with a working table. The first select gives
+    // the first rows, then the selects after it are run over the rows the last iteration added,
+    // seen as the expression's rows, until an iteration adds none. UNION drops the rows produced
+    // before, which is what ends most recursions, while UNION ALL keeps them. The view is left in
+    // place on failure, the views being dropped once the query ends
+    fn recursive_view(&mut self, cte : &CommonTableExpr) -> Result<View, QueryExecutionError> { 
+        let all = cte::recursion(cte)?;
+        let (columns, rows) = self.select_joined(&cte.query.first, None)?;
+        let anchor = View::new(cte, columns, rows)?;
+        let columns = anchor.columns;
+        let mut seen : HashSet<Vec<Value>> = HashSet::new();
+        let mut result : Vec<Vec<Value>> = set_operation::values(&columns, anchor.rows)
+            .filter(|row| all || seen.insert(row.clone()))
+            .collect();
+        let mut working = result.clone();
+        let mut iterations = 0;
+        while !working.is_empty() { 
+            if iterations == self.recursion_limit { 
+                return Err(QueryExecutionError::RecursionLimit(cte.name.clone(), self.recursion_limit));
+            }
+            iterations += 1;
+            self.views.insert(cte.name.clone(), View::from_values(columns.clone(), working));
+            let mut added = Vec::new();
+            for (op, select) in cte.query.rest.iter() { 
+                let (select_columns, rows) = self.select_joined(select, None)?;
+                set_operation::columns(*op, &columns, &select_columns)?;
+                added.extend(set_operation::values(&select_columns, rows).filter(|row| all || seen.insert(row.clone())));
+            }
+            result.extend(added.iter().cloned());
+            working = added;
+        }
+        self.views.remove(&cte.name);
+        Ok(View::from_values(columns, result))
+    }
+
+    // the output columns of the select and the values of its rows. The rows of a select the table
+    // it reads can stream are read as they are taken, those of any other select are computed first
+    fn select_values(&mut self, select : SelectStatementCondition) -> Result<(ColumnInfo, OutputRows<'_>), QueryExecutionError> { 
+        let streamed = !self.is_joined(&select) && self.tables.get(&select.table).is_some_and(|table| table.is_streamed(&select));
+        if streamed { 
+            let (columns, rows) = self.tables[&select.table].stream(select)?;
+            return Ok((columns, Box::new(rows)));
+        }
+        let rows = self.select(select)?;
+        Ok((rows.columns().clone(), Box::new(rows.map(|row| Ok(row.values())))))
+    }
+
+    // inserts the output rows of the select, feeding them to the table as they are read. A select
+    // reading the table it inserts into is run to completion first, so it never sees its own rows
+    fn insert_select(&mut self, table_name : String, columns : Vec<String>, select : SelectStatementCondition, on_conflict : Option<OnConflict>) -> Result<usize, QueryExecutionError> { 
+        let reads_target = select::tables(&select).contains(&&table_name);
+        if reads_target { 
+            let rows : Vec<Vec<Value>> = self.select(select)?.map(|row| row.values()).collect();
+            let table = self.tables.get_mut(&table_name).ok_or(QueryExecutionError::TableNotFound(table_name))?;
+            return table.insert(columns, rows.into_iter().map(Ok), on_conflict);
+        }
+        let mut table = self.tables.remove(&table_name).ok_or_else(|| QueryExecutionError::TableNotFound(table_name.clone()))?;
+        let inserted = self.select_values(select).and_then(|(_, rows)| table.insert(columns, rows, on_conflict));
+        self.tables.insert(table_name, table);
+        inserted
+    }
+
+    // creates the table from the output columns of the select and inserts its rows as they are read
+    fn create_as(&mut self, table_name : String, select : SelectStatementCondition) -> Result<(), QueryExecutionError> { 
+        if self.tables.contains_key(&table_name) { 
+            return Err(QueryExecutionError::TableAlreadyExists(table_name));
+        }
+        let pool = self.pool.clone();
+        let (columns, rows) = self.select_values(select)?;
+        // the output columns, without the constraints of the table columns they were read from
+        let columns = columns.iter().map(|col| Column { constraints : Vec::new(), ..col.clone() }).collect();
+        let mut table = table::from_select(table_name.clone(), ColumnInfo::new(columns), pool);
+        if let Err(err) = table.insert(Vec::new(), rows, None) { 
+            table.drop_storage()?;
+            return Err(err);
+        }
+        self.tables.insert(table_name, table);
+        Ok(())
+    }
+
+    pub fn run(&mut self, query : SqlQuery) -> Result<ExecResponse<'_>, QueryExecutionError> { 
         match query {
-            SqlQuery::Select(mut select) =>  {
-              
-                let table_name = select.table;
-                let  mut table = self.tables.get_mut(&table_name).ok_or(QueryExecutionError::TableNotFound(table_name));
-                match table { 
-                    Ok(mut t) => { 
-                    
-                        let s = t.select(select.fields.clone(), select.where_clause.clone()).unwrap();
-                        //let rows : Vec<Row>  = t.iter().collect();
-                        Ok(ExecResponse::Select(t.select(select.fields, select.where_clause)?))
-            
-                    },
-                    Err(err) => Err(err)
-                }
-                },
+            SqlQuery::Select(select) => Ok(ExecResponse::Select(self.select(select)?)),
+            SqlQuery::Compound(compound) => Ok(ExecResponse::Select(self.compound(compound)?)),
+            SqlQuery::With(with) => Ok(ExecResponse::Select(self.with(with)?)),
             SqlQuery::Insert(insert) => {
-                println!("in insert");
-                let Some(table) = self.tables.get_mut(&insert.table) else { 
-                    return Err(QueryExecutionError::TableNotFound(insert.table))
+                let rows_affected = match insert.source { 
+                    InsertSource::Values(values) => { 
+                        let Some(table) = self.tables.get_mut(&insert.table) else { 
+                            return Err(QueryExecutionError::TableNotFound(insert.table))
+                        };
+                        table.insert(insert.columns, values.into_iter().map(Ok), insert.on_conflict)?
+                    },
+                    InsertSource::Select(select) => self.insert_select(insert.table, insert.columns, *select, insert.on_conflict)?,
                 };
-                let values : Vec<String>= insert.values.iter().map(|val| val.to_string()).collect();
-                table.insert(insert.values);
-                Ok(ExecResponse::Insert)
+                Ok(ExecResponse::Insert { rows_affected })
+            },
+            SqlQuery::Create(CreateStatement { table : table_name, as_select : Some(select), .. }) => { 
+                self.create_as(table_name, *select)?;
+                self.save_to_json().map_err(QueryExecutionError::StorageError)?;
+                Ok(ExecResponse::Crete)
             },
             SqlQuery::Create(create) => { 
-                let columns  = create.columns;
-                let primary_key = columns[0].clone().name;
-                let mut prim_key_type: key_type;
-                if columns[0].type_info == SqlTypeInfo::String { 
-                    prim_key_type = key_type::Strings;
-                } else  { 
-                    prim_key_type = key_type::Number
-                }
-                let table_metadata = TableMetaData::new(create.table.clone(), primary_key, prim_key_type);
-                let table = table::new(ColumnInfo::new(columns), table_metadata);
-                self.tables.insert(create.table, table);
+                let table_name = create.table.clone();
+                let table = table::from_create(create, self.pool.clone())?;
+                self.tables.insert(table_name, table);
                 match self.save_to_json() {
                     Ok(_) => println!("execiton state saved to disk"),
                     Err(err) => println!("error saving to execution state {}", err),
                 }
                 Ok(ExecResponse::Crete)
             },
+            SqlQuery::Delete(delete) => { 
+                let Some(table) = self.tables.get_mut(&delete.table) else { 
+                    return Err(QueryExecutionError::TableNotFound(delete.table))
+                };
+                let rows_affected = table.delete(delete.where_clause)?;
+                Ok(ExecResponse::Delete { rows_affected })
+            },
+            SqlQuery::Update(update) => { 
+                let Some(table) = self.tables.get_mut(&update.table) else { 
+                    return Err(QueryExecutionError::TableNotFound(update.table))
+                };
+                let rows_affected = table.update(update.assignments, update.where_clause)?;
+                Ok(ExecResponse::Update { rows_affected })
+            },
+            SqlQuery::Drop(drop) => { 
+                let Some(table) = self.tables.remove(&drop.table) else { 
+                    if drop.if_exists { 
+                        return Ok(ExecResponse::Drop);
+                    }
+                    return Err(QueryExecutionError::TableNotFound(drop.table))
+                };
+                let saved = self.save_to_json().map_err(QueryExecutionError::StorageError);
+                table.drop_storage()?;
+                saved?;
+                Ok(ExecResponse::Drop)
+            },
+            SqlQuery::Truncate(truncate) => { 
+                let Some(table) = self.tables.get_mut(&truncate.table) else { 
+                    return Err(QueryExecutionError::TableNotFound(truncate.table))
+                };
+                let rows_affected = table.delete(None)?;
+                Ok(ExecResponse::Truncate { rows_affected })
+            },
+            SqlQuery::Alter(alter) => { 
+                if let AlterAction::RenameTable(table_name) = &alter.action { 
+                    if self.tables.contains_key(table_name) { 
+                        return Err(QueryExecutionError::TableAlreadyExists(table_name.clone()))
+                    }
+                }
+                let Some(table) = self.tables.get_mut(&alter.table) else { 
+                    return Err(QueryExecutionError::TableNotFound(alter.table))
+                };
+                table.alter(alter.action.clone())?;
+                if let AlterAction::RenameTable(table_name) = alter.action { 
+                    if let Some(table) = self.tables.remove(&alter.table) { 
+                        self.tables.insert(table_name, table);
+                    }
+                }
+                self.save_to_json().map_err(QueryExecutionError::StorageError)?;
+                Ok(ExecResponse::Alter)
+            },
         }
     } 
 }
 
+
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::PathBuf, sync::{Mutex, MutexGuard}};
+
+    use bigdecimal::BigDecimal;
+    use sql_one_parser::value::Value;
+    use tempfile::TempDir;
+
+    use crate::{error::{QueryExecutionError, SQLError}, table::INSERT_BATCH_ROWS};
+
+    use super::{ExecResponse, Execution};
+
+    // an execution keeps its state and its tables in files of the working directory, which the
+    // tests take turns to change
+    static WORKING_DIR : Mutex<()> = Mutex::new(());
+
+    // an execution run in an empty directory of its own, removed along with every file of the
+    // execution when dropped
+    struct Scratch { 
+        exec : Execution,
+        previous : PathBuf,
+        _dir : TempDir,
+        _lock : MutexGuard<'static, ()>
+    }
+
+    impl Scratch { 
+        fn new() -> Self { 
+            let lock = WORKING_DIR.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let previous = env::current_dir().unwrap();
+            let dir = tempfile::tempdir().unwrap();
+            env::set_current_dir(dir.path()).unwrap();
+            Self { exec : Execution::with_pool_frames(16), previous, _dir : dir, _lock : lock }
+        }
+
+        fn run(&mut self, sql : &str) { 
+            if let Err(err) = self.exec.parse_and_run(sql) { 
+                panic!("{} failed : {:?}", sql, err);
+            }
+        }
+
+        // the values of the rows the select gives
+        fn rows(&mut self, sql : &str) -> Vec<Vec<Value>> { 
+            match self.exec.parse_and_run(sql) { 
+                Ok(ExecResponse::Select(rows)) => rows.map(|row| row.values()).collect(),
+                other => panic!("{} did not select rows : {:?}", sql, other),
+            }
+        }
+
+        fn error(&mut self, sql : &str) -> QueryExecutionError { 
+            match self.exec.parse_and_run(sql) { 
+                Err(SQLError::QueryExecutionError(err)) => err,
+                other => panic!("{} did not fail to run : {:?}", sql, other),
+            }
+        }
+    }
+
+    impl Drop for Scratch { 
+        fn drop(&mut self) { 
+            env::set_current_dir(&self.previous).ok();
+        }
+    }
+
+    fn num(n : i64) -> Value { 
+        Value::Number(BigDecimal::from(n))
+    }
+
+    fn text(s : &str) -> Value { 
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn test_constraints_and_on_conflict() { 
+        let mut db = Scratch::new();
+        db.run("create table exectestusers (id int primary key, email string unique not null, visits int default 0);");
+        db.run("insert into exectestusers (id, email) values (1, 'a@x'), (2, 'b@x');");
+
+        assert!(matches!(db.error("insert into exectestusers (id, email) values (1, 'c@x');"), QueryExecutionError::PrimaryKeyViolation(..)));
+        assert!(matches!(db.error("insert into exectestusers (id, email) values (3, 'a@x');"), QueryExecutionError::UniqueViolation(..)));
+        assert!(matches!(db.error("insert into exectestusers (id) values (3);"), QueryExecutionError::NotNullViolation(..)));
+        // a failing row keeps every row of the statement out
+        assert!(matches!(db.error("insert into exectestusers values (3, 'c@x', 0), (4, 'a@x', 0);"), QueryExecutionError::UniqueViolation(..)));
+        assert!(matches!(db.error("update exectestusers set email = 'a@x' where id = 2;"), QueryExecutionError::UniqueViolation(..)));
+
+        db.run("insert into exectestusers values (1, 'a@x', 5) on conflict (id) do update set visits = excluded.visits;");
+        db.run("insert into exectestusers values (2, 'z@x', 9) on conflict do nothing;");
+        assert!(matches!(db.error("insert into exectestusers values (2, 'z@x', 9) on conflict (email) do nothing;"), QueryExecutionError::InvalidConflictTarget(..)));
+        assert_eq!(db.rows("select id, email, visits from exectestusers order by id;"), vec![
+            vec![num(1), text("a@x"), num(5)],
+            vec![num(2), text("b@x"), num(0)],
+        ]);
+
+        // the rows may take each other's keys within one statement
+        db.run("update exectestusers set id = id + 1;");
+        assert_eq!(db.rows("select id from exectestusers order by id;"), vec![vec![num(2)], vec![num(3)]]);
+    }
+
+    #[test]
+    fn test_join_operators() { 
+        let mut db = Scratch::new();
+        db.run("create table exectestemp (id int primary key, name string, dept int);");
+        db.run("create table exectestdept (id int primary key, name string);");
+        for row in ["1, 'ann', 10", "2, 'bob', 20", "3, 'cid', null"] { 
+            db.run(&format!("insert into exectestemp values {};", row));
+        }
+        for row in ["10, 'eng'", "20, 'ops'", "30, 'hr'"] { 
+            db.run(&format!("insert into exectestdept values {};", row));
+        }
+
+        // looked up by the primary key of the joined table
+        assert_eq!(db.rows("select e.name, d.name from exectestemp as e join exectestdept as d on e.dept = d.id order by e.name;"), vec![
+            vec![text("ann"), text("eng")],
+            vec![text("bob"), text("ops")],
+        ]);
+        // hashed on a column that is not the key of the joined table
+        assert_eq!(db.rows("select d.name, e.name from exectestdept as d join exectestemp as e on d.id = e.dept order by d.name;"), vec![
+            vec![text("eng"), text("ann")],
+            vec![text("ops"), text("bob")],
+        ]);
+        // compared row by row
+        assert_eq!(db.rows("select e.name, d.name from exectestemp as e join exectestdept as d on e.dept < d.id order by e.name, d.name;"), vec![
+            vec![text("ann"), text("hr")],
+            vec![text("ann"), text("ops")],
+            vec![text("bob"), text("hr")],
+        ]);
+        assert_eq!(db.rows("select d.name, e.name from exectestdept as d left join exectestemp as e on d.id = e.dept order by d.name;"), vec![
+            vec![text("eng"), text("ann")],
+            vec![text("hr"), Value::Null],
+            vec![text("ops"), text("bob")],
+        ]);
+        assert!(matches!(db.error("select name from exectestemp as e join exectestdept as d on e.dept = d.id;"), QueryExecutionError::AmbiguousColumn(..)));
+    }
+
+    #[test]
+    fn test_hash_aggregate() { 
+        let mut db = Scratch::new();
+        db.run("create table exectestsales (id int primary key, region string, amount int);");
+        for row in ["1, 'n', 10", "2, 's', 5", "3, 'n', 20", "4, 's', null", "5, 'e', 7"] { 
+            db.run(&format!("insert into exectestsales values {};", row));
+        }
+
+        assert_eq!(db.rows("select region, count(*), count(amount), sum(amount), max(amount) from exectestsales group by region having count(*) > 1 order by region;"), vec![
+            vec![text("n"), num(2), num(2), num(30), num(20)],
+            vec![text("s"), num(2), num(1), num(5), num(5)],
+        ]);
+        assert_eq!(db.rows("select count(*), avg(amount) from exectestsales;"), vec![
+            vec![num(5), Value::Number("10.5".parse().unwrap())],
+        ]);
+        assert!(matches!(db.error("select region, amount from exectestsales group by region;"), QueryExecutionError::NotGrouped(..)));
+        assert!(matches!(db.error("select id from exectestsales where sum(amount) > 1;"), QueryExecutionError::AggregateNotAllowed(..)));
+    }
+
+    #[test]
+    fn test_insert_select_and_create_as() { 
+        let mut db = Scratch::new();
+        db.run("create table exectestsrc (id int primary key, name string);");
+        db.run("insert into exectestsrc values (1, 'a'), (2, 'b'), (3, 'c'), (4, 'b'), (5, null);");
+
+        db.run("create table exectestcopy as select name, id * 10 as big from exectestsrc where id > 3;");
+        assert_eq!(db.rows("select name, big from exectestcopy order by big;"), vec![
+            vec![text("b"), num(40)],
+            vec![Value::Null, num(50)],
+        ]);
+        // the first column may hold the same value, or NULL, in several rows
+        db.run("create table exectestnames as select name from exectestsrc;");
+        assert_eq!(db.rows("select count(*), count(name) from exectestnames;"), vec![vec![num(5), num(4)]]);
+        assert!(matches!(db.error("create table exectestnames as select id from exectestsrc;"), QueryExecutionError::TableAlreadyExists(..)));
+
+        db.run("insert into exectestcopy select name, id from exectestsrc where id = 1;");
+        assert_eq!(db.rows("select count(*) from exectestcopy;"), vec![vec![num(3)]]);
+        // the row written before the duplicate key is taken back
+        assert!(matches!(db.error("insert into exectestsrc select 7 - id, name from exectestsrc;"), QueryExecutionError::PrimaryKeyViolation(..)));
+        assert_eq!(db.rows("select id from exectestsrc where id > 5;"), Vec::<Vec<Value>>::new());
+        assert!(matches!(db.error("insert into exectestsrc (id) select big, name from exectestcopy;"), QueryExecutionError::ValueCountMismatch(..)));
+    }
+
+    #[test]
+    fn test_insert_select_rolls_back_written_batches() { 
+        let mut db = Scratch::new();
+        let rows = 2 * INSERT_BATCH_ROWS + 500;
+        db.run("create table exectestmany (id int primary key);");
+        db.run(&format!("insert into exectestmany values {};", (1..=rows).map(|id| format!("({})", id)).collect::<Vec<_>>().join(", ")));
+        db.run("create table exectesttarget (id int primary key);");
+        db.run(&format!("insert into exectesttarget values ({});", rows - 100));
+
+        // the duplicate key is read after two batches were written
+        assert!(matches!(db.error("insert into exectesttarget select id from exectestmany;"), QueryExecutionError::PrimaryKeyViolation(..)));
+        assert_eq!(db.rows("select count(*) from exectesttarget;"), vec![vec![num(1)]]);
+        db.run("insert into exectesttarget select id from exectestmany on conflict do nothing;");
+        assert_eq!(db.rows("select count(*), min(id), max(id) from exectesttarget;"), vec![vec![num(rows as i64), num(1), num(rows as i64)]]);
+    }
+
+    #[test]
+    fn test_function_errors() { 
+        let mut db = Scratch::new();
+        db.run("create table exectestwords (id int primary key, word string);");
+        db.run("insert into exectestwords values (1, ' one '), (2, null);");
+
+        assert_eq!(db.rows("select upper(trim(word)), length(word), coalesce(word, 'none') from exectestwords order by id;"), vec![
+            vec![text("ONE"), num(5), text(" one ")],
+            vec![Value::Null, Value::Null, text("none")],
+        ]);
+        assert!(matches!(db.error("select upper(word, id) from exectestwords;"), QueryExecutionError::FunctionArity { .. }));
+        assert!(matches!(db.error("select upper(id) from exectestwords;"), QueryExecutionError::FunctionArgument { .. }));
+        assert!(matches!(db.error("select shout(word) from exectestwords;"), QueryExecutionError::UnknownFunction { .. }));
+        assert!(matches!(db.error("select id from exectestwords where abs(word) > 1;"), QueryExecutionError::FunctionArgument { .. }));
+    }
+
+    #[test]
+    fn test_correlated_subqueries() { 
+        let mut db = Scratch::new();
+        db.run("create table execteststaff (id int primary key, name string, team int);");
+        db.run("create table exectestteams (id int primary key, name string);");
+        db.run("insert into execteststaff values (1, 'ann', 10), (2, 'bob', 10), (3, 'cid', 20);");
+        db.run("insert into exectestteams values (10, 'eng'), (20, 'ops'), (30, 'hr');");
+
+        assert_eq!(db.rows("select t.name from exectestteams as t where exists (select * from execteststaff as s where s.team = t.id) order by t.name;"), vec![
+            vec![text("eng")],
+            vec![text("ops")],
+        ]);
+        assert_eq!(db.rows("select t.name, (select count(*) from execteststaff as s where s.team = t.id) as size from exectestteams as t order by t.name;"), vec![
+            vec![text("eng"), num(2)],
+            vec![text("hr"), num(0)],
+            vec![text("ops"), num(1)],
+        ]);
+        assert_eq!(db.rows("select name from execteststaff where team in (select id from exectestteams where name = 'ops');"), vec![vec![text("cid")]]);
+        assert!(matches!(db.error("select name from execteststaff where team = (select id from exectestteams);"), QueryExecutionError::SubqueryRows(..)));
+        assert!(matches!(db.error("select name from execteststaff where team in (select id, name from exectestteams);"), QueryExecutionError::SubqueryColumns(..)));
+    }
+
+    #[test]
+    fn test_set_operations() { 
+        let mut db = Scratch::new();
+        db.run("create table exectestleft (id int primary key, n int, label string);");
+        db.run("create table exectestright (id int primary key, n int);");
+        db.run("insert into exectestleft values (1, 1, 'a'), (2, 2, 'b'), (3, 3, 'c');");
+        db.run("insert into exectestright values (1, 2), (2, 3), (3, 4), (4, 4);");
+
+        let column = |values : &[i64]| values.iter().map(|n| vec![num(*n)]).collect::<Vec<_>>();
+        assert_eq!(db.rows("select n from exectestleft union select n from exectestright order by n;"), column(&[1, 2, 3, 4]));
+        assert_eq!(db.rows("select n from exectestleft union all select n from exectestright order by n;"), column(&[1, 2, 2, 3, 3, 4, 4]));
+        assert_eq!(db.rows("select n from exectestleft intersect select n from exectestright order by n;"), column(&[2, 3]));
+        assert_eq!(db.rows("select n from exectestright except select n from exectestleft;"), column(&[4]));
+        assert!(matches!(db.error("select id, n from exectestleft union select n from exectestright;"), QueryExecutionError::SetOperationColumns(..)));
+        assert!(matches!(db.error("select label from exectestleft union select n from exectestright;"), QueryExecutionError::SetOperationTypes(..)));
+    }
+
+    #[test]
+    fn test_recursion_limit() { 
+        let mut db = Scratch::new();
+        db.run("create table exectestseed (id int primary key);");
+        db.run("insert into exectestseed values (1);");
+
+        let counter = |limit : &str| format!("with recursive counter (n) as (select id from exectestseed union all select n + 1 from counter{}) select n from counter order by n;", limit);
+        assert_eq!(db.rows(&counter(" where n < 5")), (1..=5).map(|n| vec![num(n)]).collect::<Vec<_>>());
+        db.exec.recursion_limit = 10;
+        assert!(matches!(db.error(&counter("")), QueryExecutionError::RecursionLimit(name, 10) if name == "counter"));
+        // UNION ends the recursion once no new rows are found
+        assert_eq!(db.rows("with recursive cycle (n) as (select id from exectestseed union select 1 - n from cycle) select n from cycle order by n;"), vec![vec![num(0)], vec![num(1)]]);
+    }
+}
@@ -0,0 +1,275 @@
+use std::{collections::HashMap, fmt, sync::LazyLock};
+
+use bigdecimal::{BigDecimal, RoundingMode, ToPrimitive, Zero};
+use sql_one_flexi_engine::eval;
+use sql_one_parser::{commands::create::SqlTypeInfo, value::Value};
+
+use crate::error::QueryExecutionError;
+
+/// The kind of value a function parameter accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Text,
+    Number,
+    Any
+}
+
+impl Kind {
+    // whether an argument of the given type can be passed, NULL is accepted by every kind
+    fn accepts(&self, type_info : Option<&SqlTypeInfo>) -> bool {
+        match (self, type_info) {
+            (Kind::Any, _) | (_, None) => true,
+            (Kind::Text, Some(type_info)) => matches!(type_info, SqlTypeInfo::String | SqlTypeInfo::Varchar(_)),
+            (Kind::Number, Some(type_info)) => matches!(type_info, SqlTypeInfo::Int | SqlTypeInfo::Float),
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Text => write!(f, "text"),
+            Kind::Number => write!(f, "number"),
+            Kind::Any => write!(f, "any"),
+        }
+    }
+}
+
+/// A scalar function callable from expressions. The last `optional` parameters may be left out
+/// and a variadic function repeats its last parameter. A strict function gives NULL as soon as one
+/// of its arguments is NULL, without being called
+#[derive(Clone)]
+pub struct Function {
+    pub name : &'static str,
+    params : &'static [Kind],
+    optional : usize,
+    variadic : bool,
+    strict : bool,
+    // the type of the result for the types of the arguments, None for a NULL literal.
+    // None when the arguments do not go together
+    returns : fn(&[Option<SqlTypeInfo>]) -> Option<SqlTypeInfo>,
+    // None when the arguments can not be computed with, e.g. a string passed for a number
+    call : fn(&[Value]) -> Option<Value>
+}
+
+impl Function {
+    pub const fn new(name : &'static str, params : &'static [Kind], returns : fn(&[Option<SqlTypeInfo>]) -> Option<SqlTypeInfo>, call : fn(&[Value]) -> Option<Value>) -> Self {
+        Self { name, params, optional : 0, variadic : false, strict : true, returns, call }
+    }
+
+    pub const fn optional(self, optional : usize) -> Self {
+        Self { optional, ..self }
+    }
+
+    pub const fn variadic(self) -> Self {
+        Self { variadic : true, ..self }
+    }
+
+    /// Lets the function see NULL arguments instead of giving NULL for them
+    pub const fn lenient(self) -> Self {
+        Self { strict : false, ..self }
+    }
+
+    /// How the function is called, e.g. `SUBSTR(text, number[, number])`
+    pub fn signature(&self) -> String {
+        let required = self.params.len() - self.optional;
+        let mut signature = self.params[..required].iter().map(Kind::to_string).collect::<Vec<_>>().join(", ");
+        for param in self.params[required..].iter() {
+            signature.push_str(&format!("[, {param}]"));
+        }
+        if self.variadic {
+            signature.push_str(", ...");
+        }
+        format!("{}({})", self.name, signature)
+    }
+
+    // the number of arguments the function takes, e.g. `2 or 3`
+    fn arity(&self) -> String {
+        let required = self.params.len() - self.optional;
+        match (self.variadic, self.optional) {
+            (true, _) => format!("at least {required}"),
+            (false, 0) => required.to_string(),
+            (false, _) => format!("{required} to {}", self.params.len()),
+        }
+    }
+
+    fn accepts_count(&self, given : usize) -> bool {
+        given + self.optional >= self.params.len() && (self.variadic || given <= self.params.len())
+    }
+
+    fn param(&self, index : usize) -> Kind {
+        self.params.get(index).or(self.params.last()).copied().unwrap_or(Kind::Any)
+    }
+}
+
+/// The scalar functions expressions can call, looked up by their upper cased name
+pub struct FunctionRegistry {
+    functions : HashMap<&'static str, Function>
+}
+
+impl FunctionRegistry {
+    /// A registry holding the built in string, numeric and NULL handling functions
+    pub fn builtin() -> Self {
+        let mut registry = Self { functions : HashMap::new() };
+        let builtin = [
+            Function::new("UPPER", &[Kind::Text], |_| Some(SqlTypeInfo::String), |args| Some(Value::String(text(&args[0])?.to_uppercase()))),
+            Function::new("LOWER", &[Kind::Text], |_| Some(SqlTypeInfo::String), |args| Some(Value::String(text(&args[0])?.to_lowercase()))),
+            Function::new("LENGTH", &[Kind::Text], |_| Some(SqlTypeInfo::Int), |args| Some(Value::Number(BigDecimal::from(text(&args[0])?.chars().count() as u64)))),
+            Function::new("TRIM", &[Kind::Text], |_| Some(SqlTypeInfo::String), |args| Some(Value::String(text(&args[0])?.trim_matches(' ').to_string()))),
+            Function::new("SUBSTR", &[Kind::Text, Kind::Number, Kind::Number], |_| Some(SqlTypeInfo::String), substr).optional(1),
+            Function::new("CONCAT", &[Kind::Any], |_| Some(SqlTypeInfo::String), concat).variadic().lenient(),
+            Function::new("ABS", &[Kind::Number], same_number, |args| Some(Value::Number(number(&args[0])?.abs()))),
+            Function::new("ROUND", &[Kind::Number, Kind::Number], same_number, round).optional(1),
+            Function::new("CEIL", &[Kind::Number], same_number, |args| Some(Value::Number(number(&args[0])?.with_scale_round(0, RoundingMode::Ceiling)))),
+            Function::new("FLOOR", &[Kind::Number], same_number, |args| Some(Value::Number(number(&args[0])?.with_scale_round(0, RoundingMode::Floor)))),
+            Function::new("MOD", &[Kind::Number, Kind::Number], modulo_type, modulo),
+            Function::new("COALESCE", &[Kind::Any], common, |args| Some(args.iter().find(|arg| !arg.is_null()).cloned().unwrap_or(Value::Null))).variadic().lenient(),
+            Function::new("NULLIF", &[Kind::Any, Kind::Any], common, |args| Some(if args[0] == args[1] { Value::Null } else { args[0].clone() })).lenient(),
+        ];
+        for function in builtin {
+            registry.register(function);
+        }
+        registry
+    }
+
+    pub fn register(&mut self, function : Function) {
+        self.functions.insert(function.name, function);
+    }
+
+    pub fn get(&self, name : &str) -> Result<&Function, QueryExecutionError> {
+        self.functions.get(name).ok_or_else(|| {
+            let mut known : Vec<&str> = self.functions.keys().copied().collect();
+            known.sort();
+            QueryExecutionError::UnknownFunction { name : name.to_string(), known : known.join(", ") }
+        })
+    }
+
+    /// Checks the function can be called with arguments of the given types, None standing for a
+    /// NULL literal, giving the type of its result
+    pub fn check(&self, name : &str, args : &[Option<SqlTypeInfo>]) -> Result<SqlTypeInfo, QueryExecutionError> {
+        let function = self.get(name)?;
+        if !function.accepts_count(args.len()) {
+            return Err(QueryExecutionError::FunctionArity {
+                name : name.to_string(), expected : function.arity(), given : args.len(), signature : function.signature()
+            });
+        }
+        let accepted = args.iter().enumerate().all(|(index, arg)| function.param(index).accepts(arg.as_ref()));
+        match (function.returns)(args) {
+            Some(type_info) if accepted => Ok(type_info),
+            _ => Err(QueryExecutionError::FunctionArgument {
+                name : name.to_string(),
+                types : args.iter().map(|arg| arg.as_ref().map_or_else(|| "NULL".to_string(), SqlTypeInfo::to_string)).collect::<Vec<_>>().join(", "),
+                signature : function.signature()
+            }),
+        }
+    }
+
+    /// Calls the function on evaluated arguments, None when it does not exist or can not compute them
+    pub fn call(&self, name : &str, args : Vec<Value>) -> Option<Value> {
+        let function = self.functions.get(name)?;
+        if !function.accepts_count(args.len()) {
+            return None;
+        }
+        if function.strict && args.iter().any(Value::is_null) {
+            return Some(Value::Null);
+        }
+        (function.call)(&args)
+    }
+}
+
+static REGISTRY : LazyLock<FunctionRegistry> = LazyLock::new(FunctionRegistry::builtin);
+
+/// The registry every expression calls its functions from
+pub fn registry() -> &'static FunctionRegistry {
+    &REGISTRY
+}
+
+// the engine's expression evaluation calls the registry's functions
+impl eval::Functions for FunctionRegistry {
+    fn call(&self, name : &str, args : Vec<Value>) -> Option<Value> {
+        FunctionRegistry::call(self, name, args)
+    }
+}
+
+fn text(value : &Value) -> Option<&str> {
+    match value {
+        Value::String(str) => Some(str),
+        _ => None
+    }
+}
+
+fn number(value : &Value) -> Option<&BigDecimal> {
+    match value {
+        Value::Number(number) => Some(number),
+        _ => None
+    }
+}
+
+fn integer(value : &Value) -> Option<i64> {
+    number(value)?.to_i64()
+}
+
+// the characters from the 1-based start, up to the given count of positions past it. Positions
+// before the first character count towards the length without giving any character
+fn substr(args : &[Value]) -> Option<Value> {
+    let str = text(&args[0])?;
+    let start = integer(&args[1])?;
+    let end = match args.get(2) {
+        Some(length) => start.saturating_add(integer(length).filter(|length| *length >= 0)?),
+        None => i64::MAX,
+    };
+    let from = start.max(1);
+    let count = end.saturating_sub(from).max(0);
+    Some(Value::String(str.chars().skip(from as usize - 1).take(count.try_into().unwrap_or(usize::MAX)).collect()))
+}
+
+// the text of every argument that is not NULL
+fn concat(args : &[Value]) -> Option<Value> {
+    Some(Value::String(args.iter().filter(|arg| !arg.is_null()).map(Value::to_string).collect()))
+}
+
+// rounds half away from zero to the given number of decimal places, which may be negative
+fn round(args : &[Value]) -> Option<Value> {
+    let places = args.get(1).map_or(Some(0), integer)?;
+    let rounded = number(&args[0])?.with_scale_round(places, RoundingMode::HalfUp);
+    Some(Value::Number(rounded.with_scale(places.max(0))))
+}
+
+// the remainder takes the sign of the dividend, dividing by zero gives NULL
+fn modulo(args : &[Value]) -> Option<Value> {
+    let (left, right) = (number(&args[0])?, number(&args[1])?);
+    if right.is_zero() {
+        return Some(Value::Null);
+    }
+    Some(Value::Number(left % right))
+}
+
+fn same_number(args : &[Option<SqlTypeInfo>]) -> Option<SqlTypeInfo> {
+    Some(args[0].clone().unwrap_or(SqlTypeInfo::Float))
+}
+
+fn modulo_type(args : &[Option<SqlTypeInfo>]) -> Option<SqlTypeInfo> {
+    match (&args[0], &args[1]) {
+        (Some(SqlTypeInfo::Int), Some(SqlTypeInfo::Int)) => Some(SqlTypeInfo::Int),
+        _ => Some(SqlTypeInfo::Float),
+    }
+}
+
+// the type every argument can be read as, numbers widening to floats. NULL literals go with any
+// type and only NULLs are typed like a NULL literal column
+fn common(args : &[Option<SqlTypeInfo>]) -> Option<SqlTypeInfo> {
+    let mut common : Option<SqlTypeInfo> = None;
+    for arg in args.iter().flatten() {
+        let arg = match arg {
+            SqlTypeInfo::Varchar(_) => &SqlTypeInfo::String,
+            arg => arg,
+        };
+        common = Some(match (common, arg) {
+            (None, arg) => arg.clone(),
+            (Some(SqlTypeInfo::Int), SqlTypeInfo::Float) | (Some(SqlTypeInfo::Float), SqlTypeInfo::Int) => SqlTypeInfo::Float,
+            (Some(common), arg) if common == *arg => common,
+            _ => return None,
+        });
+    }
+    Some(common.unwrap_or(SqlTypeInfo::String))
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+
+use sql_one_flexi_engine::{eval, row::StoredRow, storage::Storage};
+use sql_one_parser::{commands::{create::{Column, SqlTypeInfo}, select_condition::{Join, JoinKind}}, expr::{BinaryOp, Expr}, value::Value};
+
+use crate::{cte::View, error::QueryExecutionError, functions, table::{table, ColumnInfo}};
+
+/// What a source reads its rows from
+pub enum Relation<'a> {
+    Table(&'a table),
+    /// the rows of a common table expression
+    View(&'a View)
+}
+
+/// A table or view taking part in a select, `qualifier` is its alias or otherwise its name
+pub struct Source<'a> {
+    pub relation : Relation<'a>,
+    pub qualifier : String
+}
+
+impl Source<'_> {
+    pub fn columns(&self) -> &ColumnInfo {
+        match self.relation {
+            Relation::Table(table) => table.columns(),
+            Relation::View(view) => &view.columns,
+        }
+    }
+
+    /// Every row of the source, those of a table in primary key order
+    pub fn scan(&self) -> Box<dyn Iterator<Item = StoredRow> + '_> {
+        match self.relation {
+            Relation::Table(table) => Box::new(table.storage.scan(false)),
+            Relation::View(view) => Box::new(view.rows.iter().cloned()),
+        }
+    }
+
+    // the storage rows can be looked up in by primary key, views have none
+    fn storage(&self) -> Option<&Storage> {
+        match self.relation {
+            Relation::Table(table) => Some(&table.storage),
+            Relation::View(_) => None,
+        }
+    }
+}
+
+/// The columns visible to a select over joined tables. Every column can be referenced as
+/// `<qualifier>.<column>` and, unless another table has a column of the same name, by its bare name.
+/// A subquery also sees the columns of the select it is nested in that its own tables do not hide
+pub struct Scope {
+    pub columns : ColumnInfo,
+    // the source and table column behind every visible name
+    origins : HashMap<String, (usize, String)>,
+    ambiguous : Vec<String>,
+    // the visible names that belong to the select the subquery is nested in
+    outer : Vec<String>
+}
+
+impl Scope {
+    pub fn new(sources : &[Source]) -> Result<Self, QueryExecutionError> {
+        let mut scope = Self { columns : ColumnInfo::default(), origins : HashMap::new(), ambiguous : Vec::new(), outer : Vec::new() };
+        for (index, source) in sources.iter().enumerate() {
+            if sources[..index].iter().any(|other| other.qualifier == source.qualifier) {
+                return Err(QueryExecutionError::DuplicateTableName(source.qualifier.clone()));
+            }
+            for col in source.columns().iter() {
+                let shared = sources[..index].iter().any(|other| other.columns().find_column(&col.name).is_ok());
+                if shared && !scope.ambiguous.contains(&col.name) {
+                    scope.ambiguous.push(col.name.clone());
+                }
+                scope.add(format!("{}.{}", source.qualifier, col.name), index, col);
+            }
+        }
+        for (index, source) in sources.iter().enumerate() {
+            for col in source.columns().iter() {
+                if !scope.ambiguous.contains(&col.name) {
+                    scope.add(col.name.clone(), index, col);
+                }
+            }
+        }
+        Ok(scope)
+    }
+
+    /// Makes the columns of the select the subquery is nested in visible, unless hidden by a column of the same name
+    pub fn add_outer(&mut self, outer : &ColumnInfo) {
+        for col in outer.iter() {
+            if !self.ambiguous.contains(&col.name) && self.columns.find_column(&col.name).is_err() {
+                self.outer.push(col.name.clone());
+                self.columns.columns.push(col.clone());
+            }
+        }
+    }
+
+    /// The values of the outer row for the columns the scope takes from the select the subquery is nested in
+    pub fn outer_values(&self, outer : &StoredRow) -> HashMap<String, Value> {
+        self.outer.iter()
+            .filter_map(|name| outer.row.get(name).map(|value| (name.clone(), value.clone())))
+            .collect()
+    }
+
+    fn add(&mut self, name : String, index : usize, col : &Column) {
+        self.origins.insert(name.clone(), (index, col.name.clone()));
+        self.columns.columns.push(Column { name, ..col.clone() });
+    }
+
+    pub fn find_column(&self, column_name : &String) -> Result<&Column, QueryExecutionError> {
+        if self.ambiguous.contains(column_name) {
+            return Err(QueryExecutionError::AmbiguousColumn(column_name.clone()));
+        }
+        self.columns.find_column(column_name)
+    }
+
+    /// Names the values of a row of the source by every name the scope gives its columns,
+    /// a missing row of an outer join gives NULL for every column
+    pub fn qualify(&self, index : usize, row : Option<&StoredRow>) -> HashMap<String, Value> {
+        self.origins.iter()
+            .filter(|(_, (source, _))| *source == index)
+            .map(|(name, (_, col_name))| {
+                let value = row.and_then(|row| row.row.get(col_name)).cloned().unwrap_or(Value::Null);
+                (name.clone(), value)
+            })
+            .collect()
+    }
+
+    // the source and column the name refers to
+    fn origin(&self, column_name : &String) -> Option<&(usize, String)> {
+        self.origins.get(column_name)
+    }
+}
+
+// whether values of the two column types compare equal exactly when they are equal,
+// so they can be looked up by hash or by key
+fn same_kind(left : &SqlTypeInfo, right : &SqlTypeInfo) -> bool {
+    let kind = |type_info : &SqlTypeInfo| match type_info {
+        SqlTypeInfo::Int | SqlTypeInfo::Float => SqlTypeInfo::Float,
+        SqlTypeInfo::String | SqlTypeInfo::Varchar(_) => SqlTypeInfo::String,
+        type_info => type_info.clone(),
+    };
+    kind(left) == kind(right)
+}
+
+/// Joins the rows produced so far with the rows of the source at `index`. The operator follows
+/// the ON condition: an equality on the right table's primary key is answered by index lookups,
+/// equalities between columns of both sides by a hash join, and anything else by a nested loop.
+/// The full condition is checked on every combined row whichever operator is used
+pub fn join(scope : &Scope, left : Vec<StoredRow>, sources : &[Source], index : usize, join : &Join) -> Result<Vec<StoredRow>, QueryExecutionError> {
+    // the condition can only read the joined table and the tables before it
+    for col_name in join.on.columns() {
+        scope.find_column(col_name)?;
+        if scope.origin(col_name).is_some_and(|(source, _)| *source > index) {
+            return Err(QueryExecutionError::ColumnNotFound(col_name.clone()));
+        }
+    }
+    if !join.on.aggregates().is_empty() {
+        return Err(QueryExecutionError::AggregateNotAllowed("JOIN conditions".to_string()));
+    }
+    scope.columns.check_functions(&join.on)?;
+
+    // the equalities between a column of the joined table and a column of the tables before it
+    let mut keys : Vec<(&String, &String)> = Vec::new();
+    for conjunct in join.on.conjuncts() {
+        let Expr::Binary { left : l, op : BinaryOp::Eq, right : r } = conjunct else {
+            continue;
+        };
+        let (Expr::Column(l), Expr::Column(r)) = (l.as_ref(), r.as_ref()) else {
+            continue;
+        };
+        let (Some(l_origin), Some(r_origin)) = (scope.origin(l), scope.origin(r)) else {
+            continue;
+        };
+        let key = match (l_origin.0, r_origin.0) {
+            (l_source, r_source) if l_source < index && r_source == index => (l, r),
+            (l_source, r_source) if r_source < index && l_source == index => (r, l),
+            _ => continue,
+        };
+        if same_kind(&scope.find_column(key.0)?.type_info, &scope.find_column(key.1)?.type_info) {
+            keys.push(key);
+        }
+    }
+
+    let right = &sources[index];
+    match keys.as_slice() {
+        [] => nested_loop_join(scope, left, right, index, join),
+        [(left_key, right_key)] if scope.origin(right_key).is_some_and(|(_, col_name)| right.storage().is_some_and(|storage| storage.is_ordered_by(col_name))) => {
+            index_join(scope, left, right, index, join, left_key)
+        },
+        keys => hash_join(scope, left, right, index, join, keys),
+    }
+}
+
+// adds the right row's values to a copy of the left row
+fn combine(left : &StoredRow, right : HashMap<String, Value>) -> StoredRow {
+    let mut row = left.row.clone();
+    row.extend(right);
+    StoredRow::new(row)
+}
+
+// the combined rows of the left row and its matching right rows, a left join keeps
+// a left row without matches by padding it with NULLs
+fn emit(scope : &Scope, rows : &mut Vec<StoredRow>, left : &StoredRow, matches : Vec<StoredRow>, index : usize, kind : JoinKind) {
+    if matches.is_empty() && kind == JoinKind::Left {
+        rows.push(combine(left, scope.qualify(index, None)));
+    }
+    rows.extend(matches);
+}
+
+/// Compares every left row with every right row
+pub fn nested_loop_join(scope : &Scope, left : Vec<StoredRow>, right : &Source, index : usize, join : &Join) -> Result<Vec<StoredRow>, QueryExecutionError> {
+    let right_rows : Vec<HashMap<String, Value>> = right.scan()
+        .map(|row| scope.qualify(index, Some(&row)))
+        .collect();
+    let mut rows = Vec::new();
+    for left_row in left.iter() {
+        let matches = right_rows.iter()
+            .map(|right_row| combine(left_row, right_row.clone()))
+            .filter(|row| eval::matches(&join.on, row, functions::registry()))
+            .collect();
+        emit(scope, &mut rows, left_row, matches, index, join.kind);
+    }
+    Ok(rows)
+}
+
+/// Builds a hash table of the right rows on their join key and probes it with every left row.
+/// A NULL key never matches
+pub fn hash_join(scope : &Scope, left : Vec<StoredRow>, right : &Source, index : usize, join : &Join, keys : &[(&String, &String)]) -> Result<Vec<StoredRow>, QueryExecutionError> {
+    let mut buckets : HashMap<Vec<Value>, Vec<HashMap<String, Value>>> = HashMap::new();
+    for row in right.scan() {
+        let right_row = scope.qualify(index, Some(&row));
+        let key : Option<Vec<Value>> = keys.iter()
+            .map(|(_, right_key)| right_row.get(*right_key).filter(|value| !value.is_null()).cloned())
+            .collect();
+        if let Some(key) = key {
+            buckets.entry(key).or_default().push(right_row);
+        }
+    }
+    let mut rows = Vec::new();
+    for left_row in left.iter() {
+        let key : Option<Vec<Value>> = keys.iter()
+            .map(|(left_key, _)| left_row.row.get(*left_key).filter(|value| !value.is_null()).cloned())
+            .collect();
+        let matches = key.and_then(|key| buckets.get(&key))
+            .map(|bucket| bucket.iter()
+                .map(|right_row| combine(left_row, right_row.clone()))
+                .filter(|row| eval::matches(&join.on, row, functions::registry()))
+                .collect())
+            .unwrap_or_default();
+        emit(scope, &mut rows, left_row, matches, index, join.kind);
+    }
+    Ok(rows)
+}
+
+/// Looks up the right row of every left row through the right table's primary key
+pub fn index_join(scope : &Scope, left : Vec<StoredRow>, right : &Source, index : usize, join : &Join, left_key : &String) -> Result<Vec<StoredRow>, QueryExecutionError> {
+    let Some(storage) = right.storage() else {
+        return nested_loop_join(scope, left, right, index, join);
+    };
+    let mut rows = Vec::new();
+    for left_row in left.iter() {
+        let mut matches = Vec::new();
+        if let Some(key) = left_row.row.get(left_key).filter(|key| !key.is_null() && storage.rows.contains_key(key)) {
+            let right_row = storage.read(key.clone())?;
+            let row = combine(left_row, scope.qualify(index, Some(&right_row)));
+            if eval::matches(&join.on, &row, functions::registry()) {
+                matches.push(row);
+            }
+        }
+        emit(scope, &mut rows, left_row, matches, index, join.kind);
+    }
+    Ok(rows)
+}
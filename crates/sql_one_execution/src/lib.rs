@@ -1,3 +1,10 @@
 pub mod table;
 pub mod execution;
-pub mod error;
\ No newline at end of file
+pub mod error;
+pub mod aggregate;
+pub mod select;
+pub mod join;
+pub mod functions;
+pub mod subquery;
+pub mod set_operation;
+pub mod cte;
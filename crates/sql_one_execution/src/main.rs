@@ -1,6 +1,4 @@
-use sql_one_execution::{execution::Execution, table::{table, ColumnInfo}};
-use sql_one_parser::{commands::{create::{Column, SqlTypeInfo}, select_condition::{Condition, SelectStatementCondition}}, parser::Parse, value::Value};
-use bigdecimal::FromPrimitive;
+use sql_one_execution::execution::Execution;
 
 fn main() {
     println!("hello");
@@ -20,11 +18,7 @@ fn main() {
     // ]});
     // table.insert(vec![Value::Number(bigdecimal::BigDecimal::from_i32(1).expect("value")), Value::String("raja".to_string())]);
     // table.insert(vec![Value::Number(bigdecimal::BigDecimal::from_i32(2).expect("value")), Value::String("neha".to_string())]);
-    // let condition = Some(Condition { 
-    //     first: "id".to_string(), 
-    //     second : "1".to_string(),
-    //     token: "!=".to_string()
-    // });
+    // let condition = Some(Expr::parse_from_raw("id != 1").unwrap().1);
     // let query_str = "select id, name from User where id != 1;".to_string();
     // let query = SelectStatementCondition::parse_from_raw( &query_str).unwrap().1;
     // assert_eq!(query.where_clause, condition);
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use sql_one_flexi_engine::{distinct::HashDistinct, eval, row::StoredRow, sort::ExternalSort};
+use sql_one_parser::{commands::{create::Column, select_condition::{OrderBy, SelectItem, SelectStatementCondition}}, expr::Expr, value::Value};
+
+use crate::{aggregate::{self, HashAggregate}, error::QueryExecutionError, functions, table::ColumnInfo};
+
+/// Whether the select computes aggregates over groups of rows rather than returning the rows themselves
+pub fn is_grouped(select : &SelectStatementCondition) -> bool {
+    !select.group_by.is_empty() || select.having.is_some()
+        || select.fields.iter().filter_map(SelectItem::expr).any(|expr| !expr.aggregates().is_empty())
+}
+
+/// Whether an expression of the select uses a subquery
+pub fn has_subqueries(select : &SelectStatementCondition) -> bool {
+    select.where_clause.iter()
+        .chain(select.having.iter())
+        .chain(select.fields.iter().filter_map(SelectItem::expr))
+        .chain(select.joins.iter().map(|join| &join.on))
+        .any(|expr| !expr.subqueries().is_empty())
+}
+
+/// The names of all the tables the select reads, including those read by its subqueries
+pub fn tables(select : &SelectStatementCondition) -> Vec<&String> {
+    let mut tables : Vec<&String> = std::iter::once(&select.table).chain(select.joins.iter().map(|join| &join.table)).collect();
+    let exprs = select.where_clause.iter()
+        .chain(select.having.iter())
+        .chain(select.fields.iter().filter_map(SelectItem::expr))
+        .chain(select.joins.iter().map(|join| &join.on));
+    for query in exprs.flat_map(Expr::subqueries).filter_map(Expr::query) {
+        tables.extend(self::tables(query));
+    }
+    tables
+}
+
+/// The output columns of the select as pairs of name and expression, wildcards expanded to the
+/// columns of the tables they cover. `sources` are the qualifier and columns of every table the
+/// select reads, a column found in several of them is referenced by its qualified name
+pub fn outputs(select : &SelectStatementCondition, sources : &[(&String, &ColumnInfo)]) -> Result<Vec<(String, Expr)>, QueryExecutionError> {
+    let mut outputs = Vec::new();
+    for field in select.fields.iter() {
+        match field {
+            SelectItem::Expr { expr, alias } => outputs.push((alias.clone().unwrap_or_else(|| expr.to_string()), expr.clone())),
+            SelectItem::Wildcard(qualifier) => {
+                if let Some(qualifier) = qualifier.as_ref().filter(|qualifier| !sources.iter().any(|(name, _)| name == qualifier)) {
+                    return Err(QueryExecutionError::TableNotFound(qualifier.clone()));
+                }
+                let covered = sources.iter().filter(|(name, _)| qualifier.as_ref().is_none_or(|qualifier| qualifier == *name));
+                for (name, columns) in covered {
+                    for col in columns.iter() {
+                        let shared = sources.iter().filter(|(_, columns)| columns.find_column(&col.name).is_ok()).count() > 1;
+                        let col_name = if shared { format!("{}.{}", name, col.name) } else { col.name.clone() };
+                        outputs.push((col_name.clone(), Expr::Column(col_name)));
+                    }
+                }
+            },
+        }
+    }
+    Ok(outputs)
+}
+
+/// Checks that every column the select references is resolved by `resolve`, that functions are
+/// called with arguments they accept, that aggregates are only used where they are allowed, and
+/// that a grouped select only reads the GROUP BY columns outside of its aggregates. SELECT DISTINCT
+/// is ordered by its output columns, the only ones left once duplicates are dropped
+pub fn validate(select : &SelectStatementCondition, columns : &ColumnInfo, outputs : &[(String, Expr)], resolve : &dyn Fn(&String) -> Result<(), QueryExecutionError>) -> Result<(), QueryExecutionError> {
+    let fields = || outputs.iter().map(|(_, expr)| expr);
+    for col_name in select.where_clause.iter().chain(fields()).chain(select.having.iter()).flat_map(Expr::columns) {
+        resolve(col_name)?;
+    }
+    for expr in select.where_clause.iter().chain(fields()).chain(select.having.iter()) {
+        columns.check_functions(expr)?;
+    }
+    if select.where_clause.as_ref().is_some_and(|clause| !clause.aggregates().is_empty()) {
+        return Err(QueryExecutionError::AggregateNotAllowed("WHERE".to_string()));
+    }
+    if select.distinct {
+        if let Some(key) = select.order_by.iter().find(|key| !outputs.iter().any(|(name, _)| *name == key.column)) {
+            return Err(QueryExecutionError::DistinctOrderBy(key.column.clone()));
+        }
+    }
+    if !is_grouped(select) {
+        if !select.distinct {
+            for key in select.order_by.iter() {
+                resolve(&key.column)?;
+            }
+        }
+        return Ok(());
+    }
+
+    for col_name in select.group_by.iter().chain(select.having.iter().flat_map(Expr::columns)) {
+        resolve(col_name)?;
+    }
+    if let Some(col_name) = fields().chain(select.having.iter()).find_map(|expr| aggregate::ungrouped(expr, &select.group_by)) {
+        return Err(QueryExecutionError::NotGrouped(col_name.clone()));
+    }
+    if let Some(key) = select.order_by.iter().find(|key| !select.distinct && !select.group_by.contains(&key.column)) {
+        return Err(QueryExecutionError::NotGrouped(key.column.clone()));
+    }
+    Ok(())
+}
+
+/// Groups the rows with a hash aggregate, one row per group holding the GROUP BY columns and
+/// every aggregate of the select list and HAVING clause. Groups are then filtered by HAVING,
+/// ordered and limited, unless the select is DISTINCT and orders its distinct rows instead
+pub fn group(columns : &ColumnInfo, rows : impl Iterator<Item = StoredRow>, select : &SelectStatementCondition, outputs : &[(String, Expr)]) -> Result<Vec<StoredRow>, QueryExecutionError> {
+    let mut aggregates : Vec<Expr> = Vec::new();
+    for expr in outputs.iter().map(|(_, expr)| expr).chain(select.having.iter()).flat_map(Expr::aggregates) {
+        columns.output_column(expr)?;
+        if !aggregates.contains(expr) {
+            aggregates.push(expr.clone());
+        }
+    }
+    let mut hash_aggregate = HashAggregate::new(select.group_by.clone(), aggregates);
+    for row in rows {
+        hash_aggregate.push(&row)?;
+    }
+    let groups = hash_aggregate.finish()?.into_iter()
+        .filter(|group| select.having.as_ref().is_none_or(|having| eval::matches(having, group, functions::registry())));
+    if select.distinct {
+        return Ok(groups.collect());
+    }
+    sort(groups, select)
+}
+
+/// Orders the rows with an external sort, keeping only those within the offset and limit
+pub fn sort(rows : impl Iterator<Item = StoredRow>, select : &SelectStatementCondition) -> Result<Vec<StoredRow>, QueryExecutionError> {
+    sort_by(rows, &select.order_by, select.offset, select.limit)
+}
+
+/// Orders the rows by the sort keys with an external sort, keeping only those within the offset and limit.
+/// Without sort keys the rows keep the order they come in
+pub fn sort_by(rows : impl Iterator<Item = StoredRow>, order_by : &[OrderBy], offset : Option<usize>, limit : Option<usize>) -> Result<Vec<StoredRow>, QueryExecutionError> {
+    let offset = offset.unwrap_or(0);
+    let limit = limit.unwrap_or(usize::MAX);
+    if order_by.is_empty() {
+        return Ok(rows.skip(offset).take(limit).collect());
+    }
+    let mut sort = ExternalSort::new(order_by.to_vec());
+    for row in rows {
+        sort.push(row)?;
+    }
+    Ok(sort.finish()?.skip(offset).take(limit).collect::<Result<Vec<_>, _>>()?)
+}
+
+/// Projects the rows onto the output columns of the select, evaluating each output expression
+/// and storing its value under the output name
+pub fn project(columns : &ColumnInfo, outputs : &[(String, Expr)], rows : Vec<StoredRow>) -> Result<(ColumnInfo, Vec<StoredRow>), QueryExecutionError> {
+    let output = output_columns(columns, outputs)?;
+    let rows = rows.iter()
+        .map(|row| {
+            values(outputs, row).map(|values| StoredRow::new(outputs.iter().map(|(name, _)| name.clone()).zip(values).collect::<HashMap<_, _>>()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((output, rows))
+}
+
+/// Projects the rows of a SELECT DISTINCT, dropping duplicate output rows with a hash distinct
+/// that moves to disk for large inputs. The distinct rows are then ordered and limited, without
+/// ORDER BY they are limited as the hash distinct gives them
+pub fn distinct(columns : &ColumnInfo, outputs : &[(String, Expr)], rows : impl Iterator<Item = StoredRow>, select : &SelectStatementCondition) -> Result<(ColumnInfo, Vec<StoredRow>), QueryExecutionError> {
+    let output = output_columns(columns, outputs)?;
+    let mut distinct = HashDistinct::new();
+    for row in rows {
+        distinct.push(values(outputs, &row)?)?;
+    }
+    let rows = distinct.finish()?
+        .map(|values| values.map(|values| StoredRow::new(outputs.iter().map(|(name, _)| name.clone()).zip(values).collect())));
+    if select.order_by.is_empty() {
+        let rows = rows.skip(select.offset.unwrap_or(0)).take(select.limit.unwrap_or(usize::MAX));
+        return Ok((output, rows.collect::<Result<Vec<_>, _>>()?));
+    }
+    let rows = rows.collect::<Result<Vec<_>, _>>()?;
+    Ok((output, sort(rows.into_iter(), select)?))
+}
+
+fn output_columns(columns : &ColumnInfo, outputs : &[(String, Expr)]) -> Result<ColumnInfo, QueryExecutionError> {
+    outputs.iter()
+        .map(|(name, expr)| columns.output_column(expr).map(|col| Column { name : name.clone(), ..col }))
+        .collect::<Result<Vec<_>, _>>()
+        .map(ColumnInfo::new)
+}
+
+/// The value of every output expression for the row
+pub fn values(outputs : &[(String, Expr)], row : &StoredRow) -> Result<Vec<Value>, QueryExecutionError> {
+    outputs.iter()
+        .map(|(_, expr)| eval::evaluate(expr, row, functions::registry()).ok_or_else(|| QueryExecutionError::InvalidExpression(expr.to_string())))
+        .collect()
+}
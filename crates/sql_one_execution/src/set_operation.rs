@@ -0,0 +1,53 @@
+use std::collections::HashSet;
+
+use sql_one_flexi_engine::row::StoredRow;
+use sql_one_parser::{commands::{compound::SetOperator, create::{Column, SqlTypeInfo}}, value::Value};
+
+use crate::{error::QueryExecutionError, table::ColumnInfo};
+
+/// The output columns of a set operation, named after the columns of the rows before it. Both
+/// sides must give as many columns, each of a type the other side's can be combined with
+pub fn columns(op : SetOperator, left : &ColumnInfo, right : &ColumnInfo) -> Result<ColumnInfo, QueryExecutionError> {
+    if left.columns.len() != right.columns.len() {
+        return Err(QueryExecutionError::SetOperationColumns(op, left.columns.len(), right.columns.len()));
+    }
+    left.iter().zip(right.iter())
+        .map(|(l, r)| match common(&l.type_info, &r.type_info) {
+            Some(type_info) => Ok(Column { name : l.name.clone(), type_info, constraints : Vec::new() }),
+            None => Err(QueryExecutionError::SetOperationTypes(op, l.name.clone(), l.type_info.clone(), r.type_info.clone())),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(ColumnInfo::new)
+}
+
+// the type both can be read as, integers widening to floats and strings of any length to strings
+fn common(left : &SqlTypeInfo, right : &SqlTypeInfo) -> Option<SqlTypeInfo> {
+    match (left, right) {
+        (left, right) if left == right => Some(left.clone()),
+        (SqlTypeInfo::Int | SqlTypeInfo::Float, SqlTypeInfo::Int | SqlTypeInfo::Float) => Some(SqlTypeInfo::Float),
+        (SqlTypeInfo::String | SqlTypeInfo::Varchar(_), SqlTypeInfo::String | SqlTypeInfo::Varchar(_)) => Some(SqlTypeInfo::String),
+        _ => None
+    }
+}
+
+/// The values of the rows of a select in the order of its columns
+pub fn values(columns : &ColumnInfo, rows : Vec<StoredRow>) -> impl Iterator<Item = Vec<Value>> + 'static {
+    let names : Vec<String> = columns.iter().map(|col| col.name.clone()).collect();
+    rows.into_iter().map(move |mut row| names.iter().map(|name| row.row.remove(name).unwrap_or(Value::Null)).collect())
+}
+
+/// Combines the rows before the set operation with those of the select following it. UNION ALL
+/// streams one after the other, the other operations drop duplicate rows by hashing them, NULLs
+/// being equal to each other. INTERSECT and EXCEPT hash the right side before reading the left
+pub fn combine<'a>(op : SetOperator, left : Box<dyn Iterator<Item = Vec<Value>> + 'a>, right : impl Iterator<Item = Vec<Value>> + 'a) -> Box<dyn Iterator<Item = Vec<Value>> + 'a> {
+    let mut seen : HashSet<Vec<Value>> = HashSet::new();
+    match op {
+        SetOperator::UnionAll => Box::new(left.chain(right)),
+        SetOperator::Union => Box::new(left.chain(right).filter(move |row| seen.insert(row.clone()))),
+        SetOperator::Intersect | SetOperator::Except => {
+            let right : HashSet<Vec<Value>> = right.collect();
+            let keep = op == SetOperator::Intersect;
+            Box::new(left.filter(move |row| right.contains(row) == keep && seen.insert(row.clone())))
+        },
+    }
+}
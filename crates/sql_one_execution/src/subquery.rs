@@ -0,0 +1,91 @@
+use std::borrow::Cow;
+
+use sql_one_flexi_engine::{eval, row::StoredRow};
+use sql_one_parser::{commands::{create::Column, select_condition::SelectStatementCondition}, expr::Expr, value::Value};
+
+use crate::{error::QueryExecutionError, functions, table::ColumnInfo};
+
+/// Types the output columns of a select, given the columns of the select it is nested in
+pub type Output<'a> = dyn Fn(&SelectStatementCondition, Option<&ColumnInfo>) -> Result<ColumnInfo, QueryExecutionError> + 'a;
+
+/// Runs a select, nested in a select whose columns and current row are given, giving the value of
+/// the first column of every row
+pub type Run<'a> = dyn Fn(&SelectStatementCondition, Option<(&ColumnInfo, &StoredRow)>) -> Result<Vec<Value>, QueryExecutionError> + 'a;
+
+/// A subquery used by an expression of a select, as a value, by `IN (<select>)` or by `EXISTS`.
+/// Subqueries are evaluated by the execution rather than the engine, their value being stored in
+/// the row under the expression's text the way aggregates are
+pub struct Subquery {
+    pub expr : Expr,
+    query : SelectStatementCondition,
+    /// whether the subquery reads columns of the select using it, which runs it again for every row
+    pub correlated : bool,
+    // the columns of the select using it
+    outer : ColumnInfo,
+    // the column the value of a scalar subquery is stored in
+    column : Option<Column>,
+    // the values of an uncorrelated subquery, run once up front
+    values : Option<Vec<Value>>
+}
+
+/// Checks the subqueries used by the expressions against `columns`, the columns of the select
+/// using them, inner subqueries coming before the expressions containing them. A subquery that
+/// can not be typed without these columns is correlated
+pub fn plan<'e>(exprs : impl Iterator<Item = &'e Expr>, columns : &ColumnInfo, output : &Output) -> Result<Vec<Subquery>, QueryExecutionError> {
+    let mut subqueries = Vec::new();
+    for (expr, query) in exprs.flat_map(Expr::subqueries).filter_map(|expr| Some((expr, expr.query()?))) {
+        let output_columns = output(query, Some(columns))?;
+        if !matches!(expr, Expr::Exists(_)) && output_columns.columns.len() != 1 {
+            return Err(QueryExecutionError::SubqueryColumns(query.to_string()));
+        }
+        let column = matches!(expr, Expr::Subquery(_))
+            .then(|| Column { name : expr.to_string(), type_info : output_columns.columns[0].type_info.clone(), constraints : Vec::new() });
+        subqueries.push(Subquery {
+            expr : expr.clone(),
+            query : query.clone(),
+            correlated : output(query, None).is_err(),
+            outer : columns.clone(),
+            column,
+            values : None
+        });
+    }
+    Ok(subqueries)
+}
+
+impl Subquery {
+    /// The column the select adds to its rows for a scalar subquery
+    pub fn column(&self) -> Option<&Column> {
+        self.column.as_ref()
+    }
+
+    /// Runs the subquery up front unless it is correlated
+    pub fn prepare(&mut self, run : &Run) -> Result<(), QueryExecutionError> {
+        if !self.correlated {
+            self.values = Some(run(&self.query, None)?);
+        }
+        Ok(())
+    }
+
+    /// Stores the value of the subquery's expression for the row in the row
+    pub fn annotate(&self, row : &mut StoredRow, run : &Run) -> Result<(), QueryExecutionError> {
+        let values = match &self.values {
+            Some(values) => Cow::Borrowed(values),
+            None => Cow::Owned(run(&self.query, Some((&self.outer, row)))?),
+        };
+        let value = match &self.expr {
+            Expr::Subquery(query) => match values.as_slice() {
+                [] => Value::Null,
+                [value] => value.clone(),
+                _ => return Err(QueryExecutionError::SubqueryRows(query.to_string())),
+            },
+            // membership in the values follows the rules of an IN list
+            Expr::InSubquery { expr, negated, .. } => {
+                let list = Expr::InList { expr : expr.clone(), list : values.iter().cloned().map(Expr::Literal).collect(), negated : *negated };
+                eval::evaluate(&list, row, functions::registry()).unwrap_or(Value::Null)
+            },
+            _ => Value::Boolean(!values.is_empty()),
+        };
+        row.row.insert(self.expr.to_string(), value);
+        Ok(())
+    }
+}
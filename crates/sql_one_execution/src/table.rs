@@ -1,15 +1,31 @@
 use serde::{Serialize, Deserialize};
-use sql_one_flexi_engine::page::table::TableMetaData;
+use sql_one_flexi_engine::buffer_pool::BufferPool;
+use sql_one_flexi_engine::eval;
+use sql_one_flexi_engine::page::error::InternalStorageError;
+use sql_one_flexi_engine::page::table::{key_type, TableMetaData, UniqueIndex, ROW_ID};
 use sql_one_flexi_engine::storage::Storage;
 use sql_one_flexi_engine::row::StoredRow;
-use sql_one_parser::commands::create::{Column, SqlTypeInfo};
-use sql_one_parser::commands::select_condition::Condition;
+use sql_one_flexi_engine::schema::SchemaChange;
+use sql_one_parser::commands::create::{Column, ColumnConstraint, CreateStatement, SqlTypeInfo};
+use sql_one_parser::commands::alter::AlterAction;
+use sql_one_parser::commands::insert::{ConflictAction, OnConflict};
+use sql_one_parser::commands::select_condition::SelectStatementCondition;
+use sql_one_parser::commands::update::Assignment;
+use sql_one_parser::expr::{AggregateFunc, BinaryOp, Expr};
+use sql_one_parser::temporal::{Date, Timestamp};
 use sql_one_parser::value::Value;
-use std::collections::{btree_map, BTreeMap, HashMap};
+use bigdecimal::{BigDecimal, ToPrimitive};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Bound;
 use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::Arc;
 
+use crate::{functions, select};
 use crate::error::QueryExecutionError;
 
+/// The number of inserted rows written to storage at once
+pub const INSERT_BATCH_ROWS : usize = 1024;
 
 
 #[derive(Debug, Default, Serialize, Deserialize, derive_more::From, Clone)]
@@ -29,6 +45,72 @@ impl ColumnInfo {
     pub fn find_column(&self, column_name : &String) -> Result<&Column, QueryExecutionError> { 
         self.iter().find(|col| col.name == *column_name).ok_or_else(|| QueryExecutionError::ColumnNotFound(column_name.to_owned()))
     }
+
+    /// The column an output expression of a select over these columns produces, named after the
+    /// expression's text. Computed columns such as aggregates are typed after what they compute
+    pub fn output_column(&self, expr : &Expr) -> Result<Column, QueryExecutionError> { 
+        let type_info = match expr { 
+            // a scalar subquery's value is a column the select adds to its rows
+            Expr::Column(name) => return self.find_column(name).cloned(),
+            Expr::Subquery(_) => return self.find_column(&expr.to_string()).cloned(),
+            Expr::Literal(value) => match value { 
+                Value::Number(n) if n.is_integer() => SqlTypeInfo::Int,
+                Value::Number(_) => SqlTypeInfo::Float,
+                Value::Boolean(_) => SqlTypeInfo::Boolean,
+                Value::Date(_) => SqlTypeInfo::Date,
+                Value::Timestamp(_) => SqlTypeInfo::Timestamp,
+                Value::Blob(_) => SqlTypeInfo::Blob,
+                Value::String(_) | Value::Null => SqlTypeInfo::String,
+            },
+            Expr::Aggregate { func, arg, .. } => { 
+                let arg_type = match arg { 
+                    Some(arg) if !arg.aggregates().is_empty() => return Err(QueryExecutionError::AggregateNotAllowed(format!("the argument of {}", expr))),
+                    Some(arg) => Some(self.output_column(arg)?.type_info),
+                    None => None,
+                };
+                match (func, arg_type) { 
+                    (AggregateFunc::Count, _) => SqlTypeInfo::Int,
+                    (AggregateFunc::Avg, Some(SqlTypeInfo::Int | SqlTypeInfo::Float)) => SqlTypeInfo::Float,
+                    (AggregateFunc::Sum, Some(arg_type @ (SqlTypeInfo::Int | SqlTypeInfo::Float))) => arg_type,
+                    (AggregateFunc::Min | AggregateFunc::Max, Some(arg_type)) => arg_type,
+                    (_, arg_type) => return Err(QueryExecutionError::InvalidAggregate(format!(
+                        "{} over a {} column", func, arg_type.map_or_else(|| "*".to_string(), |arg_type| arg_type.to_string())))),
+                }
+            },
+            Expr::Function { name, args } => { 
+                let arg_types = args.iter()
+                    .map(|arg| match arg { 
+                        Expr::Literal(Value::Null) => Ok(None),
+                        arg => self.output_column(arg).map(|col| Some(col.type_info)),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                functions::registry().check(name, &arg_types)?
+            },
+            Expr::Binary { left, op, right } if op.is_arithmetic() => { 
+                match (self.output_column(left)?.type_info, self.output_column(right)?.type_info) { 
+                    (SqlTypeInfo::Int, SqlTypeInfo::Int) if *op != BinaryOp::Div => SqlTypeInfo::Int,
+                    (SqlTypeInfo::Int | SqlTypeInfo::Float, SqlTypeInfo::Int | SqlTypeInfo::Float) => SqlTypeInfo::Float,
+                    _ => return Err(QueryExecutionError::InvalidExpression(expr.to_string())),
+                }
+            },
+            Expr::Binary { left, op : BinaryOp::Concat, right } => { 
+                self.output_column(left)?;
+                self.output_column(right)?;
+                SqlTypeInfo::String
+            },
+            Expr::Unary { .. } | Expr::Binary { .. } | Expr::IsNull { .. } | Expr::Between { .. } | Expr::InList { .. } | Expr::Like { .. }
+                | Expr::InSubquery { .. } | Expr::Exists(_) => SqlTypeInfo::Boolean,
+        };
+        Ok(Column { name : expr.to_string(), type_info, constraints : Vec::new() })
+    }
+
+    /// Checks that every function the expression calls exists and is given arguments it accepts
+    pub fn check_functions(&self, expr : &Expr) -> Result<(), QueryExecutionError> { 
+        match expr { 
+            Expr::Function { .. } => self.output_column(expr).map(|_| ()),
+            expr => expr.children().into_iter().try_for_each(|child| self.check_functions(child)),
+        }
+    }
 }
 
 
@@ -53,8 +135,59 @@ impl<'a> Row<'a> {
     pub fn new(id : usize, columns : Rc<ColumnInfo>, data:  HashMap<&'a String, &'a Value>) -> Self { 
         Self{id, columns, data}
     }
+
+    /// The values of the row in the order of its columns, NULL for a column it holds no value for
+    pub fn values(&self) -> Vec<Value> { 
+        self.columns.iter()
+            .map(|col| self.data.get(&col.name).map_or(Value::Null, |value| (*value).clone()))
+            .collect()
+    }
 }
 
+// the values the rows written by a statement hold for the table's unique keys, checked against
+// each other and against the values of the stored rows the storage keeps an index of
+struct UniqueCheck { 
+    // the primary keys of the stored rows the statement replaces, their stored values no longer count
+    replaced : HashSet<Value>,
+    // the primary keys of the rows the statement gave each set of values of a unique key
+    given : HashMap<Vec<String>, HashMap<Vec<Value>, Value>>
+}
+
+impl UniqueCheck { 
+    fn new(replaced : HashSet<Value>) -> Self { 
+        Self { replaced, given : HashMap::new() }
+    }
+
+    // adds the row written under the primary key, failing when another row holds the same values
+    // for one of the unique keys
+    fn add(&mut self, storage : &Storage, row : &StoredRow, key : Value) -> Result<(), QueryExecutionError> { 
+        for unique_key in storage.table_metadata.unique_keys.iter() { 
+            let Some(values) = UniqueIndex::values(unique_key, row) else { 
+                continue;
+            };
+            let given = self.given.get(unique_key).and_then(|rows| rows.get(&values));
+            let stored = storage.unique_holder(unique_key, &values).filter(|holder| !self.replaced.contains(*holder));
+            if given.into_iter().chain(stored).any(|holder| *holder != key) { 
+                let values : Vec<String> = values.iter().map(|value| value.to_string()).collect();
+                return Err(QueryExecutionError::UniqueViolation(unique_key.join(", "), values.join(", ")));
+            }
+        }
+        for unique_key in storage.table_metadata.unique_keys.iter() { 
+            if let Some(values) = UniqueIndex::values(unique_key, row) { 
+                self.given.entry(unique_key.clone()).or_default().insert(values, key.clone());
+            }
+        }
+        Ok(())
+    }
+
+    // drops the stored values of the row under the primary key, about to be replaced
+    fn replace(&mut self, key : Value) { 
+        self.replaced.insert(key);
+    }
+}
+
+/// Iterates the output rows of a select, already projected onto its output columns, which may be
+/// aliased or computed columns such as aggregates rather than columns of the table
 #[derive(Debug , Clone)]
 pub struct TableIter<'a> { 
     map_iter: std::collections::btree_map::Iter<'a, usize, StoredRow>, 
@@ -68,7 +201,10 @@ impl<'a> TableIter<'a> {
                 Self{map_iter, columns}
     }
 
-    
+    /// The output columns of the rows
+    pub fn columns(&self) -> &ColumnInfo { 
+        &self.columns
+    }
 }
 
 impl<'a> Iterator for TableIter<'a> {
@@ -77,12 +213,7 @@ impl<'a> Iterator for TableIter<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         self.map_iter
             .next()
-            .map(|(id, data)| { 
-                let projected_data  = data.row.iter()
-                    .filter_map(|(key, value) | self.columns.find_column(key).ok().map(|_|(key, value)))
-                    .collect();
-                Row::new(*id, self.columns.clone(), projected_data)
-            })
+            .map(|(id, data)| Row::new(*id, self.columns.clone(), data.row.iter().collect()))
     }
 }
 
@@ -100,16 +231,83 @@ impl<'a> IntoIterator for &'a table {
 
 impl table { 
 
-    pub fn iter(&self ) -> impl Iterator<Item = Row> { 
+    pub fn iter(&self ) -> impl Iterator<Item = Row<'_>> { 
         self.into_iter()
     }
-    pub fn new(columns : ColumnInfo , table_metadata : TableMetaData ) -> Self { 
+
+    pub fn columns(&self) -> &ColumnInfo { 
+        &self.columns
+    }
+    pub fn new(columns : ColumnInfo , table_metadata : TableMetaData, pool : Arc<BufferPool> ) -> Self { 
+        let storage_columns = Self::storage_columns(&columns, &table_metadata);
+        let storage = Storage::open(Some(table_metadata.clone()), &storage_columns, format!("{}_storage.json", table_metadata.table_name.clone()), pool);
         table { 
             rows : BTreeMap::new(),
-            columns: columns, 
+            columns, 
             filtered_rows: BTreeMap::new(),
-            storage: Storage::new(Some(table_metadata.clone()), format!("{}_storage.json", table_metadata.table_name.clone()))
+            storage
+        }
+    }
+
+    /// Loads the table's storage from its file, reading its pages through the pool
+    pub fn open_storage(&mut self, table_name : &str, pool : Arc<BufferPool>) { 
+        let storage_columns = Self::storage_columns(&self.columns, &self.storage.table_metadata);
+        self.storage = Storage::open(None, &storage_columns, format!("{}_storage.json", table_name), pool);
+    }
+
+    // the columns of the stored rows, led by the hidden row id for a table keyed by it
+    fn storage_columns(columns : &ColumnInfo, table_metadata : &TableMetaData) -> Vec<String> { 
+        let row_id = table_metadata.is_row_id().then(|| ROW_ID.to_string());
+        row_id.into_iter().chain(columns.iter().map(|col| col.name.clone())).collect()
+    }
+
+    /// Builds the table from its definition. The declared PRIMARY KEY and UNIQUE constraints
+    /// are kept in the storage's table metadata, without a declared primary key the first column is used.
+    /// Its pages are read and changed through the pool
+    pub fn from_create(create : CreateStatement, pool : Arc<BufferPool>) -> Result<Self, QueryExecutionError> { 
+        let columns = ColumnInfo::new(create.columns.clone());
+        let mut primary_keys = create.primary_key();
+        if primary_keys.len() > 1 { 
+            return Err(QueryExecutionError::MultiplePrimaryKeys(create.table));
         }
+        let key_columns = match primary_keys.pop() { 
+            Some(key_columns) => key_columns,
+            None => { 
+                let first = columns.iter().next()
+                    .ok_or_else(|| QueryExecutionError::InvalidConstraint(format!("table {} has no columns", create.table)))?;
+                vec![first.name.clone()]
+            }
+        };
+        let unique_keys = create.unique_keys();
+        for col_name in key_columns.iter().chain(unique_keys.iter().flatten()) { 
+            columns.find_column(col_name)?;
+        }
+        for col in columns.iter() { 
+            if let Some(default) = col.default() { 
+                Self::check_type(col, default.clone())?;
+            }
+        }
+
+        let mut table_metadata = match key_columns.as_slice() { 
+            [key] => { 
+                let prim_key_type = match &columns.find_column(key)?.type_info { 
+                    SqlTypeInfo::String | SqlTypeInfo::Varchar(_) => key_type::Strings,
+                    SqlTypeInfo::Int | SqlTypeInfo::Float => key_type::Number,
+                    type_info => return Err(QueryExecutionError::InvalidConstraint(format!("a {} column can not be the primary key", type_info))),
+                };
+                TableMetaData::new(create.table, key.clone(), prim_key_type)
+            },
+            _ => TableMetaData::composite(create.table, key_columns),
+        };
+        table_metadata.unique_keys = unique_keys;
+        Ok(Self::new(columns, table_metadata, pool))
+    }
+
+    /// Builds the table created from the output columns of a select. Those carry no constraints
+    /// and any of them may hold the same value or NULL in several rows, so the rows are keyed by a
+    /// hidden row id rather than by one of the columns
+    pub fn from_select(table_name : String, columns : ColumnInfo, pool : Arc<BufferPool>) -> Self { 
+        Self::new(columns, TableMetaData::row_id(table_name), pool)
     }
 
     // pub fn from_existing(columns: ColumnInfo, data : BTreeMap<usize, StoredRow> ) -> Self { 
@@ -121,77 +319,412 @@ impl table {
     //     }
     // }
 
-    pub fn filter_rows<'a>(&'a mut self, conditions : Condition)  { 
-        let (condition_key , condition_value, token ) = { 
-            let k = conditions.first;
-            let v = Value::value(conditions.second);
-            (k, v, conditions.token)
-        };
-        let filtered_rows : BTreeMap<usize, StoredRow>= self.rows.iter().filter(move |(_, row)| { 
-            row.row.iter().any(|(key , value) | { 
-                (token == "=".to_string() && key == &condition_key && *value == condition_value) || 
-                (token == "!=".to_string() && key == &condition_key && *value != condition_value)
-            })
-        }).map(|(id, row)| (*id, StoredRow::new(row.row.clone()))).collect();
+    pub fn filter_rows(&mut self, conditions : Expr)  { 
+        let filtered_rows : BTreeMap<usize, StoredRow>= self.rows.iter()
+            .filter(|(_, row)| eval::matches(&conditions, row, functions::registry()))
+            .map(|(id, row)| (*id, StoredRow::new(row.row.clone()))).collect();
         self.filtered_rows = filtered_rows;
        
     }
 
-    pub fn select(&mut self, columns : Vec<String>, clause : Option<Condition>) -> Result<TableIter, QueryExecutionError> { 
-
-        let selected_cols : Result<Vec<_>, _> = columns.into_iter()
-            .map(|col_name| { 
-                self.columns.find_column(&col_name).map(|col| col.clone())
-            }).collect();
-        let column_rc : Rc<ColumnInfo> = Rc::new(selected_cols.unwrap().into());
-        let rows : BTreeMap<usize, StoredRow>;
-            match clause { 
-                Some(condition) => { 
-                    let rows = self.storage.read_when(Some(condition));
-                    
-                    let mut map = BTreeMap::new();
-                    for (i , row) in rows.iter().enumerate() { 
-                        map.insert(i, row.clone());
-                    }
-                    self.rows = map;
-                    //self.filter_rows(condition);
-                    Ok(TableIter::new(self.rows.iter(), column_rc))
-                    
-                }, 
-                None => {
-                    let rows = self.storage.read_when(None);
-         
-                    let mut map = BTreeMap::new();
-                    for (i , row) in rows.iter().enumerate() { 
-                        map.insert(i, row.clone());
+    // makes sure every column referenced by the where clause exists in the table
+    fn validate_clause(&self, clause : &Option<Expr>) -> Result<(), QueryExecutionError> { 
+        if let Some(condition) = clause { 
+            if !condition.subqueries().is_empty() { 
+                return Err(QueryExecutionError::SubqueryNotAllowed("UPDATE or DELETE".to_string()));
+            }
+            for col_name in condition.columns() { 
+                self.columns.find_column(col_name)?;
+            }
+            self.columns.check_functions(condition)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the select against the table. Rows come back in primary key order unless ordered
+    /// otherwise, ORDER BY the primary key is served straight from the storage's btree while any
+    /// other ordering goes through an external sort. Selects with aggregates, GROUP BY or HAVING
+    /// go through a hash aggregate instead
+    pub fn select(&mut self, select : SelectStatementCondition) -> Result<TableIter<'_>, QueryExecutionError> { 
+        let outputs = select::outputs(&select, &[(&select.table, &self.columns)])?;
+        select::validate(&select, &self.columns, &outputs, &|col_name| self.columns.find_column(col_name).map(|_| ()))?;
+        let rows = self.storage.scan_where(select.where_clause.as_ref(), false, functions::registry());
+        let (columns, rows) = match (select::is_grouped(&select), select.distinct) { 
+            (true, distinct) => { 
+                let groups = select::group(&self.columns, rows, &select, &outputs)?;
+                if distinct { 
+                    select::distinct(&self.columns, &outputs, groups.into_iter(), &select)?
+                } else { 
+                    select::project(&self.columns, &outputs, groups)?
+                }
+            },
+            // the distinct rows are ordered and limited once duplicates are dropped
+            (false, true) => select::distinct(&self.columns, &outputs, rows, &select)?,
+            (false, false) => select::project(&self.columns, &outputs, self.select_rows(&select)?)?,
+        };
+        self.rows = rows.into_iter().enumerate().collect();
+        Ok(TableIter::new(self.rows.iter(), Rc::new(columns)))
+    } 
+
+    /// Whether the select's rows can be read lazily with `stream`, which is the case for a select
+    /// without aggregates, DISTINCT or an ordering other than the primary key's
+    pub fn is_streamed(&self, select : &SelectStatementCondition) -> bool { 
+        let sorted = select.order_by.first().is_some_and(|key| !self.storage.is_ordered_by(&key.column));
+        !select::is_grouped(select) && !select.distinct && !sorted
+    }
+
+    /// The output columns of the select and its rows read from storage one at a time as they are
+    /// taken, for the selects `is_streamed` accepts
+    pub fn stream(&self, select : SelectStatementCondition) -> Result<(ColumnInfo, impl Iterator<Item = Result<Vec<Value>, QueryExecutionError>> + '_), QueryExecutionError> { 
+        let outputs = select::outputs(&select, &[(&select.table, &self.columns)])?;
+        select::validate(&select, &self.columns, &outputs, &|col_name| self.columns.find_column(col_name).map(|_| ()))?;
+        let (columns, _) = select::project(&self.columns, &outputs, Vec::new())?;
+        let range = select.where_clause.as_ref().map_or((Bound::Unbounded, Bound::Unbounded), |clause| self.storage.key_range(clause));
+        let descending = select.order_by.first().is_some_and(|key| key.descending);
+        let clause = select.where_clause;
+        let rows = self.storage.scan_range(range, descending)
+            .filter(move |row| clause.as_ref().is_none_or(|condition| eval::matches(condition, row, functions::registry())))
+            .skip(select.offset.unwrap_or(0))
+            .take(select.limit.unwrap_or(usize::MAX))
+            .map(move |row| select::values(&outputs, &row));
+        Ok((columns, rows))
+    }
+
+    // the rows of a select without aggregates, filtered, ordered and limited
+    fn select_rows(&self, select : &SelectStatementCondition) -> Result<Vec<StoredRow>, QueryExecutionError> { 
+        let clause = select.where_clause.as_ref();
+        let offset = select.offset.unwrap_or(0);
+        let limit = select.limit.unwrap_or(usize::MAX);
+        let rows = match select.order_by.first() { 
+            Some(key) if !self.storage.is_ordered_by(&key.column) => { 
+                select::sort(self.storage.scan_where(clause, false, functions::registry()), select)?
+            },
+            // the primary key is unique, so the keys after it never change the order
+            first => { 
+                let descending = first.is_some_and(|key| key.descending);
+                self.storage.scan_where(clause, descending, functions::registry())
+                    .skip(offset)
+                    .take(limit)
+                    .collect()
+            }
+        };
+        Ok(rows)
+    }
+
+    /// Deletes the rows matching the clause, returning the number of rows removed
+    pub fn delete(&mut self, clause : Option<Expr>) -> Result<usize, QueryExecutionError> { 
+        self.validate_clause(&clause)?;
+        Ok(self.storage.delete(clause, functions::registry()))
+    }
+
+    // checks that the value can be stored in the given column, NULL fits any type.
+    // Strings spelling out a date or timestamp are accepted by the temporal columns
+    fn check_type(col : &Column, value : Value) -> Result<Value, QueryExecutionError> { 
+        let out_of_range = |v| Err(QueryExecutionError::OutOfRange(col.type_info.clone(), v));
+        match (&col.type_info, value) {
+            (_, Value::Null) => Ok(Value::Null),
+            (SqlTypeInfo::String, v @ Value::String(_)) => Ok(v),
+            (SqlTypeInfo::Varchar(max), Value::String(str)) if str.chars().count() > *max => out_of_range(Value::String(str)),
+            (SqlTypeInfo::Varchar(_), v @ Value::String(_)) => Ok(v),
+            (SqlTypeInfo::Int, Value::Number(n)) if !n.is_integer() || n.to_i64().is_none() => out_of_range(Value::Number(n)),
+            (SqlTypeInfo::Int, v @ Value::Number(_)) => Ok(v),
+            (SqlTypeInfo::Float, Value::Number(n)) if !n.to_f64().is_some_and(f64::is_finite) => out_of_range(Value::Number(n)),
+            (SqlTypeInfo::Float, v @ Value::Number(_)) => Ok(v),
+            (SqlTypeInfo::Boolean, v @ Value::Boolean(_)) => Ok(v),
+            (SqlTypeInfo::Date, v @ Value::Date(_)) => Ok(v),
+            (SqlTypeInfo::Date, Value::String(str)) => Date::from_str(&str).map(Value::Date)
+                .map_err(|_| QueryExecutionError::InsertTypeMismatch(col.type_info.clone(), Value::String(str))),
+            (SqlTypeInfo::Timestamp, v @ Value::Timestamp(_)) => Ok(v),
+            (SqlTypeInfo::Timestamp, Value::Date(date)) => Ok(Value::Timestamp(Timestamp::midnight(date))),
+            (SqlTypeInfo::Timestamp, Value::String(str)) => Timestamp::from_str(&str).map(Value::Timestamp)
+                .map_err(|_| QueryExecutionError::InsertTypeMismatch(col.type_info.clone(), Value::String(str))),
+            (SqlTypeInfo::Blob, v @ Value::Blob(_)) => Ok(v),
+            (_,v) => Err(QueryExecutionError::InsertTypeMismatch(col.to_owned().type_info, v)),
+        }
+    }
+
+    /// Removes the table's pages and storage metadata from disk
+    pub fn drop_storage(self) -> Result<(), QueryExecutionError> { 
+        Ok(self.storage.destroy()?)
+    }
+
+    /// Changes the table definition. Rows already on disk are left as they are,
+    /// the storage migrates them to the new definition when they are read
+    pub fn alter(&mut self, action : AlterAction) -> Result<(), QueryExecutionError> { 
+        let change = match action { 
+            AlterAction::AddColumn(column) => { 
+                if self.columns.find_column(&column.name).is_ok() { 
+                    return Err(QueryExecutionError::ColumnAlreadyExists(column.name));
+                }
+                if column.has_constraint(&ColumnConstraint::PrimaryKey) { 
+                    return Err(QueryExecutionError::InvalidConstraint(format!("primary key can not be added to table {}", self.storage.table_metadata.table_name)));
+                }
+                let default = column.default().cloned().map(|value| Self::check_type(&column, value)).transpose()?;
+                // the existing rows take the default, which has to satisfy the new column's constraints
+                if default.as_ref().is_none_or(Value::is_null) && column.has_constraint(&ColumnConstraint::NotNull) && !self.storage.rows.is_empty() { 
+                    return Err(QueryExecutionError::NotNullViolation(column.name));
+                }
+                if column.has_constraint(&ColumnConstraint::Unique) { 
+                    if let Some(default) = default.as_ref().filter(|default| !default.is_null() && self.storage.rows.len() > 1) { 
+                        return Err(QueryExecutionError::UniqueViolation(column.name, default.to_string()));
                     }
-                    self.rows = map;
-                    Ok(TableIter::new(self.rows.iter(),column_rc))
+                    self.storage.table_metadata.unique_keys.push(vec![column.name.clone()]);
+                }
+                let name = column.name.clone();
+                self.columns.columns.push(column);
+                SchemaChange::AddColumn { name, default }
+            },
+            AlterAction::DropColumn(name) => { 
+                self.columns.find_column(&name)?;
+                if self.storage.table_metadata.is_key_column(&name) { 
+                    return Err(QueryExecutionError::PrimaryKeyColumn(name));
+                }
+                self.columns.columns.retain(|col| col.name != name);
+                SchemaChange::DropColumn(name)
+            },
+            AlterAction::RenameColumn { from, to } => { 
+                self.columns.find_column(&from)?;
+                if self.columns.find_column(&to).is_ok() { 
+                    return Err(QueryExecutionError::ColumnAlreadyExists(to));
+                }
+                for col in self.columns.columns.iter_mut().filter(|col| col.name == from) { 
+                    col.name = to.clone();
                 }
+                SchemaChange::RenameColumn { from, to }
+            },
+            AlterAction::RenameTable(table_name) => { 
+                return Ok(self.storage.rename(table_name)?);
+            },
+        };
+        self.storage.alter(change);
+        Ok(())
+    }
+
+    // fills the columns the insert left out with their defaults, or NULL when they have none
+    fn complete_row(&self, row : &mut HashMap<String, Value>) { 
+        for col in self.columns.iter() { 
+            if !row.contains_key(&col.name) { 
+                row.insert(col.name.clone(), col.default().cloned().unwrap_or(Value::Null));
             }
-        
-    } 
+        }
+    }
 
-    pub fn insert(&mut self, values : Vec<Value>) -> Result<(), QueryExecutionError> { 
-        let id = self.rows.last_key_value().map_or(0, |(max_id, _)| (max_id + 1));
-        let row = values
+    // enforces the NOT NULL constraints on a row about to be written, primary key columns are never null
+    fn check_not_null(&self, row : &StoredRow) -> Result<(), QueryExecutionError> { 
+        for col in self.columns.iter() { 
+            let not_null = col.has_constraint(&ColumnConstraint::NotNull) || self.storage.table_metadata.is_key_column(&col.name);
+            if not_null && row.row.get(&col.name).is_none_or(Value::is_null) { 
+                return Err(QueryExecutionError::NotNullViolation(col.name.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    // the columns the values of an inserted row are given for, every column of the table in order when none are listed
+    fn insert_columns(&self, columns : &[String]) -> Result<Vec<Column>, QueryExecutionError> { 
+        if columns.is_empty() { 
+            return Ok(self.columns.iter().cloned().collect());
+        }
+        let mut targets : Vec<Column> = Vec::new();
+        for col_name in columns.iter() { 
+            let col = self.columns.find_column(col_name)?;
+            if targets.iter().any(|target| target.name == col.name) { 
+                return Err(QueryExecutionError::DuplicateColumn(col_name.clone()));
+            }
+            targets.push(col.clone());
+        }
+        Ok(targets)
+    }
+
+    // maps the values of an inserted row onto the columns they are given for, the remaining columns
+    // get their default or NULL. Without a column list the values may leave out trailing columns
+    fn insert_row(&self, targets : &[Column], listed : bool, values : Vec<Value>) -> Result<StoredRow, QueryExecutionError> { 
+        if values.len() > targets.len() || (listed && values.len() != targets.len()) { 
+            return Err(QueryExecutionError::ValueCountMismatch(targets.len(), values.len()));
+        }
+        let mut row = values
             .into_iter()
-            .zip(self.columns.iter())
-            .map(|(value, col)| match (col.to_owned().type_info, value) {
-                (SqlTypeInfo::String, v @ Value::String(_)) => Ok((col.name.to_owned(), v)),
-                (SqlTypeInfo::Int, v @ Value::Number(_)) => Ok((col.name.to_owned(), v)), // TODO: when we add floats make sure number is an int
-                (_,v) => Err(QueryExecutionError::InsertTypeMismatch(col.to_owned().type_info, v)),
-            })
+            .zip(targets.iter())
+            .map(|(value, col)| Self::check_type(col, value).map(|v| (col.name.to_owned(), v)))
             .collect::<Result<HashMap<_, _>,_>>()?;
-        let s_row = StoredRow::new(row.clone());
-    
-        let written = self.storage.write(s_row).unwrap();
-        
+        self.complete_row(&mut row);
+        Ok(StoredRow::new(row))
+    }
+
+    /// Inserts the rows and returns how many were inserted or updated. Rows are checked as they are
+    /// read from `values` and written to storage in batches of `INSERT_BATCH_ROWS`, the updates of
+    /// ON CONFLICT once every row passed. When a row fails the rows already written are deleted
+    /// again, so a failed insert leaves the table as it was
+    pub fn insert(&mut self, columns : Vec<String>, values : impl IntoIterator<Item = Result<Vec<Value>, QueryExecutionError>>, on_conflict : Option<OnConflict>) -> Result<usize, QueryExecutionError> { 
+        let mut written : Vec<Value> = Vec::new();
+        let inserted = self.insert_batches(columns, values, on_conflict, &mut written);
+        if inserted.is_err() { 
+            self.storage.delete_keys(&written);
+        }
+        inserted
+    }
+
+    // inserts the rows, the primary keys of the rows written being pushed to `written`
+    fn insert_batches(&mut self, columns : Vec<String>, values : impl IntoIterator<Item = Result<Vec<Value>, QueryExecutionError>>, on_conflict : Option<OnConflict>, written : &mut Vec<Value>) -> Result<usize, QueryExecutionError> { 
+        let targets = self.insert_columns(&columns)?;
+        if let Some(on_conflict) = &on_conflict { 
+            if !on_conflict.columns.is_empty() && on_conflict.columns != self.storage.table_metadata.key_columns() { 
+                return Err(QueryExecutionError::InvalidConflictTarget(on_conflict.columns.join(", ")));
+            }
+            if let ConflictAction::DoUpdate(assignments) = &on_conflict.action { 
+                self.validate_assignments(assignments, true)?;
+            }
+        }
+
+        let table_name = self.storage.table_metadata.table_name.clone();
+        let mut unique = UniqueCheck::new(HashSet::new());
+        let mut next_row_id = self.next_row_id();
+        let mut rows : Vec<StoredRow> = Vec::new();
+        let mut inserted = 0;
+        let mut updates : Vec<(Value, StoredRow)> = Vec::new();
+        // the primary keys of the rows inserted and of the stored rows to be updated
+        let mut pending_keys : HashSet<Value> = HashSet::new();
+        let mut updated_keys : HashSet<Value> = HashSet::new();
+        for values in values { 
+            let mut row = self.insert_row(&targets, !columns.is_empty(), values?)?;
+            if self.storage.table_metadata.is_row_id() { 
+                row.row.insert(ROW_ID.to_string(), Value::Number(BigDecimal::from(next_row_id)));
+                next_row_id += 1;
+            }
+            let key = self.storage.table_metadata.key_of(&row);
+
+            if let Some(on_conflict) = &on_conflict { 
+                let pending = key.as_ref().is_some_and(|key| pending_keys.contains(key));
+                let stored = key.as_ref().filter(|key| self.storage.rows.contains_key(key));
+                match &on_conflict.action { 
+                    ConflictAction::DoNothing if pending || stored.is_some() => continue,
+                    ConflictAction::DoUpdate(assignments) if !pending => if let Some(key) = stored { 
+                        // a row can only be updated once by the same statement
+                        if !updated_keys.insert(key.clone()) { 
+                            return Err(QueryExecutionError::PrimaryKeyViolation(table_name, key.clone()));
+                        }
+                        let existing = self.storage.read(key.clone())?;
+                        let mut scope = existing.clone();
+                        for (name, value) in row.row.iter() { 
+                            scope.row.insert(format!("excluded.{}", name), value.clone());
+                        }
+                        let updated = self.assign(assignments, &existing, &scope)?;
+                        self.check_not_null(&updated)?;
+                        unique.replace(key.clone());
+                        unique.add(&self.storage, &updated, key.clone())?;
+                        updates.push((key.clone(), updated));
+                        continue;
+                    },
+                    _ => {},
+                }
+            }
+
+            if let Some(key) = key.as_ref().filter(|key| self.storage.rows.contains_key(key) || pending_keys.contains(*key)) { 
+                return Err(QueryExecutionError::PrimaryKeyViolation(table_name, key.clone()));
+            }
+            self.check_not_null(&row)?;
+            if let Some(key) = key { 
+                unique.add(&self.storage, &row, key.clone())?;
+                pending_keys.insert(key);
+            }
+            rows.push(row);
+            inserted += 1;
+            if rows.len() == INSERT_BATCH_ROWS { 
+                self.write_rows(std::mem::take(&mut rows), written)?;
+            }
+        }
+
+        self.write_rows(rows, written)?;
+        let updated = self.storage.update_batch(updates).map_err(|err| self.storage_error(err))?;
+        Ok(inserted + updated)
+    }
 
-        //self.rows.insert(id, StoredRow::new(row.into()));
-      
+    // writes a batch of inserted rows, pushing their primary keys to `written`
+    fn write_rows(&mut self, rows : Vec<StoredRow>, written : &mut Vec<Value>) -> Result<(), QueryExecutionError> { 
+        if rows.is_empty() { 
+            return Ok(());
+        }
+        let keys : Vec<Value> = rows.iter().filter_map(|row| self.storage.table_metadata.key_of(row)).collect();
+        self.storage.write_batch(rows).map_err(|err| self.storage_error(err))?;
+        written.extend(keys);
         Ok(())
     }
+
+    // the error of a write to storage, a duplicate key violating the table's primary key
+    fn storage_error(&self, err : InternalStorageError) -> QueryExecutionError { 
+        match err { 
+            InternalStorageError::ErrDuplicatePrimaryKey(duplicate) => QueryExecutionError::PrimaryKeyViolation(self.storage.table_metadata.table_name.clone(), Value::String(duplicate)),
+            err => err.into(),
+        }
+    }
+
+    // the row id following the highest one stored, for a table keyed by row ids
+    fn next_row_id(&self) -> u64 { 
+        self.storage.rows.keys().next_back()
+            .and_then(|key| match key { 
+                Value::Number(n) => n.to_u64(),
+                _ => None,
+            })
+            .map_or(1, |id| id + 1)
+    }
+
+    // makes sure the assignments only target and reference columns of the table,
+    // `excluded.<column>` references are accepted when `allow_excluded` is set
+    fn validate_assignments(&self, assignments : &[Assignment], allow_excluded : bool) -> Result<(), QueryExecutionError> { 
+        for assignment in assignments.iter() { 
+            self.columns.find_column(&assignment.column)?;
+            if !assignment.value.subqueries().is_empty() { 
+                return Err(QueryExecutionError::SubqueryNotAllowed("UPDATE or DELETE".to_string()));
+            }
+            for col_name in assignment.value.columns() { 
+                match col_name.strip_prefix("excluded.") { 
+                    Some(excluded) if allow_excluded => self.columns.find_column(&excluded.to_string())?,
+                    _ => self.columns.find_column(col_name)?,
+                };
+            }
+            if !allow_excluded { 
+                self.columns.check_functions(&assignment.value)?;
+            }
+        }
+        Ok(())
+    }
+
+    // evaluates the assignments against the scope and applies them to a copy of the row
+    fn assign(&self, assignments : &[Assignment], row : &StoredRow, scope : &StoredRow) -> Result<StoredRow, QueryExecutionError> { 
+        let mut updated = row.clone();
+        for assignment in assignments.iter() { 
+            let value = eval::evaluate(&assignment.value, scope, functions::registry())
+                .ok_or_else(|| QueryExecutionError::InvalidExpression(assignment.value.to_string()))?;
+            let col = self.columns.find_column(&assignment.column)?;
+            updated.row.insert(col.name.clone(), Self::check_type(col, value)?);
+        }
+        Ok(updated)
+    }
+
+    /// Applies the assignments to the rows matching the clause, returning the number of rows updated.
+    /// Assigned expressions are evaluated against the row as it was before the update. Every
+    /// updated row is checked before any is written, the unique keys against the values given
+    /// to the other rows by the same update, then the rows are written to storage as one batch
+    pub fn update(&mut self, assignments : Vec<Assignment>, clause : Option<Expr>) -> Result<usize, QueryExecutionError> { 
+        self.validate_clause(&clause)?;
+        self.validate_assignments(&assignments, false)?;
+        let mut updates : Vec<(Value, StoredRow)> = Vec::new();
+        for row in self.storage.scan_where(clause.as_ref(), false, functions::registry()) { 
+            let Some(key) = self.storage.table_metadata.key_of(&row) else { 
+                continue;
+            };
+            let updated = self.assign(&assignments, &row, &row)?;
+            self.check_not_null(&updated)?;
+            updates.push((key, updated));
+        }
+
+        let mut unique = UniqueCheck::new(updates.iter().map(|(key, _)| key.clone()).collect());
+        for (key, updated) in updates.iter() { 
+            unique.add(&self.storage, updated, key.clone())?;
+        }
+        self.storage.update_batch(updates).map_err(|err| self.storage_error(err))
+    }
+
     pub fn travserse(&self) { 
         for (key , val) in self.rows.iter() { 
             println!("key is {}, val is {:#?}", key, val);
@@ -203,8 +736,8 @@ impl table {
 #[cfg(test)]
 mod tests {
     use bigdecimal::FromPrimitive;
-    use sql_one_flexi_engine::page::table::TableMetaData;
-    use sql_one_parser::{commands::{create::{Column, SqlTypeInfo}, select_condition::Condition}, value::Value};
+    use sql_one_flexi_engine::page::table::{key_type, TableMetaData};
+    use sql_one_parser::{commands::create::{Column, SqlTypeInfo}, expr::Expr, value::Value};
 
     use super::{table, ColumnInfo};
 
@@ -228,11 +761,7 @@ mod tests {
     //     ]});
     //     table.insert(vec![Value::Number(bigdecimal::BigDecimal::from_i32(1).expect("value")), Value::String("raja".to_string())]);
     //     table.insert(vec![Value::Number(bigdecimal::BigDecimal::from_i32(2).expect("value")), Value::String("neha".to_string())]);
-    //     let condition = Some(Condition { 
-    //         first: "id".to_string(), 
-    //         second : "1".to_string(),
-    //         token: "!=".to_string()
-    //     });
+    //     let condition = Some(Expr::parse_from_raw("id != 1").unwrap().1);
     //     let table_iter = table.select(vec!["id".to_string(), "name".to_string()], condition).unwrap();
     // }
 }
\ No newline at end of file
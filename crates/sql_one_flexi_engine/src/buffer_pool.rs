@@ -0,0 +1,265 @@
+use std::{collections::HashMap, fmt, sync::{atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering}, Arc, Mutex, MutexGuard, OnceLock, RwLock, RwLockReadGuard, RwLockWriteGuard}};
+
+use crate::page::{error::InternalStorageError, page::Page};
+
+/// The number of frames of the buffer pool shared by every table
+pub const SHARED_FRAMES : usize = 256;
+
+static SHARED : OnceLock<Arc<BufferPool>> = OnceLock::new();
+
+/// A page of a table's file
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PageId {
+    pub table : String,
+    pub page_number : usize
+}
+
+impl PageId {
+    pub fn new(table : &str, page_number : usize) -> Self {
+        Self { table : table.to_string(), page_number }
+    }
+}
+
+#[derive(Debug)]
+struct Frame {
+    id : PageId,
+    page : RwLock<Page>,
+    pins : AtomicUsize,
+    dirty : AtomicBool,
+    // set on every pin, cleared as the clock hand passes the frame
+    referenced : AtomicBool
+}
+
+#[derive(Debug, Default)]
+struct Frames {
+    // None for a free frame
+    frames : Vec<Option<Arc<Frame>>>,
+    page_table : HashMap<PageId, usize>,
+    hand : usize
+}
+
+/// A fixed number of frames holding the pages read from the tables' files. A page stays in its
+/// frame while it is pinned, unpinned pages are evicted by the clock algorithm, which passes over
+/// the pages pinned since it last came by once. Changed pages are written back when they are
+/// evicted or flushed
+pub struct BufferPool {
+    capacity : usize,
+    frames : Mutex<Frames>,
+    hits : AtomicU64,
+    misses : AtomicU64
+}
+
+impl fmt::Debug for BufferPool {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferPool").field("capacity", &self.capacity).field("hits", &self.hits()).field("misses", &self.misses()).finish()
+    }
+}
+
+/// A page pinned in the buffer pool, unpinned when dropped
+#[derive(Debug)]
+pub struct PinnedPage {
+    frame : Arc<Frame>
+}
+
+impl PinnedPage {
+    pub fn read(&self) -> RwLockReadGuard<'_, Page> {
+        self.frame.page.read().unwrap()
+    }
+
+    /// The page to change, it is written back to disk before its frame is reused
+    pub fn write(&self) -> RwLockWriteGuard<'_, Page> {
+        let page = self.frame.page.write().unwrap();
+        self.frame.dirty.store(true, Ordering::Release);
+        page
+    }
+}
+
+impl Drop for PinnedPage {
+    fn drop(&mut self) {
+        self.frame.pins.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl BufferPool {
+    pub fn new(capacity : usize) -> Self {
+        Self { capacity : capacity.max(1), frames : Mutex::new(Frames::default()), hits : AtomicU64::new(0), misses : AtomicU64::new(0) }
+    }
+
+    /// The pool every storage uses unless given one of its own
+    pub fn shared() -> Arc<Self> {
+        SHARED.get_or_init(|| Arc::new(Self::new(SHARED_FRAMES))).clone()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of pins that found their page in a frame
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// The number of pins that read their page from disk, or started a new page
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    fn frames(&self) -> MutexGuard<'_, Frames> {
+        self.frames.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Pins the page of the table, reading it from disk when it is not in a frame
+    pub fn pin(&self, table : &str, page_number : usize) -> Result<PinnedPage, InternalStorageError> {
+        let id = PageId::new(table, page_number);
+        let mut frames = self.frames();
+        if let Some(frame) = frames.page_table.get(&id).and_then(|index| frames.frames[*index].clone()) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(Self::pinned(frame));
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let page = Page::read(page_number, table.to_string())
+            .ok_or_else(|| InternalStorageError::ErrReadFromDisk(format!("unable to read page {} of {}", page_number, table)))?;
+        self.load(&mut frames, id, page, false)
+    }
+
+    /// Pins a new empty page of the table, to be written at the end of its file
+    pub fn pin_new(&self, table : &str, page_number : usize) -> Result<PinnedPage, InternalStorageError> {
+        let id = PageId::new(table, page_number);
+        let mut frames = self.frames();
+        if let Some(frame) = frames.page_table.get(&id).and_then(|index| frames.frames[*index].clone()) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            *frame.page.write().unwrap() = Page::new(page_number);
+            frame.dirty.store(true, Ordering::Release);
+            return Ok(Self::pinned(frame));
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        self.load(&mut frames, id, Page::new(page_number), true)
+    }
+
+    fn pinned(frame : Arc<Frame>) -> PinnedPage {
+        frame.pins.fetch_add(1, Ordering::AcqRel);
+        frame.referenced.store(true, Ordering::Release);
+        PinnedPage { frame }
+    }
+
+    // puts the page in a free frame, or in the frame of the page the clock evicts
+    fn load(&self, frames : &mut Frames, id : PageId, page : Page, dirty : bool) -> Result<PinnedPage, InternalStorageError> {
+        let index = match frames.frames.iter().position(Option::is_none) {
+            Some(index) => index,
+            None if frames.frames.len() < self.capacity => {
+                frames.frames.push(None);
+                frames.frames.len() - 1
+            },
+            None => self.evict(frames)?,
+        };
+        let frame = Arc::new(Frame { id : id.clone(), page : RwLock::new(page), pins : AtomicUsize::new(0), dirty : AtomicBool::new(dirty), referenced : AtomicBool::new(false) });
+        frames.frames[index] = Some(frame.clone());
+        frames.page_table.insert(id, index);
+        Ok(Self::pinned(frame))
+    }
+
+    // frees the first unpinned frame the clock hand finds unreferenced, writing its page back
+    // when it was changed. Fails when every frame is pinned
+    fn evict(&self, frames : &mut Frames) -> Result<usize, InternalStorageError> {
+        // the first sweep clears the references, so the second finds a frame unless all are pinned
+        for _ in 0..2 * frames.frames.len() {
+            let index = frames.hand;
+            frames.hand = (frames.hand + 1) % frames.frames.len();
+            let Some(frame) = frames.frames[index].clone() else {
+                return Ok(index);
+            };
+            if frame.pins.load(Ordering::Acquire) > 0 || frame.referenced.swap(false, Ordering::AcqRel) {
+                continue;
+            }
+            Self::write_back(&frame)?;
+            frames.page_table.remove(&frame.id);
+            frames.frames[index] = None;
+            return Ok(index);
+        }
+        Err(InternalStorageError::ErrBufferPoolFull(self.capacity))
+    }
+
+    fn write_back(frame : &Frame) -> Result<(), InternalStorageError> {
+        if frame.dirty.swap(false, Ordering::AcqRel) && !frame.page.read().unwrap().write(frame.id.table.clone()) {
+            frame.dirty.store(true, Ordering::Release);
+            return Err(InternalStorageError::ErrWriteToDisk(format!("unable to write page {} of {}", frame.id.page_number, frame.id.table)));
+        }
+        Ok(())
+    }
+
+    /// Writes the changed pages of the table back to disk
+    pub fn flush(&self, table : &str) -> Result<(), InternalStorageError> {
+        let frames = self.frames();
+        frames.frames.iter().flatten()
+            .filter(|frame| frame.id.table == table)
+            .try_for_each(|frame| Self::write_back(frame))
+    }
+
+    /// Drops the pages of the table without writing them, once its file is removed or renamed
+    pub fn discard(&self, table : &str) {
+        self.discard_after(table, 0);
+    }
+
+    /// Drops the pages of the table past the first `page_count` without writing them, once its
+    /// file is cut short
+    pub fn discard_after(&self, table : &str, page_count : usize) {
+        let dropped = |id : &PageId| id.table == table && id.page_number > page_count;
+        let mut frames = self.frames();
+        let Frames { frames, page_table, .. } = &mut *frames;
+        for frame in frames.iter_mut() {
+            if frame.as_ref().is_some_and(|frame| dropped(&frame.id)) {
+                *frame = None;
+            }
+        }
+        page_table.retain(|id, _| !dropped(id));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::page::{error::InternalStorageError, page::Page};
+
+    use super::BufferPool;
+
+    #[test]
+    fn test_clock_evicts_unpinned_pages() {
+        let table = "buffer_pool_test";
+        Page::delete(table).unwrap();
+        let pool = BufferPool::new(2);
+        for page_number in 1..=3 {
+            let page = pool.pin_new(table, page_number).unwrap();
+            page.write().insert(format!("page {}", page_number).as_bytes()).unwrap();
+        }
+        // the first page was written back when its frame went to the third
+        assert_eq!(Page::read(1, table.to_string()).unwrap().tuple(0), Some(&b"page 1"[..]));
+        assert_eq!(Page::page_count(table), 1);
+
+        let first = pool.pin(table, 1).unwrap();
+        let third = pool.pin(table, 3).unwrap();
+        assert_eq!((pool.hits(), pool.misses()), (1, 4));
+        assert_eq!(Page::page_count(table), 2);
+        assert!(matches!(pool.pin(table, 2), Err(InternalStorageError::ErrBufferPoolFull(2))));
+        drop(third);
+        // the second page was written back when the first took its frame, every frame is pinned
+        // until the third page is unpinned
+        assert_eq!(pool.pin(table, 2).unwrap().read().tuple(0), Some(&b"page 2"[..]));
+        assert_eq!(first.read().tuple(0), Some(&b"page 1"[..]));
+        drop(first);
+
+        // starting over a page still in its frame reuses the frame
+        let restarted = pool.pin_new(table, 2).unwrap();
+        assert_eq!((pool.hits(), pool.misses()), (2, 6));
+        assert_eq!(restarted.read().tuple(0), None);
+        restarted.write().insert(b"page 2").unwrap();
+        drop(restarted);
+
+        pool.flush(table).unwrap();
+        let on_disk = Page::read(3, table.to_string()).unwrap();
+        pool.discard(table);
+        Page::delete(table).unwrap();
+
+        assert_eq!(on_disk.tuple(0), Some(&b"page 3"[..]));
+        assert!(pool.pin(table, 1).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+use std::{collections::{hash_map::{DefaultHasher, Entry}, HashMap, HashSet}, fs::{self, File}, hash::{Hash, Hasher}, io::{BufReader, BufWriter, Write}, path::PathBuf, sync::atomic::{AtomicUsize, Ordering}, vec};
+
+use sql_one_parser::value::Value;
+
+use crate::{page::error::InternalStorageError, spill};
+
+/// The number of distinct rows kept in memory before de-duplication moves to disk
+pub const DISTINCT_BUFFER_ROWS : usize = 4096;
+
+/// The number of files rows are spread over once de-duplication moved to disk
+const DISTINCT_PARTITIONS : usize = 16;
+
+// distinguishes the partition files of de-duplications running at the same time
+static NEXT_DISTINCT_ID : AtomicUsize = AtomicUsize::new(0);
+
+/// A hash de-duplication of rows of values, NULLs being equal to each other. Rows are hashed in
+/// memory until the buffer holds too many distinct ones, then those and every row pushed after
+/// them are written to partition files chosen by their hash, so that equal rows always share a
+/// partition. The partitions are de-duplicated one at a time when the rows are taken
+pub struct HashDistinct {
+    buffer_rows : usize,
+    // the distinct rows in memory with the order they were first seen in
+    seen : HashMap<Vec<Value>, usize>,
+    partitions : Vec<(PathBuf, BufWriter<File>)>,
+    id : usize
+}
+
+impl Default for HashDistinct {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HashDistinct {
+    pub fn new() -> Self {
+        Self::with_buffer(DISTINCT_BUFFER_ROWS)
+    }
+
+    pub fn with_buffer(buffer_rows : usize) -> Self {
+        let id = NEXT_DISTINCT_ID.fetch_add(1, Ordering::Relaxed);
+        Self { buffer_rows : buffer_rows.max(1), seen : HashMap::new(), partitions : Vec::new(), id }
+    }
+
+    pub fn push(&mut self, row : Vec<Value>) -> Result<(), InternalStorageError> {
+        if self.spilled() {
+            return self.write(row);
+        }
+        let next = self.seen.len();
+        if let Entry::Vacant(entry) = self.seen.entry(row) {
+            entry.insert(next);
+            if self.seen.len() >= self.buffer_rows {
+                self.spill()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether de-duplication moved to disk
+    pub fn spilled(&self) -> bool {
+        !self.partitions.is_empty()
+    }
+
+    // creates the partition files and moves the rows in memory to them
+    fn spill(&mut self) -> Result<(), InternalStorageError> {
+        for partition in 0..DISTINCT_PARTITIONS {
+            let path = std::env::temp_dir().join(format!("sql_one_distinct_{}_{}_{}.part", std::process::id(), self.id, partition));
+            let file = File::create(&path).map_err(|err| InternalStorageError::ErrWriteToDisk(err.to_string()))?;
+            self.partitions.push((path, BufWriter::new(file)));
+        }
+        for row in Self::in_order(std::mem::take(&mut self.seen)) {
+            self.write(row)?;
+        }
+        Ok(())
+    }
+
+    // appends the row to the partition its hash falls in, see `spill::write_values`
+    fn write(&mut self, row : Vec<Value>) -> Result<(), InternalStorageError> {
+        let mut hasher = DefaultHasher::new();
+        row.hash(&mut hasher);
+        let (_, writer) = &mut self.partitions[hasher.finish() as usize % DISTINCT_PARTITIONS];
+        spill::write_values(writer, &row.iter().collect::<Vec<_>>())
+    }
+
+    fn in_order(seen : HashMap<Vec<Value>, usize>) -> Vec<Vec<Value>> {
+        let mut rows : Vec<(Vec<Value>, usize)> = seen.into_iter().collect();
+        rows.sort_by_key(|(_, index)| *index);
+        rows.into_iter().map(|(row, _)| row).collect()
+    }
+
+    /// The distinct rows. They come in the order they were first pushed unless de-duplication
+    /// moved to disk, then they come partition by partition
+    pub fn finish(mut self) -> Result<DistinctRows, InternalStorageError> {
+        let mut paths = Vec::new();
+        for (path, mut writer) in std::mem::take(&mut self.partitions) {
+            paths.push(path);
+            writer.flush().map_err(|err| InternalStorageError::ErrWriteToDisk(err.to_string()))?;
+        }
+        let rows = Self::in_order(std::mem::take(&mut self.seen)).into_iter();
+        Ok(DistinctRows { rows, paths, next : 0 })
+    }
+}
+
+impl Drop for HashDistinct {
+    fn drop(&mut self) {
+        for (path, _) in self.partitions.iter() {
+            fs::remove_file(path).ok();
+        }
+    }
+}
+
+/// The rows of a finished hash de-duplication, reading back one partition at a time
+pub struct DistinctRows {
+    rows : vec::IntoIter<Vec<Value>>,
+    paths : Vec<PathBuf>,
+    // the partition read after the current rows
+    next : usize
+}
+
+impl DistinctRows {
+    // the distinct rows of a partition in the order they were written
+    fn read(path : &PathBuf) -> Result<Vec<Vec<Value>>, InternalStorageError> {
+        let file = File::open(path).map_err(|err| InternalStorageError::ErrReadFromDisk(err.to_string()))?;
+        let mut reader = BufReader::new(file);
+        let mut seen = HashSet::new();
+        let mut rows = Vec::new();
+        while let Some(row) = spill::read_values(&mut reader)? {
+            if seen.insert(row.clone()) {
+                rows.push(row);
+            }
+        }
+        Ok(rows)
+    }
+}
+
+impl Iterator for DistinctRows {
+    type Item = Result<Vec<Value>, InternalStorageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.rows.next() {
+                return Some(Ok(row));
+            }
+            let path = self.paths.get(self.next)?;
+            self.next += 1;
+            match Self::read(path) {
+                Ok(rows) => self.rows = rows.into_iter(),
+                Err(err) => {
+                    self.next = self.paths.len();
+                    return Some(Err(err));
+                },
+            }
+        }
+    }
+}
+
+impl Drop for DistinctRows {
+    fn drop(&mut self) {
+        for path in self.paths.iter() {
+            fs::remove_file(path).ok();
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use bigdecimal::BigDecimal;
+    use sql_one_parser::value::Value;
+
+    use super::HashDistinct;
+
+    fn row(id : i32, name : Option<&str>) -> Vec<Value> {
+        vec![Value::Number(BigDecimal::from(id)), name.map_or(Value::Null, |name| Value::String(name.to_string()))]
+    }
+
+    fn text(value : &str) -> Value {
+        Value::String(value.to_string())
+    }
+
+    #[test]
+    fn test_hash_distinct_spills_and_deduplicates() {
+        let input = [(1, Some("a")), (2, None), (1, Some("a")), (3, Some("b")), (2, None), (3, Some("b")), (4, Some("c")), (1, Some("a"))];
+
+        let mut distinct = HashDistinct::new();
+        for (id, name) in input {
+            distinct.push(row(id, name)).unwrap();
+        }
+        assert!(!distinct.spilled());
+        // in memory the rows keep the order they were first seen in
+        let rows = distinct.finish().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(rows, vec![row(1, Some("a")), row(2, None), row(3, Some("b")), row(4, Some("c"))]);
+
+        let mut distinct = HashDistinct::with_buffer(2);
+        for (id, name) in input {
+            distinct.push(row(id, name)).unwrap();
+        }
+        assert!(distinct.spilled());
+        let rows = distinct.finish().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(rows.len(), 4);
+        let expected : HashSet<Vec<Value>> = [row(1, Some("a")), row(2, None), row(3, Some("b")), row(4, Some("c"))].into_iter().collect();
+        assert_eq!(rows.into_iter().collect::<HashSet<_>>(), expected);
+
+        // a string that looks like a number stays apart from the number once on disk
+        let mut distinct = HashDistinct::with_buffer(1);
+        let input = [vec![text("1")], vec![Value::Number(BigDecimal::from(1))], vec![text("007")], vec![text("1")], vec![text("1e3")]];
+        for row in input.iter().cloned() {
+            distinct.push(row).unwrap();
+        }
+        assert!(distinct.spilled());
+        let rows = distinct.finish().unwrap().collect::<Result<HashSet<_>, _>>().unwrap();
+        assert_eq!(rows, input.into_iter().collect::<HashSet<_>>());
+        assert_eq!(rows.len(), 4);
+    }
+}
@@ -0,0 +1,382 @@
+use std::{cmp::Ordering, str::FromStr};
+
+use bigdecimal::{BigDecimal, Zero};
+use sql_one_parser::{expr::{BinaryOp, Expr, UnaryOp}, temporal::{Date, Timestamp}, value::Value};
+
+use crate::row::StoredRow;
+
+/// The scalar functions an `Expr::Function` calls, they live with whoever runs the expressions
+pub trait Functions {
+    /// Calls the function by its upper cased name on already evaluated arguments, None when the
+    /// function does not exist or can not be applied to them
+    fn call(&self, name : &str, args : Vec<Value>) -> Option<Value>;
+}
+
+/// No functions at all, for expressions that call none. A call evaluates to None, so an
+/// expression calling one can not be evaluated
+pub struct NoFunctions;
+
+impl Functions for NoFunctions {
+    fn call(&self, _ : &str, _ : Vec<Value>) -> Option<Value> {
+        None
+    }
+}
+
+/// Evaluates an expression against a stored row, predicates giving a boolean or NULL when unknown.
+/// None when it references a column the row does not have, applies arithmetic to a non number or
+/// calls a function the `functions` do not have
+pub fn evaluate(expr : &Expr, row : &StoredRow, functions : &dyn Functions) -> Option<Value> {
+    match expr {
+        Expr::Column(name) => row.row.get(name).cloned(),
+        Expr::Literal(value) => Some(value.clone()),
+        // aggregates are computed per group beforehand and stored under their text, e.g. `COUNT(*)`,
+        // the same goes for subqueries which are run by the execution
+        Expr::Aggregate { .. } | Expr::Subquery(_) | Expr::InSubquery { .. } | Expr::Exists(_) => row.row.get(&expr.to_string()).cloned(),
+        Expr::Function { name, args } => { 
+            let args = args.iter().map(|arg| evaluate(arg, row, functions)).collect::<Option<Vec<Value>>>()?;
+            functions.call(name, args)
+        },
+        Expr::Binary { left, op, right } if op.is_arithmetic() => { 
+            match (evaluate(left, row, functions)?, evaluate(right, row, functions)?) { 
+                (Value::Number(l), Value::Number(r)) => Some(arithmetic(*op, l, r)),
+                (Value::Null, Value::Number(_) | Value::Null) | (Value::Number(_), Value::Null) => Some(Value::Null),
+                _ => None
+            }
+        },
+        // any value but a blob concatenates as its text, NULL when either side is NULL
+        Expr::Binary { left, op : BinaryOp::Concat, right } => { 
+            match (evaluate(left, row, functions)?, evaluate(right, row, functions)?) { 
+                (Value::Blob(_), _) | (_, Value::Blob(_)) => None,
+                (Value::Null, _) | (_, Value::Null) => Some(Value::Null),
+                (left, right) => Some(Value::String(format!("{left}{right}"))),
+            }
+        },
+        Expr::Unary { .. } | Expr::Binary { .. } | Expr::IsNull { .. } | Expr::Between { .. } | Expr::InList { .. } | Expr::Like { .. } => { 
+            Some(truth(expr, row, functions).map_or(Value::Null, Value::Boolean))
+        },
+    }
+}
+
+// division rounds to the precision of a double, dividing by zero gives NULL
+fn arithmetic(op : BinaryOp, left : BigDecimal, right : BigDecimal) -> Value { 
+    match op { 
+        BinaryOp::Add => Value::Number(left + right),
+        BinaryOp::Sub => Value::Number(left - right),
+        BinaryOp::Mul => Value::Number(left * right),
+        BinaryOp::Div if right.is_zero() => Value::Null,
+        BinaryOp::Div => Value::Number((left / right).with_prec(15).normalized()),
+        _ => Value::Null,
+    }
+}
+
+/// Checks whether the row satisfies the predicate, rows for which it is unknown do not match
+pub fn matches(expr : &Expr, row : &StoredRow, functions : &dyn Functions) -> bool {
+    truth(expr, row, functions) == Some(true)
+}
+
+/// Evaluates the predicate with SQL's three-valued logic, None standing for UNKNOWN.
+/// Comparing against NULL, or against a column the row does not have, is unknown
+pub fn truth(expr : &Expr, row : &StoredRow, functions : &dyn Functions) -> Option<bool> {
+    match expr {
+        Expr::Unary { op : UnaryOp::Not, expr } => truth(expr, row, functions).map(|value| !value),
+        Expr::Binary { left, op : BinaryOp::And, right } => and(truth(left, row, functions), truth(right, row, functions)),
+        Expr::Binary { left, op : BinaryOp::Or, right } => {
+            match (truth(left, row, functions), truth(right, row, functions)) {
+                (Some(true), _) | (_, Some(true)) => Some(true),
+                (Some(false), Some(false)) => Some(false),
+                _ => None
+            }
+        },
+        Expr::Binary { left, op, right } if op.is_predicate() => comparison(*op, left, right, row, functions),
+        Expr::IsNull { expr, negated } => {
+            let is_null = evaluate(expr, row, functions).is_none_or(|value| value.is_null());
+            Some(is_null != *negated)
+        },
+        Expr::Between { expr, low, high, negated } => { 
+            and(comparison(BinaryOp::GtEq, expr, low, row, functions), comparison(BinaryOp::LtEq, expr, high, row, functions)).map(|between| between != *negated)
+        },
+        // true when the value equals one of the list, otherwise unknown when the list holds a NULL
+        Expr::InList { expr, list, negated } => { 
+            let value = evaluate(expr, row, functions).filter(|value| !value.is_null())?;
+            let mut unknown = false;
+            for item in list.iter() { 
+                match evaluate(item, row, functions).filter(|item| !item.is_null()) { 
+                    Some(item) if compare(BinaryOp::Eq, &value, &item) => return Some(!*negated),
+                    Some(_) => {},
+                    None => unknown = true,
+                }
+            }
+            (!unknown).then_some(*negated)
+        },
+        Expr::Like { expr, pattern, negated, case_insensitive } => { 
+            let value = evaluate(expr, row, functions).filter(|value| !value.is_null())?;
+            let pattern = evaluate(pattern, row, functions).filter(|pattern| !pattern.is_null())?;
+            let matched = match (value, pattern) { 
+                (Value::String(value), Value::String(pattern)) if *case_insensitive => like(&value.to_lowercase(), &pattern.to_lowercase()),
+                (Value::String(value), Value::String(pattern)) => like(&value, &pattern),
+                _ => false,
+            };
+            Some(matched != *negated)
+        },
+        // a boolean operand is a predicate on its own
+        Expr::Column(_) | Expr::Literal(_) | Expr::Aggregate { .. } | Expr::Function { .. } | Expr::Binary { .. }
+            | Expr::Subquery(_) | Expr::InSubquery { .. } | Expr::Exists(_) => match evaluate(expr, row, functions) {
+            Some(Value::Boolean(value)) => Some(value),
+            Some(Value::Null) | None => None,
+            Some(_) => Some(false),
+        },
+    }
+}
+
+// AND of two truth values, false wins over unknown
+fn and(left : Option<bool>, right : Option<bool>) -> Option<bool> { 
+    match (left, right) {
+        (Some(false), _) | (_, Some(false)) => Some(false),
+        (Some(true), Some(true)) => Some(true),
+        _ => None
+    }
+}
+
+// compares the values of both operands, unknown when either is NULL or can not be evaluated
+fn comparison(op : BinaryOp, left : &Expr, right : &Expr, row : &StoredRow, functions : &dyn Functions) -> Option<bool> { 
+    match (evaluate(left, row, functions), evaluate(right, row, functions)) {
+        (Some(left), Some(right)) if !left.is_null() && !right.is_null() => Some(compare(op, &left, &right)),
+        _ => None
+    }
+}
+
+// a LIKE pattern broken into its wildcards and the characters matched literally
+enum Wildcard { 
+    Many,
+    One,
+    Char(char)
+}
+
+// whether the text matches the LIKE pattern. Matching backtracks to the last `%` on a mismatch,
+// which is enough since a later `%` can absorb anything an earlier one could
+fn like(text : &str, pattern : &str) -> bool { 
+    let mut wildcards = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() { 
+        wildcards.push(match c { 
+            '%' => Wildcard::Many,
+            '_' => Wildcard::One,
+            '\\' => Wildcard::Char(chars.next().unwrap_or('\\')),
+            c => Wildcard::Char(c),
+        });
+    }
+    let text : Vec<char> = text.chars().collect();
+    let (mut t, mut p) = (0, 0);
+    // the wildcard after the last `%` seen and the text position it was resumed from
+    let mut resume : Option<(usize, usize)> = None;
+    while t < text.len() { 
+        match wildcards.get(p) { 
+            Some(Wildcard::Many) => { 
+                p += 1;
+                resume = Some((p, t));
+            },
+            Some(Wildcard::One) => { 
+                t += 1;
+                p += 1;
+            },
+            Some(Wildcard::Char(c)) if *c == text[t] => { 
+                t += 1;
+                p += 1;
+            },
+            _ => match resume { 
+                Some((after, from)) => { 
+                    p = after;
+                    t = from + 1;
+                    resume = Some((after, from + 1));
+                },
+                None => return false,
+            },
+        }
+    }
+    wildcards[p..].iter().all(|wildcard| matches!(wildcard, Wildcard::Many))
+}
+
+// values of different kinds are never ordered against each other, except for dates against
+// timestamps and for string literals spelling out a date or timestamp, e.g. `day > '2024-01-31'`
+fn ordering(left : &Value, right : &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Number(l), Value::Number(r)) => Some(l.cmp(r)),
+        (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+        (Value::Boolean(l), Value::Boolean(r)) => Some(l.cmp(r)),
+        (Value::Date(l), Value::Date(r)) => Some(l.cmp(r)),
+        (Value::Timestamp(l), Value::Timestamp(r)) => Some(l.cmp(r)),
+        (Value::Blob(l), Value::Blob(r)) => Some(l.cmp(r)),
+        (Value::Date(l), Value::Timestamp(r)) => Some(Timestamp::midnight(*l).cmp(r)),
+        (Value::Timestamp(_), Value::Date(_)) => ordering(right, left).map(Ordering::reverse),
+        (Value::Date(_) | Value::Timestamp(_), Value::String(str)) => ordering(left, &temporal(left, str)?),
+        (Value::String(_), Value::Date(_) | Value::Timestamp(_)) => ordering(right, left).map(Ordering::reverse),
+        _ => None
+    }
+}
+
+// reads the string as the same kind of temporal value as `like`
+fn temporal(like : &Value, str : &str) -> Option<Value> {
+    match like {
+        Value::Date(_) => Date::from_str(str).ok().map(Value::Date),
+        _ => Timestamp::from_str(str).ok().map(Value::Timestamp),
+    }
+}
+
+fn compare(op : BinaryOp, left : &Value, right : &Value) -> bool {
+    let Some(ordering) = ordering(left, right) else {
+        return op == BinaryOp::NotEq;
+    };
+    match op {
+        BinaryOp::Eq => ordering == Ordering::Equal,
+        BinaryOp::NotEq => ordering != Ordering::Equal,
+        BinaryOp::Lt => ordering == Ordering::Less,
+        BinaryOp::LtEq => ordering != Ordering::Greater,
+        BinaryOp::Gt => ordering == Ordering::Greater,
+        BinaryOp::GtEq => ordering != Ordering::Less,
+        _ => false,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use bigdecimal::BigDecimal;
+    use sql_one_parser::{expr::Expr, parser::Parse, value::Value};
+
+    use crate::row::StoredRow;
+
+    use super::{matches, truth, Functions, NoFunctions};
+
+    #[test]
+    fn test_matches_compound_predicate() {
+        let mut rows = HashMap::new();
+        rows.insert("id".to_string(), Value::Number(BigDecimal::from(3)));
+        rows.insert("name".to_string(), Value::String("raja".to_string()));
+        let row = StoredRow::new(rows);
+        let predicate = |input : &str| Expr::parse_from_raw(input).unwrap().1;
+
+        assert!(matches(&predicate("id > 1 and name = 'raja'"), &row, &NoFunctions));
+        assert!(matches(&predicate("id = 1 or not (name != 'raja')"), &row, &NoFunctions));
+        assert!(!matches(&predicate("id >= 4 or name < 'a'"), &row, &NoFunctions));
+        assert!(!matches(&predicate("missing = 1"), &row, &NoFunctions));
+    }
+
+    #[test]
+    fn test_three_valued_logic() {
+        let mut rows = HashMap::new();
+        rows.insert("id".to_string(), Value::Number(BigDecimal::from(3)));
+        rows.insert("name".to_string(), Value::Null);
+        let row = StoredRow::new(rows);
+        let predicate = |input : &str| Expr::parse_from_raw(input).unwrap().1;
+
+        assert_eq!(truth(&predicate("name = 'raja'"), &row, &NoFunctions), None);
+        assert_eq!(truth(&predicate("not (name != 'raja')"), &row, &NoFunctions), None);
+        assert_eq!(truth(&predicate("name = null"), &row, &NoFunctions), None);
+        assert_eq!(truth(&predicate("id = 1 and name = 'raja'"), &row, &NoFunctions), Some(false));
+        assert_eq!(truth(&predicate("id = 3 or name = 'raja'"), &row, &NoFunctions), Some(true));
+        assert!(matches(&predicate("name is null and id is not null"), &row, &NoFunctions));
+        assert!(!matches(&predicate("name is not null"), &row, &NoFunctions));
+    }
+
+    #[test]
+    fn test_matches_typed_values() {
+        let mut rows = HashMap::new();
+        rows.insert("active".to_string(), Value::Boolean(true));
+        rows.insert("day".to_string(), Value::parse_from_raw("date '2024-01-31'").unwrap().1);
+        rows.insert("score".to_string(), Value::parse_from_raw("-1.5").unwrap().1);
+        let row = StoredRow::new(rows);
+        let predicate = |input : &str| Expr::parse_from_raw(input).unwrap().1;
+
+        assert!(matches(&predicate("active and score < 0"), &row, &NoFunctions));
+        assert!(!matches(&predicate("not active"), &row, &NoFunctions));
+        assert!(matches(&predicate("day > '2024-01-30' and day < timestamp '2024-01-31 00:00:01'"), &row, &NoFunctions));
+        assert!(matches(&predicate("day = date '2024-01-31'"), &row, &NoFunctions));
+        assert!(!matches(&predicate("day = 'not a date'"), &row, &NoFunctions));
+    }
+
+    struct Shout;
+
+    impl Functions for Shout {
+        fn call(&self, name : &str, args : Vec<Value>) -> Option<Value> {
+            match (name, args.as_slice()) {
+                ("SHOUT", [Value::String(str)]) => Some(Value::String(str.to_uppercase())),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn test_functions_are_given_to_the_evaluation() {
+        let mut rows = HashMap::new();
+        rows.insert("name".to_string(), Value::String("raja".to_string()));
+        let row = StoredRow::new(rows);
+        let expr = |input : &str| Expr::parse_from_raw(input).unwrap().1;
+
+        assert_eq!(super::evaluate(&expr("shout(name)"), &row, &Shout), Some(Value::String("RAJA".to_string())));
+        assert!(matches(&expr("shout(name) = 'RAJA'"), &row, &Shout));
+        // a function the evaluation was not given can not be evaluated, its predicate is unknown
+        assert_eq!(super::evaluate(&expr("shout(name)"), &row, &NoFunctions), None);
+        assert_eq!(super::evaluate(&expr("whisper(name)"), &row, &Shout), None);
+        assert_eq!(truth(&expr("shout(name) = 'RAJA'"), &row, &NoFunctions), None);
+    }
+
+    #[test]
+    fn test_evaluate_arithmetic() {
+        let mut rows = HashMap::new();
+        rows.insert("price".to_string(), Value::parse_from_raw("2.5").unwrap().1);
+        rows.insert("qty".to_string(), Value::Number(BigDecimal::from(4)));
+        rows.insert("note".to_string(), Value::Null);
+        let row = StoredRow::new(rows);
+        let evaluate = |input : &str| super::evaluate(&Expr::parse_from_raw(input).unwrap().1, &row, &NoFunctions);
+
+        assert_eq!(evaluate("price * qty - 1"), Some(Value::Number(BigDecimal::from(9))));
+        assert_eq!(evaluate("qty / 3"), Some(Value::parse_from_raw("1.33333333333333").unwrap().1));
+        assert_eq!(evaluate("qty / 0"), Some(Value::Null));
+        assert_eq!(evaluate("qty + null"), Some(Value::Null));
+        assert_eq!(evaluate("qty + 'a'"), None);
+        assert_eq!(evaluate("qty > 3 and note is null"), Some(Value::Boolean(true)));
+        assert_eq!(evaluate("note = 1"), Some(Value::Null));
+        assert!(matches(&Expr::parse_from_raw("price * 2 = 5").unwrap().1, &row, &NoFunctions));
+    }
+
+    #[test]
+    fn test_between_in_like() {
+        let mut rows = HashMap::new();
+        rows.insert("id".to_string(), Value::Number(BigDecimal::from(3)));
+        rows.insert("name".to_string(), Value::String("Raja_1".to_string()));
+        rows.insert("note".to_string(), Value::Null);
+        let row = StoredRow::new(rows);
+        let predicate = |input : &str| Expr::parse_from_raw(input).unwrap().1;
+
+        assert!(matches(&predicate("id between 3 and 4 and id not between 4 and 10"), &row, &NoFunctions));
+        assert_eq!(truth(&predicate("id between note and 4"), &row, &NoFunctions), None);
+        assert_eq!(truth(&predicate("id between 4 and note"), &row, &NoFunctions), Some(false));
+        assert!(matches(&predicate("id in (1, 2 + 1) and name not in ('raja')"), &row, &NoFunctions));
+        assert_eq!(truth(&predicate("id in (1, null)"), &row, &NoFunctions), None);
+        assert_eq!(truth(&predicate("id not in (1, null)"), &row, &NoFunctions), None);
+        assert_eq!(truth(&predicate("id in (3, null)"), &row, &NoFunctions), Some(true));
+        assert!(matches(&predicate("name like 'R%' and name like '_aja%' and name like '%\\_1'"), &row, &NoFunctions));
+        assert!(!matches(&predicate("name like 'r%' or name like 'Raja' or name like '%\\_2'"), &row, &NoFunctions));
+        assert!(matches(&predicate("name ilike 'r%A%1' and name not ilike '%x%'"), &row, &NoFunctions));
+        assert!(super::like("mississippi", "%iss%ppi"));
+        assert!(super::like("", "%"));
+        assert!(!super::like("abc", "a%d"));
+        assert_eq!(truth(&predicate("note like '%'"), &row, &NoFunctions), None);
+    }
+
+    #[test]
+    fn test_evaluate_concat() {
+        let mut rows = HashMap::new();
+        rows.insert("id".to_string(), Value::Number(BigDecimal::from(3)));
+        rows.insert("name".to_string(), Value::String("raja".to_string()));
+        rows.insert("note".to_string(), Value::Null);
+        let row = StoredRow::new(rows);
+        let evaluate = |input : &str| super::evaluate(&Expr::parse_from_raw(input).unwrap().1, &row, &NoFunctions);
+
+        assert_eq!(evaluate("name || '-' || id"), Some(Value::String("raja-3".to_string())));
+        assert_eq!(evaluate("name || note"), Some(Value::Null));
+        assert!(matches(&Expr::parse_from_raw("name || id = 'raja3'").unwrap().1, &row, &NoFunctions));
+    }
+}
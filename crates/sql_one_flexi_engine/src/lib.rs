@@ -1,3 +1,9 @@
 pub mod page;
 pub mod storage;
-pub mod row;
\ No newline at end of file
+pub mod row;
+pub mod eval;
+pub mod schema;
+pub mod sort;
+pub mod distinct;
+pub mod buffer_pool;
+pub mod spill;
@@ -8,8 +8,13 @@ pub enum RowSerializerError {
 #[derive(Debug)]
 pub enum InternalStorageError { 
     ErrPrimaryKeyNotFound(String),
+    ErrDuplicatePrimaryKey(String),
     ErrWriteToDisk(String),
     ErrReadFromDisk(String),
     ErrInternal(String),
+    // the encoded row, of the given size in bytes, does not fit on an empty page
+    ErrRowTooLarge(usize),
+    // every one of the buffer pool's frames holds a pinned page
+    ErrBufferPoolFull(usize),
     SerializerError(RowSerializerError)
 }
\ No newline at end of file
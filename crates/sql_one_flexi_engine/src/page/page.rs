@@ -1,11 +1,25 @@
-use std::{fs::{self, File, OpenOptions}, io::{Read, Write}};
+use std::{fs::{self, File, OpenOptions}, io::{ErrorKind, Read, Seek, SeekFrom, Write}, path::{Path, PathBuf}};
 
 use serde::{Deserialize, Serialize};
 
 
 pub const PAGE_SIZE : usize = 4096;
 
-
+// the page header: the page id, the LSN of the last change, the free space pointer
+// and the slot count, little endian
+const PAGE_ID : usize = 0;
+const LSN : usize = 4;
+const FREE_SPACE_POINTER : usize = 12;
+const SLOT_COUNT : usize = 14;
+pub const HEADER_SIZE : usize = 16;
+// a slot holds the offset and the length of its tuple, the offset is 0 for a free slot
+pub const SLOT_SIZE : usize = 4;
+
+
+/// A slotted page. The header is followed by the slot array growing towards the end of the
+/// page, while the tuples grow from the end of the page towards the slot array. A tuple is
+/// addressed by its slot, which stays the same while the tuples around it are removed, updated
+/// or moved to close the gaps between them
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Page { 
     pub page_number : usize,
@@ -14,61 +28,198 @@ pub struct Page {
 
 
 impl Page { 
-    pub fn default(page_number : usize) -> Self {
-        Self { 
+    /// An empty page
+    pub fn new(page_number : usize) -> Self {
+        let mut page = Self { 
             page_number,
-            data : Vec::new()
+            data : vec![0; PAGE_SIZE]
+        };
+        page.put(PAGE_ID, &(page_number as u32).to_le_bytes());
+        page.put(FREE_SPACE_POINTER, &(PAGE_SIZE as u16).to_le_bytes());
+        page
+    }
+
+    fn get<const N : usize>(&self, at : usize) -> [u8; N] { 
+        self.data[at..at + N].try_into().unwrap()
+    }
+
+    fn put(&mut self, at : usize, bytes : &[u8]) { 
+        self.data[at..at + bytes.len()].copy_from_slice(bytes);
+    }
+
+    // records a change to the page in its header
+    fn changed(&mut self) { 
+        self.set_lsn(self.lsn() + 1);
+    }
+
+    /// The page id kept in the header
+    pub fn id(&self) -> usize { 
+        u32::from_le_bytes(self.get(PAGE_ID)) as usize
+    }
+
+    /// The log sequence number of the last change made to the page. There is no log yet, so
+    /// every change to the page advances it by one
+    pub fn lsn(&self) -> u64 { 
+        u64::from_le_bytes(self.get(LSN))
+    }
+
+    pub fn set_lsn(&mut self, lsn : u64) { 
+        self.put(LSN, &lsn.to_le_bytes());
+    }
+
+    pub fn slot_count(&self) -> usize { 
+        u16::from_le_bytes(self.get(SLOT_COUNT)) as usize
+    }
+
+    // the offset the tuples start at
+    fn free_space_pointer(&self) -> usize { 
+        u16::from_le_bytes(self.get(FREE_SPACE_POINTER)) as usize
+    }
+
+    // the offset and length of the slot's tuple, None for a free or missing slot
+    fn slot(&self, slot : usize) -> Option<(usize, usize)> { 
+        if slot >= self.slot_count() { 
+            return None;
         }
+        let at = HEADER_SIZE + slot * SLOT_SIZE;
+        let offset = u16::from_le_bytes(self.get(at)) as usize;
+        let length = u16::from_le_bytes(self.get(at + 2)) as usize;
+        (offset != 0).then_some((offset, length))
     }
 
-    pub fn new(page_number : usize, data : Vec<u8>) -> Self {
-        Self { 
-            page_number,
-            data
+    fn set_slot(&mut self, slot : usize, offset : usize, length : usize) { 
+        let at = HEADER_SIZE + slot * SLOT_SIZE;
+        self.put(at, &(offset as u16).to_le_bytes());
+        self.put(at + 2, &(length as u16).to_le_bytes());
+    }
+
+    /// The bytes between the slot array and the tuples
+    pub fn free_space(&self) -> usize { 
+        self.free_space_pointer() - HEADER_SIZE - self.slot_count() * SLOT_SIZE
+    }
+
+    /// The bytes a tuple can take up once the gaps left by removed tuples are closed, less
+    /// the size of a slot when every slot is in use
+    pub fn available(&self) -> usize { 
+        let used : usize = (0..self.slot_count()).filter_map(|slot| self.slot(slot)).map(|(_, length)| length).sum();
+        let free = PAGE_SIZE - HEADER_SIZE - self.slot_count() * SLOT_SIZE - used;
+        match self.free_slot() { 
+            Some(_) => free,
+            None => free.saturating_sub(SLOT_SIZE),
         }
-    }   
+    }
 
-    pub fn append_chunks(&mut self, mut chunks : Vec<u8>, range : Vec<usize>)  { 
-        self.data.append(&mut chunks);
+    fn free_slot(&self) -> Option<usize> { 
+        (0..self.slot_count()).find(|slot| self.slot(*slot).is_none())
     }
 
-    pub fn delete_chunks(&mut self , start : usize , end : usize, is_disk_cleanup : bool, table_name : String) -> Page{
-        let mut updated_data = Vec::new();
-        for (index , item) in self.data.iter().enumerate() {
-            if index >= start && index <= end { 
-                continue
+    /// The tuple stored in the slot
+    pub fn tuple(&self, slot : usize) -> Option<&[u8]> { 
+        self.slot(slot).map(|(offset, length)| &self.data[offset..offset + length])
+    }
+
+    /// Stores the tuple in a free slot, or a new one, returning the slot. None when the page
+    /// does not have room for it
+    pub fn insert(&mut self, tuple : &[u8]) -> Option<usize> { 
+        if tuple.is_empty() || tuple.len() > self.available() { 
+            return None;
+        }
+        let slot = match self.free_slot() { 
+            Some(slot) => slot,
+            None => { 
+                let slot = self.slot_count();
+                // the new slot must not run into the tuples, so the gaps are closed first
+                if tuple.len() + SLOT_SIZE > self.free_space() { 
+                    self.compact();
+                }
+                self.put(SLOT_COUNT, &((slot + 1) as u16).to_le_bytes());
+                slot
             }
-            updated_data.push(*item);
+        };
+        self.place(slot, tuple);
+        self.changed();
+        Some(slot)
+    }
+
+    // writes the tuple in front of the others, closing the gaps first when it does not fit
+    fn place(&mut self, slot : usize, tuple : &[u8]) { 
+        if tuple.len() > self.free_space() { 
+            self.compact();
         }
-        self.data = updated_data;
-        if is_disk_cleanup { 
-            self.write(table_name);
+        let offset = self.free_space_pointer() - tuple.len();
+        self.data[offset..offset + tuple.len()].copy_from_slice(tuple);
+        self.put(FREE_SPACE_POINTER, &(offset as u16).to_le_bytes());
+        self.set_slot(slot, offset, tuple.len());
+    }
+
+    /// Frees the slot, its tuple's bytes are reclaimed when the page is compacted
+    pub fn remove(&mut self, slot : usize) -> bool { 
+        if self.slot(slot).is_none() { 
+            return false;
         }
-        self.clone()
+        self.set_slot(slot, 0, 0);
+        self.changed();
+        true
     }
 
-    pub fn read(page_number : usize, table_name : String) -> Option<Self> { 
-        let mut file = match File::open(format!("storage/{}/page_{}.bin", table_name, page_number)) {
-            Ok(file) => file,
-            Err(err) => {
-                println!("here error opening the file {}", err);
-                return None;
-            },
+    /// Replaces the tuple stored in the slot, in place when it is not longer than the old one.
+    /// False, leaving the page unchanged, when the page does not have room for it
+    pub fn update(&mut self, slot : usize, tuple : &[u8]) -> bool { 
+        let Some((offset, length)) = self.slot(slot) else { 
+            return false;
         };
-        let mut buffer = vec![0; PAGE_SIZE];
-        match file.read(&mut buffer) {
-            Ok(_) => Some(Self{page_number: page_number, data : buffer}),
-            Err(err) => {
-                println!("error reading the file {}", err);
-                None
-            },
+        if tuple.is_empty() { 
+            return false;
         }
+        if tuple.len() <= length { 
+            self.data[offset..offset + tuple.len()].copy_from_slice(tuple);
+            self.set_slot(slot, offset, tuple.len());
+            self.changed();
+            return true;
+        }
+        // the slot is in use, so available() counts no new slot
+        if tuple.len() > self.available() + length { 
+            return false;
+        }
+        self.set_slot(slot, 0, 0);
+        self.place(slot, tuple);
+        self.changed();
+        true
     }
 
-    pub fn read_chunks(page_number : usize, chunk_range: Vec<usize>, table_name: String) -> Option<Vec<u8>>{
-    
+    // moves the tuples to the end of the page, leaving no gaps between them
+    fn compact(&mut self) { 
+        let mut tuples : Vec<(usize, usize, usize)> = (0..self.slot_count())
+            .filter_map(|slot| self.slot(slot).map(|(offset, length)| (slot, offset, length)))
+            .collect();
+        // moving the tuples nearest to the end first never overwrites one yet to be moved
+        tuples.sort_by_key(|(_, offset, _)| std::cmp::Reverse(*offset));
+        let mut end = PAGE_SIZE;
+        for (slot, offset, length) in tuples { 
+            self.data.copy_within(offset..offset + length, end - length);
+            end -= length;
+            self.set_slot(slot, end, length);
+        }
+        self.put(FREE_SPACE_POINTER, &(end as u16).to_le_bytes());
+    }
 
-        let mut file = match File::open(format!("storage/{}/page_{}.bin", table_name, page_number)) {
+    /// The file holding the pages of the table one after the other, page `n` starting at
+    /// byte `(n - 1) * PAGE_SIZE`
+    pub fn file(table_name : &str) -> PathBuf { 
+        Path::new("storage").join(format!("{}.pages", table_name))
+    }
+
+    /// The number of pages in the table's file
+    pub fn page_count(table_name : &str) -> usize { 
+        fs::metadata(Self::file(table_name)).map_or(0, |metadata| metadata.len() as usize / PAGE_SIZE)
+    }
+
+    fn offset(page_number : usize) -> u64 { 
+        (page_number.saturating_sub(1) * PAGE_SIZE) as u64
+    }
+
+    pub fn read(page_number : usize, table_name : String) -> Option<Self> { 
+        let mut file = match File::open(Self::file(&table_name)) {
             Ok(file) => file,
             Err(err) => {
                 println!("here error opening the file {}", err);
@@ -76,52 +227,48 @@ impl Page {
             },
         };
         let mut buffer = vec![0; PAGE_SIZE];
-        match file.read(&mut buffer) {
-            Ok(_) => { 
-                let data: Vec<u8> = buffer.iter().enumerate().filter(|(index , item)| index >= chunk_range.get(0).unwrap() && index <= chunk_range.get(1).unwrap())
-                    .map(|(index, item)| *item)
-                    .collect();
-                Some(data)
-            },
+        match file.seek(SeekFrom::Start(Self::offset(page_number))).and_then(|_| file.read_exact(&mut buffer)) {
+            Ok(_) => Some(Self{page_number: page_number, data : buffer}),
             Err(err) => {
-                println!("error reading the file {}", err);
+                println!("error reading page {} : {}", page_number, err);
                 None
             },
         }
     }
 
-    pub fn delete(page_number : usize, table_name : String) -> Result<(), String>{
-        fs::remove_file(format!("storage/{}/page_{}.bin", table_name, page_number)).map_err(|err| err.to_string())
+    /// Reads the tuple stored in the slot of the page from disk
+    pub fn read_tuple(page_number : usize, slot : usize, table_name : String) -> Option<Vec<u8>> { 
+        Self::read(page_number, table_name)?.tuple(slot).map(<[u8]>::to_vec)
     }
 
+    /// Removes the table's file along with all of its pages
+    pub fn delete(table_name : &str) -> Result<(), String>{
+        match fs::remove_file(Self::file(table_name)) { 
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.to_string()),
+            _ => Ok(()),
+        }
+    }
 
+    /// Cuts the table's file short after its first `page_count` pages
+    pub fn truncate(table_name : &str, page_count : usize) -> Result<(), String> { 
+        match OpenOptions::new().write(true).open(Self::file(table_name)) { 
+            Ok(file) => file.set_len((page_count * PAGE_SIZE) as u64).map_err(|err| err.to_string()),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
 
+    /// Writes the page at its place in the table's file, growing the file for a new page
     pub fn write(&self, table_name : String) -> bool { 
-
-        let current_dir = match std::env::current_dir() {
-            Ok(dir) => dir,
-            Err(err) => {
-                eprintln!("Error getting current directory: {}", err);
-                return false;
-            }
-        };
-        let storage_dir = current_dir.join("storage");
-        if let Err(err) = fs::create_dir_all(&storage_dir) {
+        if let Err(err) = fs::create_dir_all("storage") {
             eprintln!("Error creating storage directory: {}", err);
             return false;
         }
-
-        let table_dir = storage_dir.join(table_name);
-        if let Err(err) = fs::create_dir_all(&table_dir) {
-            eprintln!("Error creating table directory: {}", err);
-            return false;
-        }
-
-        let file_path = table_dir.join(format!("page_{}.bin", self.page_number));
         let mut file = match OpenOptions::new()
             .write(true)
             .create(true)
-            .open(&file_path) 
+            .truncate(false)
+            .open(Self::file(&table_name)) 
         {
             Ok(file) => file ,
             Err(err) => {
@@ -129,7 +276,7 @@ impl Page {
                 return false;
             }
         };
-        match file.write_all(&self.data) {
+        match file.seek(SeekFrom::Start(Self::offset(self.page_number))).and_then(|_| file.write_all(&self.data)) {
             Ok(_) => true,
             Err(err) => {
                 println!("error writing to file : {}", err);
@@ -149,17 +296,16 @@ mod tests {
     use super::*;
     use bigdecimal::{BigDecimal, FromPrimitive};
     use sql_one_parser::value::Value;
-    use crate::{page::{serializer::RowSerializer, table::{key_type, PageData, RowMetaData, TableMetaData}}, row::StoredRow}; 
+    use crate::{page::{serializer::RowSerializer, table::{key_type, RowMetaData, TableMetaData}}, row::StoredRow}; 
 
     //#[test]
     pub fn test_writeandRead()  {
-        // create page metadata and table metadata
-        let mut page_data = PageData::default(1);
+        // create table metadata
         let table_data = TableMetaData::new("users".to_string(), "id".to_string(), key_type::Number);
         let mut row_data_vec = Vec::new();
         // pretest setup 
         // cleanup the disk data
-        let result = Page::delete(1, "users".to_string());
+        let result = Page::delete("users");
         if result.is_err() {
             panic!("clean up stuck");
         }
@@ -172,28 +318,22 @@ mod tests {
 
         // 2 serialize into vec<u8>
 
-        let data = row.to_bytes().unwrap();
-        println!("data is {:?}", data.data);
-        let (page_number , range) = page_data.getChunkData(data.size); 
-        let row1 = RowMetaData::new(table_data, Value::Number(BigDecimal::from_i16(1).unwrap()), data.size, range, page_number);
+        let columns = vec!["id".to_string(), "name".to_string()];
+        let data = row.to_bytes(&columns).unwrap();
+        let mut page = Page::new(1);
+        let slot = page.insert(&data.data).unwrap();
+        let row1 = RowMetaData::new(table_data, Value::Number(BigDecimal::from_i16(1).unwrap()), data.size, page.page_number, slot);
         row_data_vec.push(row1.clone());
         
-        //println!("data is {:?}", data);
-        let page = Page::new(1, data.data);
-        println!("{:?}", page);
-        
         // write it to disk
         let result = page.write("users".to_string());
-        println!("{}", result);
 
         // check the assertion
         assert!(result);
-        let page = Page::read_chunks(1, row1.range.clone(),  "users".to_string());
+        let page = Page::read_tuple(1, row1.slot,  "users".to_string());
         assert_eq!(page.is_none(), false);
         if let Some(data) = page {
-            println!("page is {:?}", data);
-            let row = StoredRow::from_bytes(&data).unwrap();
-            println!("row is {:#?}", row);
+            assert_eq!(StoredRow::from_bytes(&data, &columns).unwrap(), row);
         }        
     }
 
@@ -203,9 +343,7 @@ mod tests {
         let page = Page::read(1, "users".to_string());
         assert_eq!(page.is_none(), false);
         if let Some(data) = page {
-            println!("page is {:?}", data);
-            let row = StoredRow::from_bytes(&data.data).unwrap();
-            println!("row is {:#?}", row);
+            assert!(StoredRow::from_bytes(data.tuple(0).unwrap(), &["id".to_string(), "name".to_string()]).is_ok());
         }
     }
 
@@ -216,11 +354,76 @@ mod tests {
         rows.insert("id".to_string(), Value::Number(BigDecimal::from(1)));
         rows.insert("name".to_string(), Value::String("raja".to_string()));
         let row = StoredRow::new(rows);
-        let bytes = row.to_bytes().unwrap();
-        println!("bytes are {:?}",bytes.data);
-        let retrieved = StoredRow::from_bytes(&bytes.data).unwrap();
-        println!("retrieved is {:#?}", retrieved);
+        let columns = vec!["id".to_string(), "name".to_string()];
+        let bytes = row.to_bytes(&columns).unwrap();
+        let retrieved = StoredRow::from_bytes(&bytes.data, &columns).unwrap();
         assert_eq!(retrieved, row);
         
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_slots_stay_put() { 
+        let mut page = Page::new(7);
+        page.set_lsn(42);
+        let a = page.insert(b"first").unwrap();
+        let b = page.insert(b"second").unwrap();
+        let c = page.insert(b"third").unwrap();
+        assert_eq!((page.id(), page.lsn(), page.slot_count()), (7, 45, 3));
+        assert_eq!(page.free_space(), PAGE_SIZE - HEADER_SIZE - 3 * SLOT_SIZE - 16);
+
+        // removing a tuple leaves the slots of the others as they are, and its slot is reused
+        assert!(page.remove(b));
+        assert!(!page.remove(b));
+        assert_eq!(page.tuple(b), None);
+        assert_eq!(page.tuple(c), Some(&b"third"[..]));
+        assert_eq!(page.insert(b"fourth"), Some(b));
+
+        // a longer tuple moves within the page keeping its slot, a shorter one is written in place
+        assert!(page.update(a, b"the first tuple grown"));
+        assert!(page.update(c, b"3"));
+        assert_eq!(page.tuple(a), Some(&b"the first tuple grown"[..]));
+        assert_eq!(page.tuple(b), Some(&b"fourth"[..]));
+        assert_eq!(page.tuple(c), Some(&b"3"[..]));
+        // every change advances the LSN, a failed one leaves it as it is
+        assert!(!page.remove(b + 10));
+        assert_eq!(page.lsn(), 49);
+    }
+
+    #[test]
+    fn test_full_page_is_compacted() { 
+        let mut page = Page::new(1);
+        let tuple = vec![7; 1000];
+        let slots : Vec<usize> = (0..4).map(|_| page.insert(&tuple).unwrap()).collect();
+        assert_eq!(page.insert(&tuple), None);
+        assert!(!page.update(slots[0], &vec![1; 1100]));
+        assert_eq!(page.tuple(slots[0]), Some(&tuple[..]));
+
+        // the gaps left by removed tuples are closed once a tuple does not fit in front of the others
+        page.remove(slots[1]);
+        page.remove(slots[2]);
+        assert!(page.free_space() < 1500);
+        assert_eq!(page.insert(&[2; 1500]), Some(slots[1]));
+        assert!(page.update(slots[3], &[3; 1050]));
+        assert_eq!(page.tuple(slots[0]), Some(&tuple[..]));
+        assert_eq!(page.tuple(slots[1]), Some(&[2; 1500][..]));
+        assert_eq!(page.tuple(slots[3]), Some(&[3; 1050][..]));
+        assert_eq!(page.available(), PAGE_SIZE - HEADER_SIZE - 4 * SLOT_SIZE - 3550);
+    }
+
+    #[test]
+    fn test_new_slot_closes_the_gaps() { 
+        let mut page = Page::new(1);
+        let slots : Vec<usize> = (0..3).map(|_| page.insert(&[7; 1000]).unwrap()).collect();
+        let length = page.free_space() - SLOT_SIZE;
+        let last = page.insert(&vec![8; length]).unwrap();
+        assert_eq!(page.free_space(), 0);
+
+        // every slot is in use, so the tuple needs a new slot the gap left by the update makes room for
+        assert!(page.update(slots[0], b"shrunk"));
+        assert_eq!(page.insert(&[9; 500]), Some(4));
+        assert_eq!(page.tuple(slots[0]), Some(&b"shrunk"[..]));
+        assert_eq!(page.tuple(slots[1]), Some(&[7; 1000][..]));
+        assert_eq!(page.tuple(last), Some(&vec![8; length][..]));
+        assert_eq!(page.tuple(4), Some(&[9; 500][..]));
+    }
+}
@@ -1,32 +1,266 @@
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, ToPrimitive};
+use sql_one_parser::{temporal::{Date, Timestamp}, value::Value};
+
 use crate::row::StoredRow;
 
 use super::error::RowSerializerError;
 
-pub trait RowSerializer { 
-    fn to_bytes(&self) -> Result<Chunk, RowSerializerError> ;
-    fn from_bytes(bytes : &[u8]) -> Result<Self, RowSerializerError> where Self:Sized;
+/// The first byte of a row in the first version of the binary format. Rows written before the
+/// binary format are json objects, their first byte `{` telling them apart
+pub const BINARY_ROW_V1 : u8 = 1;
+
+const JSON_ROW : u8 = b'{';
+
+pub trait RowSerializer {
+    /// Encodes the row with its values in the order of the columns, failing for a row holding a
+    /// column they do not name
+    fn to_bytes(&self, columns : &[String]) -> Result<Chunk, RowSerializerError> ;
+    /// Decodes a row of any format, `columns` being the columns it was encoded with. Rows written
+    /// before the binary format are json, they are read but never written
+    fn from_bytes(bytes : &[u8], columns : &[String]) -> Result<Self, RowSerializerError> where Self:Sized;
 }
 
-pub struct Chunk { 
-    pub size : usize, 
+pub struct Chunk {
+    pub size : usize,
     pub data : Vec<u8>
 }
 
+// a row of the binary format is a bitmap with a bit set for every NULL column, followed by the
+// values of the other columns in column order. The columns carry no types, so every value starts
+// with a byte telling its kind. Numbers, booleans, dates and timestamps take a fixed number of
+// little endian bytes, text, blobs and decimals too large for a float are prefixed by their length
+const INT : u8 = 0;
+const FLOAT : u8 = 1;
+const DECIMAL : u8 = 2;
+const TEXT : u8 = 3;
+const BOOLEAN : u8 = 4;
+const DATE : u8 = 5;
+const TIMESTAMP : u8 = 6;
+const BLOB : u8 = 7;
+
+// writes the value that is not NULL, numbers taking the narrowest encoding that reads back exactly
+fn write_value(bytes : &mut Vec<u8>, value : &Value) -> Result<(), RowSerializerError> {
+    match value {
+        Value::Number(n) => {
+            let (digits, scale) = n.as_bigint_and_exponent();
+            if let Some(int) = digits.to_i64().filter(|_| scale == 0) {
+                bytes.push(INT);
+                bytes.extend(int.to_le_bytes());
+                return Ok(());
+            }
+            match n.to_f64().filter(|float| BigDecimal::from_str(&float.to_string()).is_ok_and(|read| read.as_bigint_and_exponent() == (digits.clone(), scale))) {
+                Some(float) => {
+                    bytes.push(FLOAT);
+                    bytes.extend(float.to_le_bytes());
+                },
+                None => write_bytes(bytes, DECIMAL, n.to_string().as_bytes())?,
+            }
+        },
+        Value::String(str) => write_bytes(bytes, TEXT, str.as_bytes())?,
+        Value::Boolean(bool) => bytes.extend([BOOLEAN, *bool as u8]),
+        Value::Date(date) => {
+            bytes.push(DATE);
+            write_date(bytes, date);
+        },
+        Value::Timestamp(ts) => {
+            bytes.push(TIMESTAMP);
+            write_date(bytes, &ts.date);
+            bytes.extend([ts.hour, ts.minute, ts.second]);
+            bytes.extend(ts.micros.to_le_bytes());
+        },
+        Value::Blob(blob) => write_bytes(bytes, BLOB, blob)?,
+        Value::Null => return Err(RowSerializerError::ErrRowSerialize("NULL is kept in the null bitmap".to_string())),
+    }
+    Ok(())
+}
+
+fn write_date(bytes : &mut Vec<u8>, date : &Date) {
+    bytes.extend(date.year.to_le_bytes());
+    bytes.extend([date.month, date.day]);
+}
+
+// a value of variable length, prefixed by its length as a u32
+fn write_bytes(bytes : &mut Vec<u8>, kind : u8, value : &[u8]) -> Result<(), RowSerializerError> {
+    let len = u32::try_from(value.len()).map_err(|_| RowSerializerError::ErrRowSerialize(format!("value of {} bytes is too long", value.len())))?;
+    bytes.push(kind);
+    bytes.extend(len.to_le_bytes());
+    bytes.extend(value);
+    Ok(())
+}
+
+// reads a binary row from the front, failing on bytes that run out before the row does
+struct Reader<'a> {
+    bytes : &'a [u8]
+}
+
+impl<'a> Reader<'a> {
+    fn take<const N : usize>(&mut self) -> Result<[u8; N], RowSerializerError> {
+        self.slice(N).map(|bytes| bytes.try_into().expect("slice of N bytes"))
+    }
+
+    fn slice(&mut self, len : usize) -> Result<&'a [u8], RowSerializerError> {
+        if self.bytes.len() < len {
+            return Err(RowSerializerError::ErrRowDeserialize("binary row ends early".to_string()));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn variable(&mut self) -> Result<&'a [u8], RowSerializerError> {
+        let len = u32::from_le_bytes(self.take()?);
+        self.slice(len as usize)
+    }
+
+    fn date(&mut self) -> Result<Option<Date>, RowSerializerError> {
+        let year = i32::from_le_bytes(self.take()?);
+        let [month, day] = self.take()?;
+        Ok(Date::new(year, month, day))
+    }
+
+    fn value(&mut self) -> Result<Value, RowSerializerError> {
+        let invalid = |kind : &str| RowSerializerError::ErrRowDeserialize(format!("invalid {kind} in binary row"));
+        let text = |bytes : &[u8]| String::from_utf8(bytes.to_vec()).map_err(|_| invalid("text"));
+        let [kind] = self.take()?;
+        Ok(match kind {
+            INT => Value::Number(BigDecimal::from(i64::from_le_bytes(self.take()?))),
+            FLOAT => Value::Number(BigDecimal::from_str(&f64::from_le_bytes(self.take()?).to_string()).map_err(|_| invalid("float"))?),
+            DECIMAL => Value::Number(BigDecimal::from_str(&text(self.variable()?)?).map_err(|_| invalid("decimal"))?),
+            TEXT => Value::String(text(self.variable()?)?),
+            BOOLEAN => Value::Boolean(self.take::<1>()?[0] != 0),
+            DATE => Value::Date(self.date()?.ok_or_else(|| invalid("date"))?),
+            TIMESTAMP => {
+                let date = self.date()?.ok_or_else(|| invalid("timestamp"))?;
+                let [hour, minute, second] = self.take()?;
+                let micros = u32::from_le_bytes(self.take()?);
+                Value::Timestamp(Timestamp::new(date, hour, minute, second, micros).ok_or_else(|| invalid("timestamp"))?)
+            },
+            BLOB => Value::Blob(self.variable()?.to_vec()),
+            kind => return Err(RowSerializerError::ErrRowDeserialize(format!("unknown value kind {kind} in binary row"))),
+        })
+    }
+}
+
+/// Encodes the values the way a binary row holds them, a null bitmap followed by the values that
+/// are not NULL, each starting with the byte telling its kind, so every value reads back as the
+/// kind it was written as
+pub fn encode_values(values : &[&Value]) -> Result<Vec<u8>, RowSerializerError> {
+    let mut nulls = vec![0; values.len().div_ceil(8)];
+    let mut fields = Vec::new();
+    for (index, value) in values.iter().enumerate() {
+        match value {
+            Value::Null => nulls[index / 8] |= 1 << (index % 8),
+            value => write_value(&mut fields, value)?,
+        }
+    }
+    nulls.extend(fields);
+    Ok(nulls)
+}
+
+/// Decodes the `count` values encoded by `encode_values`
+pub fn decode_values(bytes : &[u8], count : usize) -> Result<Vec<Value>, RowSerializerError> {
+    let mut reader = Reader { bytes };
+    let nulls = reader.slice(count.div_ceil(8))?;
+    let values = (0..count)
+        .map(|index| match nulls[index / 8] & (1 << (index % 8)) {
+            0 => reader.value(),
+            _ => Ok(Value::Null),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if !reader.bytes.is_empty() {
+        return Err(RowSerializerError::ErrRowDeserialize(format!("binary row holds more than the {} values it was written with", count)));
+    }
+    Ok(values)
+}
+
+impl StoredRow {
+    fn to_binary(&self, columns : &[String]) -> Result<Vec<u8>, RowSerializerError> {
+        let values : Vec<&Value> = columns.iter().map(|col| self.row.get(col).unwrap_or(&Value::Null)).collect();
+        let mut bytes = vec![BINARY_ROW_V1];
+        bytes.extend(encode_values(&values)?);
+        Ok(bytes)
+    }
+
+    fn from_binary(bytes : &[u8], columns : &[String]) -> Result<Self, RowSerializerError> {
+        let values = decode_values(bytes, columns.len())?;
+        Ok(StoredRow::new(columns.iter().cloned().zip(values).collect()))
+    }
+}
+
 
 impl RowSerializer for StoredRow {
-    fn to_bytes(&self) -> Result<Chunk, RowSerializerError> {
-        
-        match serde_json::to_vec(&self).map_err(|err| err.to_string()) {
-            Ok(buffer) => Ok(Chunk { size : buffer.len(), data : buffer}),
-            Err(err) => Err(RowSerializerError::ErrRowSerialize(err)),
-        } 
-    }
-
-    fn from_bytes(bytes : &[u8]) -> Result<Self, RowSerializerError>  {
-        match serde_json::from_slice::<Self>(bytes).map_err(|err| err.to_string()) {
-            Ok(value) => Ok(value),
-            Err(err) => Err(RowSerializerError::ErrRowDeserialize(err)),
+    fn to_bytes(&self, columns : &[String]) -> Result<Chunk, RowSerializerError> {
+        if let Some(col) = self.row.keys().find(|col| !columns.contains(col)) {
+            return Err(RowSerializerError::ErrRowSerialize(format!("column {} is not a column of the row's table", col)));
         }
-        
+        let buffer = self.to_binary(columns)?;
+        Ok(Chunk { size : buffer.len(), data : buffer })
+    }
+
+    fn from_bytes(bytes : &[u8], columns : &[String]) -> Result<Self, RowSerializerError>  {
+        match bytes.first() {
+            Some(&BINARY_ROW_V1) => Self::from_binary(&bytes[1..], columns),
+            Some(&JSON_ROW) => serde_json::from_slice::<Self>(bytes).map_err(|err| RowSerializerError::ErrRowDeserialize(err.to_string())),
+            Some(tag) => Err(RowSerializerError::ErrRowDeserialize(format!("unknown row format {tag}"))),
+            None => Err(RowSerializerError::ErrRowDeserialize("empty row".to_string())),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, str::FromStr};
+
+    use bigdecimal::BigDecimal;
+    use sql_one_parser::{temporal::{Date, Timestamp}, value::Value};
+
+    use crate::row::StoredRow;
+
+    use super::{RowSerializer, BINARY_ROW_V1};
+
+    fn number(n : &str) -> Value {
+        Value::Number(BigDecimal::from_str(n).unwrap())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_binary_row_round_trip() {
+        let columns : Vec<String> = ["id", "price", "exact", "huge", "name", "gone", "paid", "day", "at", "data"].iter().map(|col| col.to_string()).collect();
+        let date = Date::new(2024, 2, 29).unwrap();
+        let values = [
+            number("-7"), number("19.99"), number("1.50"), number("123456789012345678901234567890"), Value::String("raja".to_string()),
+            Value::Null, Value::Boolean(true), Value::Date(date), Value::Timestamp(Timestamp::new(date, 23, 59, 1, 250).unwrap()), Value::Blob(vec![0, 255]),
+        ];
+        let row = StoredRow::new(columns.iter().cloned().zip(values).collect());
+
+        let chunk = row.to_bytes(&columns).unwrap();
+        assert_eq!(chunk.data[0], BINARY_ROW_V1);
+        assert!(!chunk.data.windows(5).any(|window| window == b"price"));
+        // the version, two bytes of null bitmap, then a kind byte and eight bytes for the int
+        assert_eq!(chunk.data[1..4], [0b0010_0000, 0, 0]);
+        assert_eq!(chunk.data[4..12], (-7i64).to_le_bytes());
+        let read = StoredRow::from_bytes(&chunk.data, &columns).unwrap();
+        assert_eq!(read, row);
+        // the scale of a decimal is kept along with its value
+        assert_eq!(read.row["exact"].to_string(), "1.50");
+        assert!(StoredRow::from_bytes(&chunk.data, &columns[1..]).is_err());
+        assert!(StoredRow::from_bytes(&chunk.data[..chunk.data.len() - 1], &columns).is_err());
+    }
+
+    #[test]
+    fn test_json_rows_stay_readable() {
+        let mut values = HashMap::new();
+        values.insert("id".to_string(), number("1"));
+        values.insert("name".to_string(), Value::String("raja".to_string()));
+        let row = StoredRow::new(values);
+
+        let json = serde_json::to_vec(&row).unwrap();
+        assert_eq!(StoredRow::from_bytes(&json, &["id".to_string()]).unwrap(), row);
+        assert_eq!(StoredRow::from_bytes(&json, &[]).unwrap(), row);
+        // a row holding a column missing from the columns is not written at all
+        assert!(row.to_bytes(&["id".to_string()]).is_err());
+        assert!(StoredRow::from_bytes(&[9, 0], &[]).is_err());
+    }
+}
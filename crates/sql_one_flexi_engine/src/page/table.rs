@@ -1,15 +1,26 @@
+use std::collections::HashMap;
+
 use crate::row::StoredRow;
 use serde::{Deserialize, Serialize};
 use sql_one_parser::value::Value;
 
 use super::page::PAGE_SIZE;
 
+/// The hidden column keying the rows of a table without a primary key of its own, see `TableMetaData::row_id`
+pub const ROW_ID : &str = "#rowid";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TableMetaData { 
     pub table_name : String, 
     //page_number : String,
     pub primary_key : String,
-    pub prim_key_type : key_type
+    pub prim_key_type : key_type,
+    // the columns of a composite primary key, empty when the key is the single `primary_key` column
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub key_columns : Vec<String>,
+    // the column sets declared UNIQUE, no two rows may hold the same values for any of them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unique_keys : Vec<Vec<String>>
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,51 +34,165 @@ pub struct RowMetaData {
     pub table : TableMetaData,
     pub primary_key : Value, 
     pub row_size : usize,
-    pub range : Vec<usize>,
-    pub page_number : usize
-}
-#[derive(Debug, Clone , Serialize, Deserialize)]
-pub struct PageData { 
     pub page_number : usize,
-    pub current_size : usize,
-    pub max_size : usize
+    // the slot of the row's tuple on its page, see `page::Page`
+    #[serde(default)]
+    pub slot : usize,
+    // the bytes of a row stored before pages were slotted, until opening the storage moves it onto one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range : Option<Vec<usize>>,
+    // the schema version the row was written with, see `schema::SchemaChange`
+    #[serde(default)]
+    pub schema_version : usize
+}
+/// The bytes a tuple can take up on every page of the table's file, see `Page::available`,
+/// so that a row is stored on the first page with room for it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FreeSpaceMap { 
+    // the free bytes of page n at index n - 1
+    pub pages : Vec<usize>
+}
+
+/// The primary key of the row holding each set of values of the unique keys of a table, see
+/// `TableMetaData::unique_keys`. A row holding a NULL for a unique key is not indexed under it,
+/// as NULLs never collide
+#[derive(Debug, Clone, Default)]
+pub struct UniqueIndex { 
+    keys : HashMap<Vec<String>, HashMap<Vec<Value>, Value>>
 }
 
 
 impl TableMetaData { 
     pub fn new(table_name : String, primary_key : String, prim_key_type: key_type) -> Self { 
-        Self{table_name, primary_key, prim_key_type}
+        Self{table_name, primary_key, prim_key_type, key_columns : Vec::new(), unique_keys : Vec::new()}
+    }
+
+    /// Builds the metadata of a table keyed by the combination of several columns
+    pub fn composite(table_name : String, key_columns : Vec<String>) -> Self { 
+        Self{table_name, primary_key : key_columns.join(","), prim_key_type : key_type::Strings, key_columns, unique_keys : Vec::new()}
+    }
+
+    /// Builds the metadata of a table keyed by the hidden `ROW_ID` column, numbering its rows
+    pub fn row_id(table_name : String) -> Self { 
+        Self::new(table_name, ROW_ID.to_string(), key_type::Number)
+    }
+
+    /// Whether the rows are keyed by the hidden `ROW_ID` column
+    pub fn is_row_id(&self) -> bool { 
+        self.key_columns.is_empty() && self.primary_key == ROW_ID
+    }
+
+    /// The columns making up the primary key
+    pub fn key_columns(&self) -> Vec<String> { 
+        if self.key_columns.is_empty() { 
+            vec![self.primary_key.clone()]
+        } else { 
+            self.key_columns.clone()
+        }
+    }
+
+    pub fn is_key_column(&self, column : &str) -> bool { 
+        self.key_columns().iter().any(|key| key == column)
+    }
+
+    /// The value the row is indexed under. A composite key is encoded as the json array of
+    /// its column values, None when any of the key columns is missing from the row. Numbers
+    /// are normalized first, so that equal numbers written with a different scale, like 1 and
+    /// 1.0, give the same key
+    pub fn key_of(&self, row : &StoredRow) -> Option<Value> { 
+        if self.key_columns.is_empty() { 
+            return row.row.get(&self.primary_key).cloned();
+        }
+        let values = self.key_columns.iter()
+            .map(|column| row.row.get(column).map(|value| match value { 
+                Value::Number(number) => Value::Number(number.normalized()),
+                value => value.clone(),
+            }))
+            .collect::<Option<Vec<Value>>>()?;
+        serde_json::to_string(&values).ok().map(Value::String)
+    }
+
+    pub fn rename_column(&mut self, from : &str, to : &str) { 
+        let rename = |column : &mut String| if column == from { *column = to.to_string() };
+        rename(&mut self.primary_key);
+        self.key_columns.iter_mut().for_each(rename);
+        self.unique_keys.iter_mut().flatten().for_each(rename);
+        if !self.key_columns.is_empty() { 
+            self.primary_key = self.key_columns.join(",");
+        }
+    }
+
+    /// Drops the unique constraints the column takes part in
+    pub fn drop_column(&mut self, column : &str) { 
+        self.unique_keys.retain(|key| !key.iter().any(|c| c == column));
     }
 }
 
-impl PageData { 
-    pub fn default(page_number: usize) -> Self { 
-        Self{page_number: 1, current_size: 0, max_size: PAGE_SIZE}
+impl FreeSpaceMap { 
+    /// The first page with room for a tuple of the size, a new page at the end of the file when none has
+    pub fn page_for(&self, size : usize) -> usize { 
+        self.pages.iter().position(|free| *free >= size).unwrap_or(self.pages.len()) + 1
     }
-    pub fn getChunkData(&mut self, row_size : usize) -> (usize, Vec<usize>) { 
-        if(self.isFull(row_size)) { 
-            self.page_number = self.page_number + 1;
-            self.current_size = 0;
-            return (self.page_number , vec![self.current_size, self.current_size + row_size - 1]);
+
+    pub fn set(&mut self, page_number : usize, free : usize) { 
+        if self.pages.len() < page_number { 
+            self.pages.resize(page_number, PAGE_SIZE);
         }
-        let size = self.current_size;
-        self.current_size = self.current_size + row_size;
-        (self.page_number , vec![size, size + row_size - 1])
+        self.pages[page_number - 1] = free;
     }
 
-    pub fn isFull(&self, current_size : usize) -> bool { 
-        self.current_size + current_size > self.max_size
-    } 
+    /// The number of pages in the table's file
+    pub fn page_count(&self) -> usize { 
+        self.pages.len()
+    }
 
-    pub fn rollBackCurrentSize(&mut self, size : usize)  {
-        self.current_size = self.current_size - size;
-    } 
+    /// Forgets the pages past the end of the file
+    pub fn truncate(&mut self, page_count : usize) { 
+        self.pages.truncate(page_count);
+    }
+}
+
+impl UniqueIndex { 
+    /// The values the row holds for the unique key, None when one is NULL or missing
+    pub fn values(unique_key : &[String], row : &StoredRow) -> Option<Vec<Value>> { 
+        unique_key.iter().map(|col| row.row.get(col).filter(|value| !value.is_null()).cloned()).collect()
+    }
+
+    /// The primary key of the row holding the values for the unique key
+    pub fn holder(&self, unique_key : &[String], values : &[Value]) -> Option<&Value> { 
+        self.keys.get(unique_key)?.get(values)
+    }
+
+    /// Indexes the row stored under the primary key under each of the unique keys
+    pub fn insert(&mut self, unique_keys : &[Vec<String>], row : &StoredRow, key : &Value) { 
+        for unique_key in unique_keys.iter() { 
+            if let Some(values) = Self::values(unique_key, row) { 
+                self.keys.entry(unique_key.clone()).or_default().insert(values, key.clone());
+            }
+        }
+    }
+
+    /// Drops the values of the row stored under the primary key, unless another row holds them by now
+    pub fn remove(&mut self, unique_keys : &[Vec<String>], row : &StoredRow, key : &Value) { 
+        for unique_key in unique_keys.iter() { 
+            let (Some(values), Some(rows)) = (Self::values(unique_key, row), self.keys.get_mut(unique_key)) else { 
+                continue;
+            };
+            if rows.get(&values) == Some(key) { 
+                rows.remove(&values);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) { 
+        self.keys.clear();
+    }
 }
 
 
 impl RowMetaData { 
-    pub fn new(table : TableMetaData, primary_key : Value,  row_size: usize, range : Vec<usize>, page_number : usize) -> Self { 
-        Self{table, primary_key, row_size, range, page_number}
+    pub fn new(table : TableMetaData, primary_key : Value,  row_size: usize, page_number : usize, slot : usize) -> Self { 
+        Self{table, primary_key, row_size, page_number, slot, range : None, schema_version : 0}
     }
 }
 
@@ -79,22 +204,50 @@ mod tests {
     use crate::row::StoredRow;
     use sql_one_parser::value::Value;
 
-    use crate::page::serializer::RowSerializer;
+    use crate::page::{page::Page, serializer::RowSerializer};
 
-    use super::{key_type, PageData, RowMetaData, TableMetaData};
+    use super::{key_type, RowMetaData, TableMetaData};
 
 
     //#[test] 
     pub fn sample_test() { 
-        let mut page_data = PageData::default(1);
+        let mut page = Page::new(1);
         let table_data = TableMetaData::new("users".to_string(), "user_id".to_string(), key_type::Number);
         let mut rows = HashMap::new();
         rows.insert("id".to_string(), Value::Number(BigDecimal::from(1)));
         rows.insert("name".to_string(), Value::String("raja".to_string()));
         let row = StoredRow::new(rows);
-        let chunk = row.to_bytes().unwrap();
-        let (page_number, chunk_range) = page_data.getChunkData(chunk.size);
-        let row_data = RowMetaData::new(table_data, Value::Number(BigDecimal::from_i16(1).unwrap()),  chunk.size, chunk_range, page_number);
+        let chunk = row.to_bytes(&["id".to_string(), "name".to_string()]).unwrap();
+        let slot = page.insert(&chunk.data).unwrap();
+        let row_data = RowMetaData::new(table_data, Value::Number(BigDecimal::from_i16(1).unwrap()),  chunk.size, page.page_number, slot);
         println!("row meta data : {:#?}", row_data);
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn test_free_space_map() { 
+        let mut free_space = super::FreeSpaceMap::default();
+        assert_eq!(free_space.page_for(100), 1);
+        free_space.set(1, 50);
+        free_space.set(3, 400);
+        // a page between the known ones counts as empty
+        assert_eq!(free_space.page_count(), 3);
+        assert_eq!(free_space.page_for(100), 2);
+        free_space.set(2, 10);
+        assert_eq!(free_space.page_for(100), 3);
+        assert_eq!(free_space.page_for(500), 4);
+        free_space.truncate(1);
+        assert_eq!(free_space.page_for(100), 2);
+    }
+
+    #[test]
+    fn test_composite_key_normalizes_numbers() { 
+        let table = TableMetaData::composite("orders".to_string(), vec!["id".to_string(), "item".to_string()]);
+        let row = |id : &str| StoredRow::new(HashMap::from([
+            ("id".to_string(), Value::Number(id.parse().unwrap())),
+            ("item".to_string(), Value::String("x".to_string())),
+        ]));
+        assert_eq!(table.key_of(&row("1")), table.key_of(&row("1.0")));
+        assert_eq!(table.key_of(&row("10")), table.key_of(&row("10.00")));
+        assert_ne!(table.key_of(&row("1")), table.key_of(&row("10")));
+    }
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+use sql_one_parser::value::Value;
+
+use crate::row::StoredRow;
+
+/// A change made to the table definition after rows were written.
+///
+/// Rows are not rewritten when the table is altered, instead every row remembers the
+/// schema version it was written with and the changes made since are replayed on read
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SchemaChange {
+    AddColumn { name : String, default : Option<Value> },
+    DropColumn(String),
+    RenameColumn { from : String, to : String }
+}
+
+impl SchemaChange {
+    pub fn apply(&self, row : &mut StoredRow) {
+        match self {
+            SchemaChange::AddColumn { name, default } => {
+                row.row.insert(name.clone(), default.clone().unwrap_or(Value::Null));
+            },
+            SchemaChange::DropColumn(name) => {
+                row.row.remove(name);
+            },
+            SchemaChange::RenameColumn { from, to } => {
+                if let Some(value) = row.row.remove(from) {
+                    row.row.insert(to.clone(), value);
+                }
+            },
+        }
+    }
+}
+
+/// Brings a row written at `version` up to date with the latest schema
+pub fn migrate(mut row : StoredRow, version : usize, changes : &[SchemaChange]) -> StoredRow {
+    for change in changes.iter().skip(version) {
+        change.apply(&mut row);
+    }
+    row
+}
+
+/// The columns of a table created with the `initial` columns once the changes are applied, in
+/// their order. A renamed column keeps its place and an added one comes last
+pub fn columns(initial : &[String], changes : &[SchemaChange]) -> Vec<String> {
+    let mut columns = initial.to_vec();
+    for change in changes {
+        match change {
+            SchemaChange::AddColumn { name, .. } => columns.push(name.clone()),
+            SchemaChange::DropColumn(name) => columns.retain(|col| col != name),
+            SchemaChange::RenameColumn { from, to } => {
+                if let Some(col) = columns.iter_mut().find(|col| *col == from) {
+                    *col = to.clone();
+                }
+            },
+        }
+    }
+    columns
+}
+
+/// The columns a table was created with, given the `current` columns it has once the changes were
+/// applied. A dropped column is put back last, `columns` gives back the current columns either way
+pub fn initial_columns(current : &[String], changes : &[SchemaChange]) -> Vec<String> {
+    let mut columns = current.to_vec();
+    for change in changes.iter().rev() {
+        match change {
+            SchemaChange::AddColumn { name, .. } => {
+                if let Some(index) = columns.iter().rposition(|col| col == name) {
+                    columns.remove(index);
+                }
+            },
+            SchemaChange::DropColumn(name) => columns.push(name.clone()),
+            SchemaChange::RenameColumn { from, to } => {
+                if let Some(col) = columns.iter_mut().find(|col| *col == to) {
+                    *col = from.clone();
+                }
+            },
+        }
+    }
+    columns
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use bigdecimal::BigDecimal;
+    use sql_one_parser::value::Value;
+
+    use crate::row::StoredRow;
+
+    use super::{columns, initial_columns, migrate, SchemaChange};
+
+    #[test]
+    fn test_migrate_replays_changes_after_version() {
+        let mut rows = HashMap::new();
+        rows.insert("id".to_string(), Value::Number(BigDecimal::from(1)));
+        rows.insert("age".to_string(), Value::Number(BigDecimal::from(30)));
+        let changes = vec![
+            SchemaChange::AddColumn { name : "age".to_string(), default : Some(Value::Number(BigDecimal::from(18))) },
+            SchemaChange::DropColumn("age".to_string()),
+            SchemaChange::AddColumn { name : "age".to_string(), default : Some(Value::Number(BigDecimal::from(0))) },
+            SchemaChange::RenameColumn { from : "id".to_string(), to : "user_id".to_string() },
+        ];
+
+        let migrated = migrate(StoredRow::new(rows), 1, &changes);
+
+        let mut expected = HashMap::new();
+        expected.insert("user_id".to_string(), Value::Number(BigDecimal::from(1)));
+        expected.insert("age".to_string(), Value::Number(BigDecimal::from(0)));
+        assert_eq!(migrated, StoredRow::new(expected));
+    }
+
+    #[test]
+    fn test_columns_follow_changes() {
+        let initial = vec!["id".to_string(), "name".to_string(), "age".to_string()];
+        let changes = vec![
+            SchemaChange::RenameColumn { from : "name".to_string(), to : "title".to_string() },
+            SchemaChange::DropColumn("id".to_string()),
+            SchemaChange::AddColumn { name : "id".to_string(), default : None },
+        ];
+
+        assert_eq!(columns(&initial, &changes[..1]), vec!["id", "title", "age"]);
+        assert_eq!(columns(&initial, &changes), vec!["title", "age", "id"]);
+        let current = columns(&initial, &changes);
+        assert_eq!(initial_columns(&current, &changes), vec!["name", "age", "id"]);
+        assert_eq!(columns(&initial_columns(&current, &changes), &changes), current);
+    }
+}
@@ -0,0 +1,218 @@
+use std::{cmp::Ordering, fs::{self, File}, io::{BufReader, BufWriter, Write}, path::PathBuf, sync::atomic::{AtomicUsize, Ordering as AtomicOrdering}, vec};
+
+use sql_one_parser::{commands::select_condition::OrderBy, value::Value};
+
+use crate::{page::error::{InternalStorageError, RowSerializerError}, row::StoredRow, spill};
+
+/// The number of rows sorted in memory before they are spilled to disk as a sorted run
+pub const SORT_BUFFER_ROWS : usize = 4096;
+
+// distinguishes the run files of sorts running at the same time
+static NEXT_SORT_ID : AtomicUsize = AtomicUsize::new(0);
+
+/// Orders the rows by the given keys. NULLs sort after every other value, so they come
+/// last in ascending and first in descending order
+pub fn compare(order_by : &[OrderBy], left : &StoredRow, right : &StoredRow) -> Ordering {
+    for key in order_by.iter() {
+        let ordering = left.row.get(&key.column).unwrap_or(&Value::Null)
+            .cmp(right.row.get(&key.column).unwrap_or(&Value::Null));
+        let ordering = if key.descending { ordering.reverse() } else { ordering };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// An external merge sort. Rows are buffered and sorted in memory, every full buffer is
+/// written to a temporary file as a sorted run, and the runs are merged when the rows are taken
+pub struct ExternalSort {
+    order_by : Vec<OrderBy>,
+    buffer_rows : usize,
+    buffer : Vec<StoredRow>,
+    runs : Vec<PathBuf>,
+    id : usize
+}
+
+impl ExternalSort {
+    pub fn new(order_by : Vec<OrderBy>) -> Self {
+        Self::with_buffer(order_by, SORT_BUFFER_ROWS)
+    }
+
+    pub fn with_buffer(order_by : Vec<OrderBy>, buffer_rows : usize) -> Self {
+        let id = NEXT_SORT_ID.fetch_add(1, AtomicOrdering::Relaxed);
+        Self { order_by, buffer_rows : buffer_rows.max(1), buffer : Vec::new(), runs : Vec::new(), id }
+    }
+
+    pub fn push(&mut self, row : StoredRow) -> Result<(), InternalStorageError> {
+        self.buffer.push(row);
+        if self.buffer.len() >= self.buffer_rows {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// The number of sorted runs written to disk so far
+    pub fn spilled_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    // sorts the buffer and writes it out as a run, every row as the names of its columns followed
+    // by their values, see `spill::write_values`
+    fn spill(&mut self) -> Result<(), InternalStorageError> {
+        let write_err = |err : std::io::Error| InternalStorageError::ErrWriteToDisk(err.to_string());
+        self.buffer.sort_by(|left, right| compare(&self.order_by, left, right));
+        let path = std::env::temp_dir().join(format!("sql_one_sort_{}_{}_{}.run", std::process::id(), self.id, self.runs.len()));
+        let mut writer = BufWriter::new(File::create(&path).map_err(write_err)?);
+        self.runs.push(path);
+        for row in self.buffer.drain(..) {
+            let names : Vec<Value> = row.row.keys().cloned().map(Value::String).collect();
+            let values : Vec<&Value> = names.iter().chain(row.row.values()).collect();
+            spill::write_values(&mut writer, &values)?;
+        }
+        writer.flush().map_err(write_err)
+    }
+
+    /// The rows in order. Rows sorted in memory are handed out as they are, runs spilled to disk
+    /// are merged lazily, one row at a time as they are taken
+    pub fn finish(mut self) -> Result<SortedRows, InternalStorageError> {
+        if self.runs.is_empty() {
+            self.buffer.sort_by(|left, right| compare(&self.order_by, left, right));
+            let buffer = std::mem::take(&mut self.buffer).into_iter();
+            return Ok(SortedRows { order_by : std::mem::take(&mut self.order_by), buffer, runs : Vec::new(), paths : Vec::new() });
+        }
+        if !self.buffer.is_empty() {
+            self.spill()?;
+        }
+        let runs = self.runs.iter()
+            .map(Run::open)
+            .collect::<Result<Vec<_>, _>>()?;
+        let paths = std::mem::take(&mut self.runs);
+        Ok(SortedRows { order_by : std::mem::take(&mut self.order_by), buffer : Vec::new().into_iter(), runs, paths })
+    }
+}
+
+impl Drop for ExternalSort {
+    fn drop(&mut self) {
+        for path in self.runs.iter() {
+            fs::remove_file(path).ok();
+        }
+    }
+}
+
+/// The rows of a finished external sort, the sorted runs are removed once it is dropped
+pub struct SortedRows {
+    order_by : Vec<OrderBy>,
+    buffer : vec::IntoIter<StoredRow>,
+    runs : Vec<Run>,
+    paths : Vec<PathBuf>
+}
+
+impl Iterator for SortedRows {
+    type Item = Result<StoredRow, InternalStorageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.runs.is_empty() {
+            return self.buffer.next().map(Ok);
+        }
+        // the next row is the smallest head among the runs
+        let index = self.runs.iter().enumerate()
+            .filter_map(|(index, run)| run.head.as_ref().map(|head| (index, head)))
+            .min_by(|(_, left), (_, right)| compare(&self.order_by, left, right))
+            .map(|(index, _)| index)?;
+        let next = self.runs[index].advance();
+        if next.is_err() {
+            self.runs.clear();
+        }
+        Some(next)
+    }
+}
+
+impl Drop for SortedRows {
+    fn drop(&mut self) {
+        for path in self.paths.iter() {
+            fs::remove_file(path).ok();
+        }
+    }
+}
+
+// a sorted run being read back, `head` is its smallest row not yet merged
+struct Run {
+    reader : BufReader<File>,
+    head : Option<StoredRow>
+}
+
+impl Run {
+    fn open(path : &PathBuf) -> Result<Self, InternalStorageError> {
+        let file = File::open(path).map_err(|err| InternalStorageError::ErrReadFromDisk(err.to_string()))?;
+        let mut run = Self { reader : BufReader::new(file), head : None };
+        run.head = run.next_row()?;
+        Ok(run)
+    }
+
+    fn next_row(&mut self) -> Result<Option<StoredRow>, InternalStorageError> {
+        let Some(mut values) = spill::read_values(&mut self.reader)? else {
+            return Ok(None);
+        };
+        let row_values = values.split_off(values.len() / 2);
+        let row = values.into_iter().zip(row_values)
+            .map(|(name, value)| match name {
+                Value::String(name) => Ok((name, value)),
+                _ => Err(InternalStorageError::SerializerError(RowSerializerError::ErrRowDeserialize("sorted run holds a column without a name".to_string()))),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Some(StoredRow::new(row)))
+    }
+
+    // takes the head and reads the following row of the run
+    fn advance(&mut self) -> Result<StoredRow, InternalStorageError> {
+        let next = self.next_row()?;
+        std::mem::replace(&mut self.head, next)
+            .ok_or_else(|| InternalStorageError::ErrInternal("sorted run is exhausted".to_string()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use bigdecimal::BigDecimal;
+    use sql_one_parser::{commands::select_condition::OrderBy, value::Value};
+
+    use crate::row::StoredRow;
+
+    use super::ExternalSort;
+
+    fn row(id : i32, name : Option<&str>) -> StoredRow {
+        let mut rows = HashMap::new();
+        rows.insert("id".to_string(), Value::Number(BigDecimal::from(id)));
+        rows.insert("name".to_string(), name.map_or(Value::Null, |name| Value::String(name.to_string())));
+        StoredRow::new(rows)
+    }
+
+    #[test]
+    fn test_external_sort_spills_and_merges() {
+        let order_by = vec![
+            OrderBy { column : "name".to_string(), descending : true },
+            OrderBy { column : "id".to_string(), descending : false },
+        ];
+        let mut sort = ExternalSort::with_buffer(order_by, 2);
+        let input = [(5, Some("b")), (1, Some("a")), (4, None), (3, Some("007")), (2, Some("1e3")), (6, Some("b"))];
+        for (id, name) in input {
+            sort.push(row(id, name)).unwrap();
+        }
+        assert_eq!(sort.spilled_runs(), 3);
+
+        let mut rows = sort.finish().unwrap();
+        let sorted = rows.by_ref().take(5).collect::<Result<Vec<_>, _>>().unwrap();
+        // the runs are merged as rows are taken and removed once the rows are dropped
+        let paths = rows.paths.clone();
+        assert!(paths.iter().all(|path| path.exists()));
+        drop(rows);
+        assert!(!paths.iter().any(|path| path.exists()));
+        // strings that look like numbers are read back as the strings they were
+        let expected = vec![row(4, None), row(5, Some("b")), row(6, Some("b")), row(1, Some("a")), row(2, Some("1e3"))];
+        assert_eq!(sorted, expected);
+    }
+}
@@ -0,0 +1,39 @@
+use std::io::{ErrorKind, Read, Write};
+
+use sql_one_parser::value::Value;
+
+use crate::page::{error::{InternalStorageError, RowSerializerError}, serializer};
+
+// a record of a spill file is the number of its values and the length of their bytes, both u32
+// little endian, followed by the values encoded the way a binary row holds them. Unlike json, a
+// string that looks like a number reads back as the string it was
+
+/// Appends the values to the file rows are spilled to while they are sorted or de-duplicated
+pub fn write_values(writer : &mut impl Write, values : &[&Value]) -> Result<(), InternalStorageError> {
+    let bytes = serializer::encode_values(values).map_err(InternalStorageError::SerializerError)?;
+    let too_long = || InternalStorageError::SerializerError(RowSerializerError::ErrRowSerialize(format!("spilled row of {} bytes is too long", bytes.len())));
+    let count = u32::try_from(values.len()).map_err(|_| too_long())?;
+    let len = u32::try_from(bytes.len()).map_err(|_| too_long())?;
+    let write_err = |err : std::io::Error| InternalStorageError::ErrWriteToDisk(err.to_string());
+    writer.write_all(&count.to_le_bytes()).map_err(write_err)?;
+    writer.write_all(&len.to_le_bytes()).map_err(write_err)?;
+    writer.write_all(&bytes).map_err(write_err)
+}
+
+/// Reads the values written next by `write_values`, None at the end of the file
+pub fn read_values(reader : &mut impl Read) -> Result<Option<Vec<Value>>, InternalStorageError> {
+    let read_err = |err : std::io::Error| InternalStorageError::ErrReadFromDisk(err.to_string());
+    let mut count = [0; 4];
+    match reader.read_exact(&mut count) {
+        Ok(()) => {},
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(read_err(err)),
+    }
+    let mut len = [0; 4];
+    reader.read_exact(&mut len).map_err(read_err)?;
+    let mut bytes = vec![0; u32::from_le_bytes(len) as usize];
+    reader.read_exact(&mut bytes).map_err(read_err)?;
+    serializer::decode_values(&bytes, u32::from_le_bytes(count) as usize)
+        .map(Some)
+        .map_err(InternalStorageError::SerializerError)
+}
@@ -1,30 +1,43 @@
-use std::{collections::{btree_map, BTreeMap, HashMap}, fmt::format, fs::{self, File}, io::{Read, Write}, panic::RefUnwindSafe};
+use std::{cmp::Ordering, collections::{btree_map, BTreeMap, HashSet}, fs::{self, File}, io::{Read, Write}, ops::Bound, sync::Arc};
 
 
 use serde::{Deserialize, Serialize};
-use sql_one_parser::{commands::select_condition::Condition, value::Value};
-
-use crate::{page::{self, error::InternalStorageError, page::{Page, PAGE_SIZE}, serializer::RowSerializer, table::{key_type, PageData, RowMetaData, TableMetaData}}, row::StoredRow};
-
-#[derive(Clone, Debug)]
-struct Temp { 
-    value : Value,
-    row : RowMetaData
-}
+use sql_one_parser::{expr::{BinaryOp, Expr}, value::Value};
 
+use crate::{buffer_pool::BufferPool, eval::{self, Functions}, schema::{self, SchemaChange}, page::{error::InternalStorageError, page::{Page, HEADER_SIZE, PAGE_SIZE, SLOT_SIZE}, serializer::{Chunk, RowSerializer}, table::{key_type, FreeSpaceMap, RowMetaData, TableMetaData, UniqueIndex}}, row::StoredRow};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Storage { 
     pub table_metadata : TableMetaData,
-    pub pages : Page, 
-    pub page_metadata : PageData,
+    // the free bytes of the pages of the table's file, see `Page::file`
+    #[serde(default)]
+    pub free_space : FreeSpaceMap,
+    // the pool every page of the table's file is read and changed through
+    #[serde(skip, default = "BufferPool::shared")]
+    pub pool : Arc<BufferPool>,
     pub rows : btree_map::BTreeMap<Value, RowMetaData>,
-    pub file_name : String
+    pub file_name : String,
+    #[serde(default)]
+    pub schema_changes : Vec<SchemaChange>,
+    /// the columns the table was created with, giving the order of the values of a binary row.
+    /// Tables stored before it was kept have their rows in json, those are only read
+    #[serde(default)]
+    pub columns : Vec<String>,
+    // the rows holding the values of the table's unique keys, indexed again when the storage is opened
+    #[serde(skip)]
+    unique : UniqueIndex
 }
       
 
 impl Storage { 
-    pub fn new(table_metadata : Option<TableMetaData>, file_name : String) -> Self {
+    pub fn new(table_metadata : Option<TableMetaData>, columns : &[String], file_name : String) -> Self {
+        Self::open(table_metadata, columns, file_name, BufferPool::shared())
+    }
+
+    /// Loads the storage saved in the file, or starts the table of the metadata when there is
+    /// none, reading and changing its pages through the pool. `columns` are the current columns
+    /// of the table, those a new table starts with
+    pub fn open(table_metadata : Option<TableMetaData>, columns : &[String], file_name : String, pool : Arc<BufferPool>) -> Self {
         match fs::metadata(file_name.clone()) {
             Ok(metadata) => { 
                 if metadata.is_file() {
@@ -33,7 +46,13 @@ impl Storage {
                             let mut str = String::new();
                             match file.read_to_string(&mut str) {
                                 Ok(_) => { 
-                                    let s : Self = serde_json::from_str(&str).unwrap();
+                                    let mut s : Self = serde_json::from_str(&str).unwrap();
+                                    s.pool = pool;
+                                    s.restore_columns(columns);
+                                    if let Err(err) = s.migrate_legacy_rows() { 
+                                        panic!("panicked at storage : {:?}", err);
+                                    }
+                                    s.index_unique_keys();
                                     s
                                 },
                                 Err(err) => panic!("panicked at storage : {:?}", err),
@@ -45,11 +64,11 @@ impl Storage {
 
                     
                 } else { 
-                    Self::from_table_meta(table_metadata.unwrap(), file_name.clone())
+                    Self::from_table_meta(table_metadata.unwrap(), columns.to_vec(), file_name.clone(), pool)
                 }
             },
             Err(_) => { 
-                Self::from_table_meta(table_metadata.unwrap(), file_name.clone())
+                Self::from_table_meta(table_metadata.unwrap(), columns.to_vec(), file_name.clone(), pool)
             },
         }
         //let table_metadata = TableMetaData::new()
@@ -57,22 +76,18 @@ impl Storage {
     }
 
     
-    pub fn from_table_meta(table_metadata : TableMetaData, file_name : String) -> Self {
-        let pages = Page::new(1, Vec::new());
-        let page_metadata = PageData::default(1);
+    pub fn from_table_meta(table_metadata : TableMetaData, columns : Vec<String>, file_name : String, pool : Arc<BufferPool>) -> Self {
         let rows = btree_map::BTreeMap::new();
-        Self { table_metadata ,  pages, page_metadata , rows, file_name}
+        Self { table_metadata , free_space : FreeSpaceMap::default(), pool, rows, file_name, schema_changes : Vec::new(), columns, unique : UniqueIndex::default() }
     }
     pub fn default() -> Self {
-        let pages = Page::new(1, Vec::new());
-        let page_metadata = PageData::default(1);
         let rows = btree_map::BTreeMap::new();
         let t_meta = TableMetaData::new(
             "User".to_string(), 
             "id".to_string(),
             key_type::Number
         );
-        Self { table_metadata : t_meta.clone(),  pages, page_metadata , rows, file_name : format!("{}_storage.json", t_meta.table_name.clone())}
+        Self { table_metadata : t_meta.clone(), free_space : FreeSpaceMap::default(), pool : BufferPool::shared(), rows, file_name : format!("{}_storage.json", t_meta.table_name.clone()), schema_changes : Vec::new(), columns : Vec::new(), unique : UniqueIndex::default()}
     }
     pub fn save_to_json(&self) -> Result<(), String> { 
         let serialized_storage = serde_json::to_string(self).unwrap();
@@ -80,32 +95,97 @@ impl Storage {
         file.write_all(serialized_storage.as_bytes()).map_err(|err| err.to_string())
     }
 
-    pub fn isPageFull(&self, chunk_size: usize) -> bool { 
+    // sets the columns the table was created with from the `current` columns of the table, for a
+    // table stored before they were kept. Its rows are written in the binary format from then on
+    fn restore_columns(&mut self, current : &[String]) {
+        if self.columns.is_empty() {
+            self.columns = schema::initial_columns(current, &self.schema_changes);
+        }
+    }
 
-        if self.pages.data.len() == PAGE_SIZE { 
-            return true;
-        };
+    // moves the rows stored before pages were slotted, each in a page file of its own under
+    // `storage/<table>/`, onto the slotted pages of the table's file in the binary format, then
+    // removes their directory. The rows are left where they were when one can not be moved
+    fn migrate_legacy_rows(&mut self) -> Result<(), InternalStorageError> { 
+        let legacy : Vec<RowMetaData> = self.rows.values().filter(|row| row.range.is_some()).cloned().collect();
+        let columns = self.columns_at(self.schema_changes.len());
+        let mut chunks : Vec<(Value, Chunk)> = Vec::new();
+        for meta in legacy.iter() { 
+            let row = self.read_legacy(meta)?;
+            chunks.push((meta.primary_key.clone(), row.to_bytes(&columns).map_err(InternalStorageError::SerializerError)?));
+        }
+        for meta in legacy.iter() { 
+            self.rows.remove(&meta.primary_key);
+        }
+        if let Err(err) = self.store_chunks(chunks) { 
+            for meta in legacy { 
+                self.rows.insert(meta.primary_key.clone(), meta);
+            }
+            return Err(err);
+        }
+        if !legacy.is_empty() { 
+            self.save_to_json().map_err(InternalStorageError::ErrWriteToDisk)?;
+        }
+        let table_dir = format!("storage/{}", self.table_metadata.table_name);
+        if fs::metadata(&table_dir).is_ok() { 
+            fs::remove_dir_all(&table_dir).map_err(|err| InternalStorageError::ErrWriteToDisk(err.to_string()))?;
+        }
+        Ok(())
+    }
 
-        self.pages.data.len() + chunk_size > PAGE_SIZE
+    // reads a row stored before pages were slotted, the bytes of its range in the page file of its own
+    fn read_legacy(&self, meta : &RowMetaData) -> Result<StoredRow, InternalStorageError> { 
+        let file = format!("storage/{}/page_{}.bin", self.table_metadata.table_name, meta.page_number);
+        let bytes = fs::read(&file).map_err(|err| InternalStorageError::ErrReadFromDisk(format!("{file} : {err}")))?;
+        let bytes = match meta.range.as_deref() { 
+            Some([start, end]) => bytes.get(*start..=*end),
+            _ => None,
+        }.ok_or_else(|| InternalStorageError::ErrReadFromDisk(format!("{file} does not hold the row {}", meta.primary_key)))?;
+        let row = StoredRow::from_bytes(bytes, &self.columns_at(meta.schema_version)).map_err(InternalStorageError::SerializerError)?;
+        Ok(schema::migrate(row, meta.schema_version, &self.schema_changes))
     }
 
-    // pub fn page_exist(&self) -> bool { 
-    //     self.pages.len() > 0
-    // }
+    // indexes the values the stored rows hold for the table's unique keys, the table is only read
+    // when it has some
+    fn index_unique_keys(&mut self) { 
+        let mut unique = UniqueIndex::default();
+        if !self.table_metadata.unique_keys.is_empty() { 
+            for row in self.scan(false) { 
+                if let Some(key) = self.table_metadata.key_of(&row) { 
+                    unique.insert(&self.table_metadata.unique_keys, &row, &key);
+                }
+            }
+        }
+        self.unique = unique;
+    }
 
-    pub fn create_new_page(&mut self, page_number : usize) {
-        let page = Page::default(page_number);
-        self.pages = page;
+    /// The primary key of the stored row holding the values for the unique key, see `TableMetaData::unique_keys`
+    pub fn unique_holder(&self, unique_key : &[String], values : &[Value]) -> Option<&Value> { 
+        self.unique.holder(unique_key, values)
     }
 
-    pub fn read(&mut self, prim_key_value : Value) -> Result<StoredRow, InternalStorageError> {
+    // the stored rows under the keys, read only when the table has unique keys to unindex them from
+    fn unique_rows(&self, keys : &[Value]) -> Vec<(Value, StoredRow)> { 
+        if self.table_metadata.unique_keys.is_empty() { 
+            return Vec::new();
+        }
+        keys.iter().filter_map(|key| self.read(key.clone()).ok().map(|row| (key.clone(), row))).collect()
+    }
+
+    /// The columns of the table at the schema version, the order rows written at that version
+    /// hold their values in
+    pub fn columns_at(&self, version : usize) -> Vec<String> {
+        schema::columns(&self.columns, &self.schema_changes[..version.min(self.schema_changes.len())])
+    }
+
+    pub fn read(&self, prim_key_value : Value) -> Result<StoredRow, InternalStorageError> {
         let row = self.rows.get(&prim_key_value);
         match row {
             Some(value) => {
-                match  Page::read_chunks(value.page_number, value.range.clone(), value.table.table_name.clone()) {
+                match  self.read_bytes(value) {
                     Some(bytes) => {
-                        match  StoredRow::from_bytes(&bytes.clone())  {
-                            Ok(row) => Ok(row),
+                        match  StoredRow::from_bytes(&bytes, &self.columns_at(value.schema_version))  {
+                            Ok(row) => Ok(schema::migrate(row, value.schema_version, &self.schema_changes)),
                             Err(err) => Err(InternalStorageError::SerializerError(err)),
                         }
                     },
@@ -116,215 +196,430 @@ impl Storage {
         }
     }
 
-    pub fn filter_row(&mut self, val : Vec<Value> ) -> BTreeMap<Value, RowMetaData> { 
-        let mut row_temp_map = BTreeMap::new();
-        for v in val { 
-            let mut index: usize = 0;
-            for (i, (value, row) ) in self.rows.iter().enumerate() { 
-                if *value == v { 
-                    index = i;
-                }
-            }
-            let mut temp = Vec::<Temp>::new();
-            for ( i , (value , row) ) in self.rows.iter().enumerate() { 
-                if i >= index { 
-                    temp.push(Temp{value : value.clone(), row : row.clone()})
-                }
-            }
-            println!("temp is {:?}", temp);
-            
+    // the stored bytes of the row, read from its page in the buffer pool
+    fn read_bytes(&self, meta : &RowMetaData) -> Option<Vec<u8>> { 
+        let page = self.pool.pin(&meta.table.table_name, meta.page_number).ok()?;
+        let tuple = page.read().tuple(meta.slot).map(<[u8]>::to_vec);
+        tuple
+    }
 
-            let mut another_temp = Vec::new();
-        
-            if index == temp.len() - 1 { 
-                for i in  0..=temp.len() - 2 { 
-                    another_temp.push(temp.get(i).unwrap().clone());
-                }
-            } else if index != temp.len() - 1 { 
-                let mut i = index.clone();
-                while(i <= temp.len() - 2) { 
-                    let t = temp.get(i).unwrap();
-                    let next_t = temp.get(i + 1).unwrap();
-                    
-                    let next_lower_bound = t.row.range[0];
-                    let next_upper_bound = next_t.row.range[1] - next_t.row.range[0] + t.row.range[0];
-                    let next_row = RowMetaData { table : self.table_metadata.clone(), primary_key: next_t.value.clone(), row_size: next_t.row.row_size, range : vec![next_lower_bound, next_upper_bound], 
-                                                            page_number : next_t.row.page_number};  
-                    another_temp.push(Temp{value : next_t.value.clone(), row : next_row});
-                    i += 1;
-                }
-            }
-            
-            //let mut row_temp_map = BTreeMap::new();
-            for temp in another_temp  {
-                row_temp_map.insert(temp.value, temp.row );
-            }
-        }
-        
-        row_temp_map
+    // removes the row from the btree and frees its slot, the other rows of the page keep their
+    // slots
+    fn remove_row(&mut self, prim_key_value : &Value) -> Option<RowMetaData> { 
+        let removed = self.rows.remove(prim_key_value)?;
+        self.on_page(removed.page_number, |page| page.remove(removed.slot));
+        Some(removed)
     }
- 
-    pub fn read_when(&mut self, conditions : Option<Condition>) -> Vec<StoredRow> {
-        let mut rows = self.read_all();
-        if let Some(condition) = conditions { 
-            let mut required_rows = Vec::new();
-            for row in rows {
-                for (key, value) in row.row.iter() {
-                    if *key == condition.first && *value == Value::value(condition.second.clone()) && condition.token == "=".to_string() { 
-                        required_rows.push(row.clone());
-                    } else if *key == condition.first && *value != Value::value(condition.second.clone()) && condition.token == "!=".to_string() { 
-                        required_rows.push(row.clone());
-                    } 
-                }
-            }
-            return required_rows;
+
+    // changes the page in the buffer pool. False when the change fails or the page can not be read
+    fn on_page(&mut self, page_number : usize, change : impl FnOnce(&mut Page) -> bool) -> bool { 
+        let Ok(pinned) = self.pool.pin(&self.table_metadata.table_name, page_number) else { 
+            return false;
+        };
+        let mut page = pinned.write();
+        if !change(&mut page) { 
+            return false;
         }
-        rows
+        self.free_space.set(page_number, page.available());
+        true
+    }
 
+    // writes the pages of the table changed in the buffer pool back to disk
+    fn flush(&self) -> Result<(), InternalStorageError> { 
+        self.pool.flush(&self.table_metadata.table_name)
+    }
+ 
+    pub fn read_when(&mut self, conditions : Option<Expr>, functions : &dyn Functions) -> Vec<StoredRow> {
+        self.scan_where(conditions.as_ref(), false, functions).collect()
     }
 
 
     pub fn read_all(&mut self) -> Vec<StoredRow> { 
-        let mut rows = Vec::new(); 
-        
-        for (index , value) in self.rows.iter() { 
-            if let Some(value) = Page::read_chunks(value.page_number, value.range.clone(), value.table.table_name.clone()) { 
-                
-                if let Ok(row) = StoredRow::from_bytes(&value.clone()) { 
-                    rows.push(row);
-                } else if let Err(err) = StoredRow::from_bytes(&value.clone()){ 
+        self.scan(false).collect()
+    }
+
+    /// Lazily reads the rows in primary key order, or in reverse order when descending.
+    /// Rows that can not be read back are skipped
+    pub fn scan(&self, descending : bool) -> impl Iterator<Item = StoredRow> + '_ { 
+        self.scan_range((Bound::Unbounded, Bound::Unbounded), descending)
+    }
+
+    /// Lazily reads the rows matching the condition (all rows when None) in primary key order, or in
+    /// reverse order when descending, calling the `functions` the condition calls. Only the rows
+    /// within the condition's key range are read
+    pub fn scan_where<'a>(&'a self, condition : Option<&'a Expr>, descending : bool, functions : &'a dyn Functions) -> impl Iterator<Item = StoredRow> + 'a { 
+        let range = condition.map_or((Bound::Unbounded, Bound::Unbounded), |condition| self.key_range(condition));
+        self.scan_range(range, descending)
+            .filter(move |row| condition.is_none_or(|condition| eval::matches(condition, row, functions)))
+    }
+
+    /// The range of primary keys the condition can match. It is narrowed by the conjuncts comparing
+    /// a single column key with a literal of the key's kind, `id > 3`, `id = 3` or `id BETWEEN 1 AND 5`,
+    /// and is the full range when none does
+    pub fn key_range(&self, condition : &Expr) -> (Bound<Value>, Bound<Value>) { 
+        let mut range = (Bound::Unbounded, Bound::Unbounded);
+        if !self.table_metadata.key_columns.is_empty() { 
+            return range;
+        }
+        let is_key = |expr : &Expr| matches!(expr, Expr::Column(name) if *name == self.table_metadata.primary_key);
+        // a literal ordered the same way by the index as by the comparison
+        let literal = |expr : &Expr| match (expr, &self.table_metadata.prim_key_type) { 
+            (Expr::Literal(value @ Value::Number(_)), key_type::Number) | (Expr::Literal(value @ Value::String(_)), key_type::Strings) => Some(value.clone()),
+            _ => None,
+        };
+        for conjunct in condition.conjuncts() { 
+            let (lower, upper) = match conjunct { 
+                Expr::Binary { left, op, right } if is_key(left) => match literal(right) { 
+                    Some(value) => bounds(*op, value),
+                    None => continue,
+                },
+                Expr::Binary { left, op, right } if is_key(right) => match (literal(left), flipped(*op)) { 
+                    (Some(value), Some(op)) => bounds(op, value),
+                    _ => continue,
+                },
+                Expr::Between { expr, low, high, negated : false } if is_key(expr) => match (literal(low), literal(high)) { 
+                    (Some(low), Some(high)) => (Bound::Included(low), Bound::Included(high)),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            range = (tighter(range.0, lower, Ordering::Greater), tighter(range.1, upper, Ordering::Less));
+        }
+        range
+    }
+
+    /// Lazily reads the rows whose primary key falls within the range, in key order or in reverse
+    /// order when descending. Rows that can not be read back are skipped
+    pub fn scan_range(&self, range : (Bound<Value>, Bound<Value>), descending : bool) -> impl Iterator<Item = StoredRow> + '_ { 
+        let empty = match (&range.0, &range.1) { 
+            (Bound::Included(low), Bound::Included(high)) => low > high,
+            (Bound::Included(low) | Bound::Excluded(low), Bound::Included(high) | Bound::Excluded(high)) => low >= high,
+            _ => false,
+        };
+        let metas : Box<dyn Iterator<Item = &RowMetaData>> = if empty { 
+            // BTreeMap::range panics on a range that ends before it starts
+            Box::new(std::iter::empty())
+        } else if descending { 
+            Box::new(self.rows.range(range).map(|(_, meta)| meta).rev())
+        } else { 
+            Box::new(self.rows.range(range).map(|(_, meta)| meta))
+        };
+        metas.filter_map(move |meta| { 
+            let value = self.read_bytes(meta)?;
+            match StoredRow::from_bytes(&value, &self.columns_at(meta.schema_version)) { 
+                Ok(row) => Some(schema::migrate(row, meta.schema_version, &self.schema_changes)),
+                Err(err) => { 
                     println!("error is {:#?}" , err);
-                    continue
+                    None
                 }
-            } else { 
-                continue
             }
-         }
+        })
+    }
 
-        rows
+    /// Whether a scan returns the rows ordered by the column, true for a single column primary key
+    pub fn is_ordered_by(&self, column : &str) -> bool { 
+        self.table_metadata.key_columns.is_empty() && self.table_metadata.primary_key == column
     }
 
-    pub fn delete(&mut self , conditions : Option<Condition> ) { 
+    /// Deletes the rows matching the condition (all rows when None), returning how many were removed
+    pub fn delete(&mut self , conditions : Option<Expr>, functions : &dyn Functions) -> usize { 
         // deleting from disk means reclaiming the disk space occupied by the given row
         // bringing the page into the buffer
         // scan the btree map to select the rows to be deleted 
         match conditions {
             Some(condition) => {
-                let mut to_be_deleted_row_metadata = BTreeMap::new();
-                //let mut updated_row_metadata = BTreeMap::new();
-                // filter the btree to find out which rows will be deleted
-                let to_be_deleted_rows = self.read_when(Some(condition));
-                
-                for stored_row in to_be_deleted_rows { 
-                    for (key, value) in stored_row.row  { 
-                    
-                        if key == self.table_metadata.primary_key { 
-                                let (key , value) = self.rows.get_key_value(&value).unwrap();
-                                to_be_deleted_row_metadata.insert(key.clone(), value.clone());
-                            
-                        } 
-                    }
-                }
-                
-                //let mut pages = Vec::new();
-                for (value, row) in to_be_deleted_row_metadata.clone() { 
-                    //let page = self.pages.get(row.page_number - 1).unwrap();
-                    if self.pages.page_number == row.page_number { 
-                        self.pages = self.pages.delete_chunks(row.range[0], row.range[1], true, self.table_metadata.table_name.clone());
-                    }
-                    //pages.push(page);
-                    //self.rows.remove(&value);
-                }
-                //self.pages = pages.clone();
-                self.page_metadata.page_number = self.pages.page_number;
-                self.page_metadata.current_size = self.pages.data.len() - 1;
-                let values = to_be_deleted_row_metadata.iter().map(|(val, _)| val.clone()).collect();
-                self.rows = self.filter_row(values);
-                self.save_to_json();
-                //self.rows = updated_row_metadata;
-
+                // filter the rows to find out which ones will be deleted
+                let to_be_deleted : Vec<Value> = self.read_when(Some(condition), functions).into_iter()
+                    .filter_map(|stored_row| self.table_metadata.key_of(&stored_row))
+                    .collect();
+                self.delete_keys(&to_be_deleted)
             }, 
             None => { 
                 // delete all the rows of a given table
-                let page_numbers: Vec<usize> = self.rows.iter().map(|(key , value)| { 
-                    value.page_number
-                }).collect();
-                for page in page_numbers { 
-                    Page::delete(page, self.table_metadata.table_name.clone());
-                }
-                self.pages = Page::new(1, Vec::new());
-                self.page_metadata = PageData::default(1);
+                let rows_affected = self.rows.len();
+                self.pool.discard(&self.table_metadata.table_name);
+                Page::delete(&self.table_metadata.table_name).ok();
+                self.free_space.truncate(0);
                 self.rows = BTreeMap::new();
-                self.save_to_json();
+                self.unique.clear();
+                self.save_to_json().ok();
+                rows_affected
             }
         }
     }
 
+    /// Deletes the rows stored under the primary keys, returning how many were removed
+    pub fn delete_keys(&mut self, keys : &[Value]) -> usize { 
+        for (key, row) in self.unique_rows(keys) { 
+            self.unique.remove(&self.table_metadata.unique_keys, &row, &key);
+        }
+        let rows_affected = keys.iter()
+            .filter_map(|value| self.remove_row(value))
+            .count();
+        self.flush().ok();
+        self.release_pages().ok();
+        self.save_to_json().ok();
+        rows_affected
+    }
+
+    // cuts the table's file short after the last page still holding a row, so that the empty
+    // pages at its end are given back along with their entries in the free space map
+    fn release_pages(&mut self) -> Result<(), InternalStorageError> { 
+        let page_count = self.rows.values()
+            .map(|row| row.page_number)
+            .max()
+            .unwrap_or(0);
+        if page_count >= self.free_space.page_count() { 
+            return Ok(());
+        }
+        self.pool.discard_after(&self.table_metadata.table_name, page_count);
+        Page::truncate(&self.table_metadata.table_name, page_count).map_err(InternalStorageError::ErrWriteToDisk)?;
+        self.free_space.truncate(page_count);
+        Ok(())
+    }
+
+    /// Records a change to the table definition. Existing rows are not rewritten,
+    /// they are migrated to the latest schema whenever they are read
+    pub fn alter(&mut self, change : SchemaChange) { 
+        match &change { 
+            SchemaChange::RenameColumn { from, to } => self.table_metadata.rename_column(from, to),
+            SchemaChange::DropColumn(name) => self.table_metadata.drop_column(name),
+            SchemaChange::AddColumn { .. } => {}
+        }
+        for row in self.rows.values_mut() { 
+            row.table = self.table_metadata.clone();
+        }
+        self.schema_changes.push(change);
+        self.index_unique_keys();
+        self.save_to_json().ok();
+    }
+
+    /// Moves the table's page files and storage metadata file over to the new table name
+    pub fn rename(&mut self, table_name : String) -> Result<(), InternalStorageError> { 
+        self.flush()?;
+        self.pool.discard(&self.table_metadata.table_name);
+        let table_file = Page::file(&self.table_metadata.table_name);
+        if fs::metadata(&table_file).is_ok() { 
+            fs::rename(&table_file, Page::file(&table_name)).map_err(|err| InternalStorageError::ErrWriteToDisk(err.to_string()))?;
+        }
+        if fs::metadata(&self.file_name).is_ok() { 
+            fs::remove_file(&self.file_name).map_err(|err| InternalStorageError::ErrWriteToDisk(err.to_string()))?;
+        }
+        self.table_metadata.table_name = table_name.clone();
+        for row in self.rows.values_mut() { 
+            row.table.table_name = table_name.clone();
+        }
+        self.file_name = format!("{}_storage.json", table_name);
+        self.save_to_json().map_err(InternalStorageError::ErrWriteToDisk)
+    }
+
+    /// Removes every on-disk artifact of the table, its page files and its storage metadata file
+    pub fn destroy(self) -> Result<(), InternalStorageError> { 
+        self.pool.discard(&self.table_metadata.table_name);
+        Page::delete(&self.table_metadata.table_name).map_err(InternalStorageError::ErrWriteToDisk)?;
+        if fs::metadata(&self.file_name).is_ok() { 
+            fs::remove_file(&self.file_name).map_err(|err| InternalStorageError::ErrWriteToDisk(err.to_string()))?;
+        }
+        Ok(())
+    }
+
     pub fn write(&mut self, data : StoredRow)  -> Result<&str, InternalStorageError>{ 
+        self.write_batch(vec![data]).map(|_| "succesfully written to disk")
+    } 
 
-        match data.row.get(&self.table_metadata.primary_key) {
-            Some(key) => {
-                match data.to_bytes() {
-                    Ok(chunk) => { 
-                        let ( page_number, chunk_range) = self.page_metadata.getChunkData(chunk.size);
-                        if self.isPageFull(chunk.size) { 
-                            self.create_new_page(self.page_metadata.page_number);
-                        }
-                        let row = RowMetaData::new(self.table_metadata.clone(), key.clone(), chunk.size,  chunk_range.clone(), page_number);
-                        self.rows.insert(key.clone(), row);
-                        
-                        
-                        self.pages.append_chunks(chunk.data, chunk_range.clone());
-                        match self.pages.write(self.table_metadata.table_name.clone()) {
-                            true => {
-                                // commit , udpate the page metadata, return a response
-                                self.save_to_json();
-                                Ok("succesfully written to disk")
-                                        
-                            },
-                            false => { 
-                                // rollback , page metadata , btree state to its previous state
-                                self.rows.remove(&key.clone());
-                                self.page_metadata.current_size = self.page_metadata.current_size - chunk.size;
-                                self.pages.delete_chunks(*chunk_range.get(0).unwrap(), *chunk_range.get(1).unwrap(), false, self.table_metadata.table_name.clone());                                    
-                                self.save_to_json();
-                                Err(InternalStorageError::ErrWriteToDisk("error writing to disk".to_string()))
-                            },
-                        }
-                           
-                        
-                    
-                    },
-                    Err(err) => { 
-                        self.save_to_json();
-                        Err(InternalStorageError::SerializerError(err))
-                    },
+    /// Writes the rows as one batch: every page they land on is written to disk once and the
+    /// storage metadata is saved once. Nothing is written when a row has no primary key or its
+    /// key is already taken, by a stored row or by another row of the batch. Returns the number
+    /// of rows written
+    pub fn write_batch(&mut self, rows : Vec<StoredRow>) -> Result<usize, InternalStorageError> { 
+        let columns = self.columns_at(self.schema_changes.len());
+        let mut keys : HashSet<Value> = HashSet::new();
+        let mut chunks : Vec<(Value, Chunk)> = Vec::new();
+        for row in rows.iter() { 
+            let Some(key) = self.table_metadata.key_of(row) else { 
+                return Err(InternalStorageError::ErrPrimaryKeyNotFound("primary key not found".to_string()));
+            };
+            if self.rows.contains_key(&key) || !keys.insert(key.clone()) { 
+                return Err(InternalStorageError::ErrDuplicatePrimaryKey(key.to_string()));
+            }
+            chunks.push((key, row.to_bytes(&columns).map_err(InternalStorageError::SerializerError)?));
+        }
+
+        let written = self.store_chunks(chunks);
+        if written.is_ok() { 
+            for row in rows.iter() { 
+                if let Some(key) = self.table_metadata.key_of(row) { 
+                    self.unique.insert(&self.table_metadata.unique_keys, row, &key);
                 }
-            },
-            None => {
-                self.save_to_json();
-                Err(InternalStorageError::ErrPrimaryKeyNotFound("primary key not found".to_string()))
-            },
+            }
         }
-        
-        
-        
-        
-    } 
+        let saved = self.save_to_json().map_err(InternalStorageError::ErrWriteToDisk);
+        written.and(saved).map(|_| keys.len())
+    }
+
+    // stores the chunks on the first pages with room for them, indexes them under their keys and
+    // writes the pages changed back to disk. When a page can not be read or written the chunks are
+    // no longer indexed and their slots are freed again
+    fn store_chunks(&mut self, chunks : Vec<(Value, Chunk)>) -> Result<(), InternalStorageError> { 
+        let keys : Vec<Value> = chunks.iter().map(|(key, _)| key.clone()).collect();
+        let mut placed : Vec<(usize, usize)> = Vec::new();
+        let written = chunks.into_iter()
+            .try_for_each(|(key, chunk)| self.place_chunk(key, chunk).map(|slot| placed.push(slot)))
+            .and_then(|_| self.flush());
+        if written.is_err() { 
+            for key in keys.iter() { 
+                self.rows.remove(key);
+            }
+            for (page_number, slot) in placed { 
+                self.on_page(page_number, |page| page.remove(slot));
+            }
+        }
+        written
+    }
+
+    // places the chunk on the first page with room for it and indexes it under the given key,
+    // returning its page and slot. Only the page in the buffer pool changes
+    fn place_chunk(&mut self, key : Value, chunk : Chunk) -> Result<(usize, usize), InternalStorageError> { 
+        if chunk.size > PAGE_SIZE - HEADER_SIZE - SLOT_SIZE { 
+            return Err(InternalStorageError::ErrRowTooLarge(chunk.size));
+        }
+        let table_name = &self.table_metadata.table_name;
+        let page_number = self.free_space.page_for(chunk.size);
+        let pinned = if page_number > self.free_space.page_count() { 
+            self.pool.pin_new(table_name, page_number)?
+        } else { 
+            self.pool.pin(table_name, page_number)?
+        };
+        let mut page = pinned.write();
+        let Some(slot) = page.insert(&chunk.data) else { 
+            return Err(InternalStorageError::ErrRowTooLarge(chunk.size));
+        };
+        self.free_space.set(page_number, page.available());
+        let mut row = RowMetaData::new(self.table_metadata.clone(), key.clone(), chunk.size, page_number, slot);
+        row.schema_version = self.schema_changes.len();
+        self.rows.insert(key, row);
+        Ok((page_number, slot))
+    }
+
+    /// Replaces the row stored under the given primary key. The chunk stays in its slot when its
+    /// page has room for the new encoding, otherwise it is relocated to the first page with room.
+    /// The row is re-keyed when the update changes its primary key
+    pub fn update(&mut self, prim_key_value : &Value, data : StoredRow) -> Result<&'static str, InternalStorageError> { 
+        self.update_batch(vec![(prim_key_value.clone(), data)]).map(|_| "succesfully written to disk")
+    }
+
+    /// Replaces the rows stored under the given primary keys as one batch, like `update` does for
+    /// one row. Nothing is written when a row has no primary key, is not stored, or takes a key
+    /// held by a row outside the batch or by another row of the batch, so rows of the batch may
+    /// swap keys. Returns the number of rows replaced
+    pub fn update_batch(&mut self, rows : Vec<(Value, StoredRow)>) -> Result<usize, InternalStorageError> { 
+        if rows.is_empty() { 
+            return Ok(0);
+        }
+        let columns = self.columns_at(self.schema_changes.len());
+        let replaced : HashSet<&Value> = rows.iter().map(|(key, _)| key).collect();
+        let mut keys : HashSet<Value> = HashSet::new();
+        let mut chunks : Vec<(Value, Value, Chunk)> = Vec::new();
+        for (key, row) in rows.iter() { 
+            let Some(new_key) = self.table_metadata.key_of(row) else { 
+                return Err(InternalStorageError::ErrPrimaryKeyNotFound("primary key not found".to_string()));
+            };
+            if !self.rows.contains_key(key) { 
+                return Err(InternalStorageError::ErrInternal("row metadata not found".to_string()));
+            }
+            if (self.rows.contains_key(&new_key) && !replaced.contains(&new_key)) || !keys.insert(new_key.clone()) { 
+                return Err(InternalStorageError::ErrDuplicatePrimaryKey(new_key.to_string()));
+            }
+            chunks.push((key.clone(), new_key, row.to_bytes(&columns).map_err(InternalStorageError::SerializerError)?));
+        }
+
+        let replaced = self.unique_rows(&chunks.iter().map(|(key, _, _)| key.clone()).collect::<Vec<_>>());
+        // every row is taken out of the btree before any is put back under its new key
+        let stored : Vec<RowMetaData> = chunks.iter().filter_map(|(key, _, _)| self.rows.remove(key)).collect();
+        let mut relocated : Vec<(Value, Chunk)> = Vec::new();
+        for (row, (_, new_key, chunk)) in stored.into_iter().zip(chunks) { 
+            if self.on_page(row.page_number, |page| page.update(row.slot, &chunk.data)) { 
+                self.rows.insert(new_key.clone(), RowMetaData { primary_key : new_key, row_size : chunk.size, schema_version : self.schema_changes.len(), ..row });
+                continue;
+            }
+            self.on_page(row.page_number, |page| page.remove(row.slot));
+            relocated.push((new_key, chunk));
+        }
+        let written = self.store_chunks(relocated);
+        if written.is_ok() { 
+            for (key, row) in replaced.iter() { 
+                self.unique.remove(&self.table_metadata.unique_keys, row, key);
+            }
+            for (_, row) in rows.iter() { 
+                if let Some(key) = self.table_metadata.key_of(row) { 
+                    self.unique.insert(&self.table_metadata.unique_keys, row, &key);
+                }
+            }
+        } else { 
+            // the rows changed in place before the failure stay changed
+            self.index_unique_keys();
+        }
+        let saved = self.save_to_json().map_err(InternalStorageError::ErrWriteToDisk);
+        written.and(saved).map(|_| keys.len())
+    }
 }
 
 
+// the key bounds of comparing the key with the value by the operator, the full range for
+// an operator that does not bound the key
+fn bounds(op : BinaryOp, value : Value) -> (Bound<Value>, Bound<Value>) { 
+    match op { 
+        BinaryOp::Eq => (Bound::Included(value.clone()), Bound::Included(value)),
+        BinaryOp::Lt => (Bound::Unbounded, Bound::Excluded(value)),
+        BinaryOp::LtEq => (Bound::Unbounded, Bound::Included(value)),
+        BinaryOp::Gt => (Bound::Excluded(value), Bound::Unbounded),
+        BinaryOp::GtEq => (Bound::Included(value), Bound::Unbounded),
+        _ => (Bound::Unbounded, Bound::Unbounded),
+    }
+}
+
+// the operator comparing the operands the other way around, `3 < id` is `id > 3`
+fn flipped(op : BinaryOp) -> Option<BinaryOp> { 
+    match op { 
+        BinaryOp::Eq => Some(BinaryOp::Eq),
+        BinaryOp::Lt => Some(BinaryOp::Gt),
+        BinaryOp::LtEq => Some(BinaryOp::GtEq),
+        BinaryOp::Gt => Some(BinaryOp::Lt),
+        BinaryOp::GtEq => Some(BinaryOp::LtEq),
+        _ => None,
+    }
+}
+
+// the tighter of two bounds on the same side of a range, `towards` is Greater for lower bounds
+// and Less for upper bounds. At the same value an excluded bound is the tighter one
+fn tighter(current : Bound<Value>, other : Bound<Value>, towards : Ordering) -> Bound<Value> { 
+    match (&current, &other) { 
+        (Bound::Unbounded, _) => other,
+        (_, Bound::Unbounded) => current,
+        (Bound::Included(a) | Bound::Excluded(a), Bound::Included(b) | Bound::Excluded(b)) => match b.cmp(a) { 
+            Ordering::Equal if matches!(other, Bound::Excluded(_)) => other,
+            Ordering::Equal => current,
+            ordering if ordering == towards => other,
+            _ => current,
+        },
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::collections::{BTreeMap, HashMap};
+    use std::{collections::{BTreeMap, HashMap}, ops::Bound};
 
     use bigdecimal::{BigDecimal, FromPrimitive};
-    use crate::{page::{self, page::Page, table::{PageData, RowMetaData}}, row::{self, StoredRow}};
-    use sql_one_parser::{commands::select_condition::Condition, value::Value};
+    use crate::{eval::NoFunctions, page::{self, error::InternalStorageError, page::{Page, PAGE_SIZE}, table::RowMetaData}, row::{self, StoredRow}};
+    use sql_one_parser::{expr::Expr, parser::Parse, value::Value};
 
     use crate::page::table::{key_type, TableMetaData};
 
-    use super::Storage;
+    use super::{Arc, BufferPool, Storage};
+
+    fn columns(names : &[&str]) -> Vec<String> { 
+        names.iter().map(|name| name.to_string()).collect()
+    }
 
 
     //#[test] 
@@ -349,7 +644,7 @@ mod tests {
     //#[test]
     pub fn test_persistent_write() { 
         let table_data = TableMetaData::new("users".to_string(), "id".to_string(), key_type::Number);
-        let mut storage = Storage::new(Some(table_data), "users_storage.json".to_string());
+        let mut storage = Storage::new(Some(table_data), &columns(&["id", "name"]), "users_storage.json".to_string());
         let mut rows = HashMap::new();
         rows.insert("id".to_string(), Value::Number(BigDecimal::from(1)));
         rows.insert("name".to_string(), Value::String("raja".to_string()));
@@ -369,17 +664,11 @@ mod tests {
     //#[test]
     pub fn test_storage_serializer() { 
         let mut s = Storage::default();
-        let test_row = RowMetaData::new(s.clone().table_metadata, Value::Number(BigDecimal::from_i16(1).unwrap()), 32, vec![0, 31], 1);
-        s.pages = Page::new(1, vec![1,2, 3,4]);
+        let test_row = RowMetaData::new(s.clone().table_metadata, Value::Number(BigDecimal::from_i16(1).unwrap()), 32, 1, 0);
         let mut rows = BTreeMap::new();
         rows.insert(Value::Number(BigDecimal::from_i16(1).unwrap()), test_row);
-        let page_meta = PageData{
-            page_number: 1,
-            current_size: 32,
-            max_size: 4096
-        };
         s.rows = rows;
-        s.page_metadata = page_meta;
+        s.free_space.set(1, 4000);
         match serde_json::to_string(&s) {
             Ok(r) => println!("s : {:?}", s),
             Err(err) => println!("{:?}", err),
@@ -387,17 +676,261 @@ mod tests {
     }
 
     #[test]
-    pub fn test_only_read() { 
-        let mut s = Storage::new(None, "users_storage.json".to_string());
-        let data_rows = s.read_when(None);
-        println!("read results : {:#?}", data_rows);
+    pub fn test_legacy_rows_move_to_the_table_file() { 
+        // the users table checked in was stored before pages were slotted, a copy of it is opened
+        let legacy = std::fs::read_to_string("users_storage.json").unwrap()
+            .replace("\"users", "\"legacy_users");
+        std::fs::write("legacy_users_storage.json", legacy).unwrap();
+        std::fs::create_dir_all("storage/legacy_users").unwrap();
+        std::fs::copy("storage/users/page_1.bin", "storage/legacy_users/page_1.bin").unwrap();
+
+        let mut storage = Storage::new(None, &columns(&["id", "name"]), "legacy_users_storage.json".to_string());
+        assert!(storage.rows.values().all(|row| row.range.is_none()));
+        assert!(std::fs::metadata("storage/legacy_users").is_err());
+        let names : Vec<Value> = storage.read_all().into_iter().map(|row| row.row["name"].clone()).collect();
+        assert_eq!(names, vec![Value::String("raja".to_string())]);
+
+        // the move was saved, opening the table again reads the row from the table's file
+        let mut reopened = Storage::new(None, &columns(&["id", "name"]), "legacy_users_storage.json".to_string());
+        assert_eq!(reopened.read_all(), storage.read_all());
+        reopened.destroy().unwrap();
+    }
+
+
+    #[test]
+    pub fn test_conditional_delete() { 
+        let table_data = TableMetaData::new("delete_test".to_string(), "id".to_string(), key_type::Number);
+        let mut storage = Storage::new(Some(table_data), &columns(&["id", "name"]), "delete_test_storage.json".to_string());
+        for (id, name) in [(1, "raja"), (2, "neha"), (3, "rajdip")] { 
+            let mut rows = HashMap::new();
+            rows.insert("id".to_string(), Value::Number(BigDecimal::from(id)));
+            rows.insert("name".to_string(), Value::String(name.to_string()));
+            storage.write(StoredRow::new(rows)).unwrap();
+        }
+        let rows_affected = storage.delete(Some(Expr::parse_from_raw("id = 2").unwrap().1), &NoFunctions);
+        let names : Vec<Value> = storage.read_all().into_iter().map(|row| row.row["name"].clone()).collect();
+        storage.delete(None, &NoFunctions);
+        std::fs::remove_file("delete_test_storage.json").unwrap();
+        std::fs::remove_file("storage/delete_test.pages").ok();
+
+        assert_eq!(rows_affected, 1);
+        assert_eq!(names, vec![Value::String("raja".to_string()), Value::String("rajdip".to_string())]);
+    }
+
+    #[test]
+    pub fn test_write_batch() { 
+        let table_data = TableMetaData::new("batch_test".to_string(), "id".to_string(), key_type::Number);
+        let mut storage = Storage::new(Some(table_data), &columns(&["id", "name"]), "batch_test_storage.json".to_string());
+        let row = |id : i32, name : &str| { 
+            let mut rows = HashMap::new();
+            rows.insert("id".to_string(), Value::Number(BigDecimal::from(id)));
+            rows.insert("name".to_string(), Value::String(name.to_string()));
+            StoredRow::new(rows)
+        };
+        let written = storage.write_batch(vec![row(1, "raja"), row(2, "neha"), row(3, "rajdip")]);
+        // a key repeated within the batch, or already stored, writes nothing
+        let repeated = storage.write_batch(vec![row(4, "a"), row(4, "b")]).map(|_| ());
+        let taken = storage.write_batch(vec![row(5, "a"), row(1, "b")]).map(|_| ());
+        let names : Vec<Value> = storage.read_all().into_iter().map(|row| row.row["name"].clone()).collect();
+        storage.delete(None, &NoFunctions);
+        std::fs::remove_file("batch_test_storage.json").unwrap();
+        std::fs::remove_file("storage/batch_test.pages").ok();
+
+        assert_eq!(written.unwrap(), 3);
+        assert!(matches!(repeated, Err(InternalStorageError::ErrDuplicatePrimaryKey(_))));
+        assert!(matches!(taken, Err(InternalStorageError::ErrDuplicatePrimaryKey(_))));
+        assert_eq!(names, vec![Value::String("raja".to_string()), Value::String("neha".to_string()), Value::String("rajdip".to_string())]);
+    }
+
+    #[test]
+    pub fn test_key_range_scan() { 
+        let table_data = TableMetaData::new("range_test".to_string(), "id".to_string(), key_type::Number);
+        let mut storage = Storage::new(Some(table_data), &columns(&["id", "even"]), "range_test_storage.json".to_string());
+        let rows = (1..=6).map(|id| { 
+            let mut rows = HashMap::new();
+            rows.insert("id".to_string(), Value::Number(BigDecimal::from(id)));
+            rows.insert("even".to_string(), Value::Boolean(id % 2 == 0));
+            StoredRow::new(rows)
+        });
+        storage.write_batch(rows.collect()).unwrap();
+        let predicate = |input : &str| Expr::parse_from_raw(input).unwrap().1;
+        let ids = |condition : &str, descending : bool| -> Vec<Value> { 
+            let condition = predicate(condition);
+            storage.scan_where(Some(&condition), descending, &NoFunctions).map(|row| row.row["id"].clone()).collect()
+        };
+        let number = |n : i32| Value::Number(BigDecimal::from(n));
+
+        let range = storage.key_range(&predicate("id > 2 and 5 >= id and id >= 2 and even"));
+        assert_eq!(range, (Bound::Excluded(number(2)), Bound::Included(number(5))));
+        assert_eq!(storage.key_range(&predicate("id > 2 or id < 1")), (Bound::Unbounded, Bound::Unbounded));
+        assert_eq!(storage.key_range(&predicate("id > 'a'")), (Bound::Unbounded, Bound::Unbounded));
+        assert_eq!(ids("id > 2 and 5 >= id and even", false), vec![number(4)]);
+        assert_eq!(ids("id between 2 and 4", true), vec![number(4), number(3), number(2)]);
+        assert_eq!(ids("id = 3", false), vec![number(3)]);
+        assert_eq!(ids("id > 4 and id < 3", false), Vec::<Value>::new());
+        assert_eq!(ids("id >= 3 and id < 3", false), Vec::<Value>::new());
+        assert_eq!(ids("id in (1, 6)", false), vec![number(1), number(6)]);
+        storage.delete(None, &NoFunctions);
+        std::fs::remove_file("range_test_storage.json").unwrap();
+        std::fs::remove_file("storage/range_test.pages").ok();
+    }
+
+    #[test]
+    pub fn test_update_relocates_and_rekeys() { 
+        let table_data = TableMetaData::new("update_test".to_string(), "id".to_string(), key_type::Number);
+        let mut storage = Storage::new(Some(table_data), &columns(&["id", "name"]), "update_test_storage.json".to_string());
+        let row = |id : i32, name : &str| { 
+            let mut rows = HashMap::new();
+            rows.insert("id".to_string(), Value::Number(BigDecimal::from(id)));
+            rows.insert("name".to_string(), Value::String(name.to_string()));
+            StoredRow::new(rows)
+        };
+        storage.write(row(1, "raja")).unwrap();
+        storage.write(row(2, "neha")).unwrap();
+        // grows past its old length, so it moves within its page
+        storage.update(&Value::Number(BigDecimal::from(1)), row(1, "raja with a longer name")).unwrap();
+        // shrinks and changes its primary key
+        storage.update(&Value::Number(BigDecimal::from(2)), row(5, "n")).unwrap();
+        let duplicate = storage.update(&Value::Number(BigDecimal::from(5)), row(1, "n"));
+        let rows = storage.read_all();
+        let keys : Vec<Value> = storage.rows.keys().cloned().collect();
+        storage.delete(None, &NoFunctions);
+        std::fs::remove_file("update_test_storage.json").unwrap();
+        std::fs::remove_file("storage/update_test.pages").ok();
+
+        assert!(duplicate.is_err());
+        assert_eq!(keys, vec![Value::Number(BigDecimal::from(1)), Value::Number(BigDecimal::from(5))]);
+        assert_eq!(rows, vec![row(1, "raja with a longer name"), row(5, "n")]);
+    }
+
+    #[test]
+    pub fn test_update_batch_swaps_keys() { 
+        let table_data = TableMetaData::new("update_batch_test".to_string(), "id".to_string(), key_type::Number);
+        let mut storage = Storage::new(Some(table_data), &columns(&["id", "name"]), "update_batch_test_storage.json".to_string());
+        let key = |id : i32| Value::Number(BigDecimal::from(id));
+        let row = |id : i32, name : &str| { 
+            let mut rows = HashMap::new();
+            rows.insert("id".to_string(), key(id));
+            rows.insert("name".to_string(), Value::String(name.to_string()));
+            StoredRow::new(rows)
+        };
+        storage.write_batch(vec![row(1, "raja"), row(2, "neha"), row(3, "ravi")]).unwrap();
+        let swapped = storage.update_batch(vec![(key(1), row(2, "raja")), (key(2), row(1, "neha"))]);
+        // the third row keeps its key, so nothing of the batch is written
+        let taken = storage.update_batch(vec![(key(1), row(3, "raja")), (key(2), row(4, "neha"))]);
+        let rows = storage.read_all();
+        storage.delete(None, &NoFunctions);
+        std::fs::remove_file("update_batch_test_storage.json").unwrap();
+        std::fs::remove_file("storage/update_batch_test.pages").ok();
+
+        assert_eq!(swapped.unwrap(), 2);
+        assert!(matches!(taken, Err(InternalStorageError::ErrDuplicatePrimaryKey(_))));
+        assert_eq!(rows, vec![row(1, "neha"), row(2, "raja"), row(3, "ravi")]);
     }
 
+    #[test]
+    pub fn test_unique_index_follows_the_rows() { 
+        let mut table_data = TableMetaData::new("unique_test".to_string(), "id".to_string(), key_type::Number);
+        table_data.unique_keys.push(vec!["name".to_string()]);
+        let mut storage = Storage::new(Some(table_data), &columns(&["id", "name"]), "unique_test_storage.json".to_string());
+        let key = |id : i32| Value::Number(BigDecimal::from(id));
+        let name = |name : &str| vec![Value::String(name.to_string())];
+        let row = |id : i32, name : &str| { 
+            let mut rows = HashMap::new();
+            rows.insert("id".to_string(), key(id));
+            rows.insert("name".to_string(), Value::String(name.to_string()));
+            StoredRow::new(rows)
+        };
+        let unique = vec!["name".to_string()];
+        storage.write_batch(vec![row(1, "raja"), row(2, "neha"), row(3, "ravi")]).unwrap();
+        assert_eq!(storage.unique_holder(&unique, &name("neha")), Some(&key(2)));
+
+        // the rows swap names, then one is deleted
+        storage.update_batch(vec![(key(1), row(1, "neha")), (key(2), row(2, "raja"))]).unwrap();
+        storage.delete_keys(&[key(3)]);
+        assert_eq!(storage.unique_holder(&unique, &name("neha")), Some(&key(1)));
+        assert_eq!(storage.unique_holder(&unique, &name("raja")), Some(&key(2)));
+        assert_eq!(storage.unique_holder(&unique, &name("ravi")), None);
+
+        // the index is built again from the rows when the table is opened
+        let reopened = Storage::new(None, &columns(&["id", "name"]), "unique_test_storage.json".to_string());
+        let holders : Vec<Option<&Value>> = ["neha", "raja", "ravi"].iter().map(|n| reopened.unique_holder(&unique, &name(n))).collect();
+        assert_eq!(holders, vec![Some(&key(1)), Some(&key(2)), None]);
+        storage.delete(None, &NoFunctions);
+        assert_eq!(storage.unique_holder(&unique, &name("neha")), None);
+        reopened.destroy().unwrap();
+    }
+
+    #[test]
+    pub fn test_null_columns_round_trip() { 
+        let table_data = TableMetaData::new("null_test".to_string(), "id".to_string(), key_type::Number);
+        let mut storage = Storage::new(Some(table_data), &columns(&["id", "name", "nick"]), "null_test_storage.json".to_string());
+        let mut rows = HashMap::new();
+        rows.insert("id".to_string(), Value::Number(BigDecimal::from(1)));
+        rows.insert("name".to_string(), Value::Null);
+        rows.insert("nick".to_string(), Value::String("null".to_string()));
+        let row = StoredRow::new(rows);
+        storage.write(row.clone()).unwrap();
+        let read = storage.read(Value::Number(BigDecimal::from(1)));
+        storage.delete(None, &NoFunctions);
+        std::fs::remove_file("null_test_storage.json").unwrap();
+        std::fs::remove_file("storage/null_test.pages").ok();
+
+        assert_eq!(read.unwrap(), row);
+    }
+
+    #[test]
+    pub fn test_composite_primary_key() { 
+        let table_data = TableMetaData::composite("composite_test".to_string(), vec!["id".to_string(), "name".to_string()]);
+        let mut storage = Storage::new(Some(table_data), &columns(&["id", "name"]), "composite_test_storage.json".to_string());
+        let row = |id : i32, name : &str| { 
+            let mut rows = HashMap::new();
+            rows.insert("id".to_string(), Value::Number(BigDecimal::from(id)));
+            rows.insert("name".to_string(), Value::String(name.to_string()));
+            StoredRow::new(rows)
+        };
+        storage.write(row(1, "raja")).unwrap();
+        let same_id = storage.write(row(1, "neha")).map(|_| ());
+        let duplicate = storage.write(row(1, "raja")).map(|_| ());
+        let key = storage.table_metadata.key_of(&row(1, "neha")).unwrap();
+        let read = storage.read(key);
+        storage.delete(None, &NoFunctions);
+        std::fs::remove_file("composite_test_storage.json").unwrap();
+        std::fs::remove_file("storage/composite_test.pages").ok();
+
+        assert!(same_id.is_ok());
+        assert!(matches!(duplicate, Err(InternalStorageError::ErrDuplicatePrimaryKey(_))));
+        assert_eq!(read.unwrap(), row(1, "neha"));
+    }
+
+    #[test]
+    pub fn test_duplicate_primary_key_is_rejected() { 
+        let table_data = TableMetaData::new("duplicate_test".to_string(), "id".to_string(), key_type::Number);
+        let mut storage = Storage::new(Some(table_data), &columns(&["id", "name"]), "duplicate_test_storage.json".to_string());
+        let row = |name : &str| { 
+            let mut rows = HashMap::new();
+            rows.insert("id".to_string(), Value::Number(BigDecimal::from(1)));
+            rows.insert("name".to_string(), Value::String(name.to_string()));
+            StoredRow::new(rows)
+        };
+        storage.write(row("raja")).unwrap();
+        let free_space = storage.free_space.pages.clone();
+        let duplicate = storage.write(row("neha")).map(|_| ());
+        let rows = storage.read_all();
+        let duplicate_free_space = storage.free_space.pages.clone();
+        storage.delete(None, &NoFunctions);
+        std::fs::remove_file("duplicate_test_storage.json").unwrap();
+        std::fs::remove_file("storage/duplicate_test.pages").ok();
+
+        assert!(matches!(duplicate, Err(InternalStorageError::ErrDuplicatePrimaryKey(_))));
+        assert_eq!(duplicate_free_space, free_space);
+        assert_eq!(rows, vec![row("raja")]);
+    }
 
     //#[test]
     pub fn test_read_by_primary_key() { 
         let table_data = TableMetaData::new("users".to_string(), "id".to_string(), key_type::Number);
-        let mut storage = Storage::new(Some(table_data), "users_storage.json".to_string());
+        let mut storage = Storage::new(Some(table_data), &columns(&["id", "name"]), "users_storage.json".to_string());
         println!("storge snap : {:?}", storage.clone() );
         let mut rows = HashMap::new();
         rows.insert("id".to_string(), Value::Number(BigDecimal::from(1)));
@@ -417,15 +950,85 @@ mod tests {
         // let third_row = StoredRow::new(third_rows);
         // let third_result = storage.write(third_row).unwrap();
         // println!("Res is {:?}", third_result);
-        let conditions = Condition { first : "name".to_string(), second : "raja".to_string(), token :"!=".to_string()};
-        let row = storage.read_when(None);
+        let conditions = Expr::parse_from_raw("name != 'raja'").unwrap().1;
+        let row = storage.read_when(None, &NoFunctions);
         println!("read result is {:#?}", row);
         // conditional delete
-        //storage.delete(None);
+        //storage.delete(None, &NoFunctions);
         
         // delete all 
-        //storage.delete(None);
+        //storage.delete(None, &NoFunctions);
         //println!("storage : {:?}", storage);
-        //println!("after deleting read results are {:#?}", storage.read_when(None));
+        //println!("after deleting read results are {:#?}", storage.read_when(None, &NoFunctions));
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn test_rows_fill_free_space() { 
+        let table_data = TableMetaData::new("free_space_test".to_string(), "id".to_string(), key_type::Number);
+        let mut storage = Storage::new(Some(table_data), &columns(&["id", "name"]), "free_space_test_storage.json".to_string());
+        let row = |id : i32| { 
+            let mut rows = HashMap::new();
+            rows.insert("id".to_string(), Value::Number(BigDecimal::from(id)));
+            rows.insert("name".to_string(), Value::String("x".repeat(500)));
+            StoredRow::new(rows)
+        };
+        storage.write_batch((1..=20).map(row).collect()).unwrap();
+        let page_of = |storage : &Storage, id : i32| storage.rows[&Value::Number(BigDecimal::from(id))].page_number;
+        let pages = Page::page_count("free_space_test");
+        let file_size = std::fs::metadata(Page::file("free_space_test")).unwrap().len() as usize;
+        // the rows freed on the first page make room for the next one
+        storage.delete(Some(Expr::parse_from_raw("id < 3").unwrap().1), &NoFunctions);
+        storage.write(row(21)).unwrap();
+        let page = page_of(&storage, 21);
+        let count = storage.read_all().len();
+        // the pages emptied at the end of the file are given back
+        let last = page_of(&storage, 20);
+        let on_last : Vec<Value> = storage.rows.iter().filter(|(_, row)| row.page_number == last).map(|(key, _)| key.clone()).collect();
+        storage.delete_keys(&on_last);
+        let remaining_pages = (Page::page_count("free_space_test"), storage.free_space.page_count());
+        let remaining = storage.read_all().len();
+        storage.delete(None, &NoFunctions);
+        std::fs::remove_file("free_space_test_storage.json").unwrap();
+
+        assert!(pages > 1);
+        assert_eq!(file_size, pages * PAGE_SIZE);
+        assert_eq!(page, 1);
+        assert_eq!(count, 19);
+        assert_eq!(remaining_pages, (last - 1, last - 1));
+        assert_eq!(remaining, 19 - on_last.len());
+        assert!(!Page::file("free_space_test").exists());
+    }
+
+    #[test]
+    pub fn test_pages_are_read_through_the_buffer_pool() { 
+        let table_data = TableMetaData::new("pool_test".to_string(), "id".to_string(), key_type::Number);
+        let pool = Arc::new(BufferPool::new(2));
+        let mut storage = Storage::open(Some(table_data), &columns(&["id", "name"]), "pool_test_storage.json".to_string(), pool.clone());
+        let row = |id : i32, name : &str| { 
+            let mut rows = HashMap::new();
+            rows.insert("id".to_string(), Value::Number(BigDecimal::from(id)));
+            rows.insert("name".to_string(), Value::String(name.repeat(500)));
+            StoredRow::new(rows)
+        };
+        // more pages than frames, so pages are evicted while the rows are written
+        storage.write_batch((1..=30).map(|id| row(id, "x")).collect()).unwrap();
+        let pages = Page::page_count("pool_test");
+        let (hits, misses) = (pool.hits(), pool.misses());
+        storage.read(Value::Number(BigDecimal::from(30))).unwrap();
+        storage.read(Value::Number(BigDecimal::from(29))).unwrap();
+        let (read_hits, read_misses) = (pool.hits() - hits, pool.misses() - misses);
+        storage.update(&Value::Number(BigDecimal::from(1)), row(1, "y")).unwrap();
+        let on_disk = Page::read(1, "pool_test".to_string()).unwrap().tuple(0).map(<[u8]>::to_vec);
+        let rows = storage.read_all();
+        storage.delete(None, &NoFunctions);
+        std::fs::remove_file("pool_test_storage.json").unwrap();
+
+        assert!(pages > 2);
+        // the last page written is still in its frame
+        assert_eq!((read_hits, read_misses), (2, 0));
+        assert!(on_disk.is_some_and(|tuple| tuple.windows(3).any(|window| window == b"yyy")));
+        assert_eq!(rows.len(), 30);
+        assert_eq!(rows[0], row(1, "y"));
+        assert_eq!(rows[29], row(30, "x"));
+    }
+}
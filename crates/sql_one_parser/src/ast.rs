@@ -15,7 +15,7 @@ use crate::{
     parser::{peek_then_cut, Parse},
 };
 
-use crate::commands::{create::CreateStatement, select::SelectStatement, insert::InsertStatement};
+use crate::commands::{compound::CompoundSelect, create::CreateStatement, insert::InsertStatement, delete::DeleteStatement, update::UpdateStatement, drop::{DropStatement, TruncateStatement}, alter::AlterStatement, with::WithQuery};
 
 use self::select_condition::SelectStatementCondition;
 
@@ -23,8 +23,17 @@ use self::select_condition::SelectStatementCondition;
 #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub enum SqlQuery {
     Select(SelectStatementCondition),
+    /// selects combined by UNION, INTERSECT or EXCEPT
+    Compound(CompoundSelect),
+    /// a select reading the common table expressions of its WITH clause
+    With(WithQuery),
     Insert(InsertStatement),
     Create(CreateStatement),
+    Delete(DeleteStatement),
+    Update(UpdateStatement),
+    Drop(DropStatement),
+    Truncate(TruncateStatement),
+    Alter(AlterStatement),
 }
 
 impl<'a> Parse<'a> for SqlQuery {
@@ -35,9 +44,21 @@ impl<'a> Parse<'a> for SqlQuery {
                 multispace0,
                 tuple((
                     alt((
-                        peek_then_cut("select", map(SelectStatementCondition::parse, SqlQuery::Select)),
+                        peek_then_cut("select", map(CompoundSelect::parse, |compound| {
+                            if compound.rest.is_empty() {
+                                SqlQuery::Select(compound.first)
+                            } else {
+                                SqlQuery::Compound(compound)
+                            }
+                        })),
+                        peek_then_cut("with", map(WithQuery::parse, SqlQuery::With)),
                         peek_then_cut("insert", map(InsertStatement::parse, SqlQuery::Insert)),
                         peek_then_cut("create", map(CreateStatement::parse, SqlQuery::Create)),
+                        peek_then_cut("delete", map(DeleteStatement::parse, SqlQuery::Delete)),
+                        peek_then_cut("update", map(UpdateStatement::parse, SqlQuery::Update)),
+                        peek_then_cut("drop", map(DropStatement::parse, SqlQuery::Drop)),
+                        peek_then_cut("truncate", map(TruncateStatement::parse, SqlQuery::Truncate)),
+                        peek_then_cut("alter", map(AlterStatement::parse, SqlQuery::Alter)),
                     )),
                     multispace0,
                     char(';'),
@@ -90,7 +111,7 @@ mod tests {
     use super::*;
     use crate::value::Value;
     use crate::commands::create::CreateStatement;
-    use crate::commands::{select::SelectStatement, insert::InsertStatement};
+    use crate::commands::{select::SelectStatement, insert::{InsertSource, InsertStatement}};
 
     #[test]
     fn test_error() {
@@ -116,10 +137,12 @@ mod tests {
     fn test_insert() {
         let expected = InsertStatement {
             table: "foo".to_string(),
-            values: vec![
+            columns: Vec::new(),
+            source: InsertSource::Values(vec![vec![
                 Value::String("foo".to_string()),
                 Value::Number(bigdecimal::BigDecimal::from_i32(445 as i32).unwrap()),
-            ],
+            ]]),
+            on_conflict: None,
         };
         assert_eq!(
             SqlQuery::parse_from_raw("insert into foo values 'foo',445;")
@@ -0,0 +1,181 @@
+use core::fmt;
+
+use nom::{
+    branch::alt,
+    character::complete::multispace1,
+    combinator::{map, opt},
+    error::context,
+    sequence::{pair, preceded, separated_pair, tuple},
+};
+use nom_supreme::{tag::complete::tag_no_case, ParserExt};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commands::create::Column,
+    parser::{identifier, keyword, Parse, ParseResult, RawSpan},
+};
+
+/// The change to apply to a table's definition
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum AlterAction {
+    /// the column's DEFAULT constraint provides the value of the existing rows
+    AddColumn(Column),
+    DropColumn(String),
+    RenameColumn {
+        from: String,
+        to: String,
+    },
+    RenameTable(String),
+}
+
+impl fmt::Display for AlterAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlterAction::AddColumn(column) => {
+                write!(f, "ADD COLUMN {} {}", column.name, column.type_info)?;
+                if let Some(default) = column.default() {
+                    write!(f, " DEFAULT {}", default)?;
+                }
+                Ok(())
+            }
+            AlterAction::DropColumn(name) => write!(f, "DROP COLUMN {}", name),
+            AlterAction::RenameColumn { from, to } => write!(f, "RENAME COLUMN {} TO {}", from, to),
+            AlterAction::RenameTable(name) => write!(f, "RENAME TO {}", name),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct AlterStatement {
+    pub table: String,
+    pub action: AlterAction,
+}
+
+impl fmt::Display for AlterStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ALTER TABLE {} {}", self.table, self.action)
+    }
+}
+
+// parses an optional "COLUMN" keyword followed by whitespace
+fn opt_column_keyword(input: RawSpan<'_>) -> ParseResult<'_, Option<RawSpan<'_>>> {
+    opt(map(pair(keyword("column"), multispace1), |(kw, _)| kw))(input)
+}
+
+// parses "ADD [COLUMN] <colName> <colType> [<constraint> ...]"
+fn add_column(input: RawSpan<'_>) -> ParseResult<'_, AlterAction> {
+    map(
+        tuple((keyword("add"), multispace1, opt_column_keyword, Column::parse)),
+        |(_, _, _, column)| AlterAction::AddColumn(column),
+    )(input)
+}
+
+// parses "DROP [COLUMN] <colName>"
+fn drop_column(input: RawSpan<'_>) -> ParseResult<'_, AlterAction> {
+    map(
+        tuple((
+            keyword("drop"),
+            multispace1,
+            opt_column_keyword,
+            identifier.context("Column Name"),
+        )),
+        |(_, _, _, name)| AlterAction::DropColumn(name),
+    )(input)
+}
+
+// parses "RENAME TO <table name>" or "RENAME [COLUMN] <colName> TO <colName>"
+fn rename(input: RawSpan<'_>) -> ParseResult<'_, AlterAction> {
+    preceded(
+        pair(keyword("rename"), multispace1),
+        alt((
+            map(
+                preceded(pair(keyword("to"), multispace1), identifier.context("Table Name")),
+                AlterAction::RenameTable,
+            ),
+            map(
+                preceded(
+                    opt_column_keyword,
+                    separated_pair(
+                        identifier.context("Column Name"),
+                        tuple((multispace1, keyword("to"), multispace1)),
+                        identifier.context("Column Name"),
+                    ),
+                ),
+                |(from, to)| AlterAction::RenameColumn { from, to },
+            ),
+        )),
+    )(input)
+}
+
+// parses "ALTER TABLE <table name> <action>"
+impl<'a> Parse<'a> for AlterStatement {
+    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+        let (remaining_input, (_, _, table, action)) = context(
+            "Alter Statement",
+            tuple((
+                tag_no_case("alter"),
+                preceded(multispace1, tag_no_case("table")),
+                preceded(multispace1, identifier.context("Table Name")),
+                preceded(
+                    multispace1,
+                    alt((add_column, drop_column, rename)).context("Alter Action"),
+                ),
+            )),
+        )(input)?;
+
+        Ok((remaining_input, AlterStatement { table, action }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::{BigDecimal, FromPrimitive};
+
+    use super::*;
+    use crate::{
+        commands::create::{ColumnConstraint, SqlTypeInfo},
+        value::Value,
+    };
+
+    fn parse_action(input: &str) -> AlterAction {
+        AlterStatement::parse_from_raw(input).unwrap().1.action
+    }
+
+    #[test]
+    fn test_add_column() {
+        let expected = AlterAction::AddColumn(Column {
+            name: "age".to_string(),
+            type_info: SqlTypeInfo::Int,
+            constraints: vec![ColumnConstraint::Default(Value::Number(
+                BigDecimal::from_i32(18).unwrap(),
+            ))],
+        });
+        assert_eq!(parse_action("ALTER TABLE foo ADD COLUMN age int DEFAULT 18;"), expected);
+
+        let expected = AlterAction::AddColumn(Column {
+            name: "nick".to_string(),
+            type_info: SqlTypeInfo::String,
+            constraints: Vec::new(),
+        });
+        assert_eq!(parse_action("alter table foo add nick string;"), expected);
+    }
+
+    #[test]
+    fn test_drop_and_rename() {
+        assert_eq!(
+            parse_action("alter table foo drop column age;"),
+            AlterAction::DropColumn("age".to_string())
+        );
+        assert_eq!(
+            parse_action("alter table foo rename column age to years;"),
+            AlterAction::RenameColumn {
+                from: "age".to_string(),
+                to: "years".to_string()
+            }
+        );
+        assert_eq!(
+            parse_action("alter table foo rename to bar;"),
+            AlterAction::RenameTable("bar".to_string())
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use core::fmt;
+
+use nom::{
+    branch::alt,
+    character::complete::{multispace0, multispace1},
+    combinator::{cut, map, opt, verify},
+    error::context,
+    multi::many0,
+    sequence::{pair, preceded},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commands::select_condition::{OrderBy, SelectStatementCondition},
+    parser::{keyword, Parse, ParseResult, RawSpan},
+};
+
+/// How the rows of a select are combined with the rows before it
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum SetOperator {
+    /// the distinct rows of both
+    Union,
+    /// every row of both, duplicates included
+    UnionAll,
+    /// the distinct rows found in both
+    Intersect,
+    /// the distinct rows before it that it does not give
+    Except,
+}
+
+impl fmt::Display for SetOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            SetOperator::Union => "UNION",
+            SetOperator::UnionAll => "UNION ALL",
+            SetOperator::Intersect => "INTERSECT",
+            SetOperator::Except => "EXCEPT",
+        };
+        write!(f, "{op}")
+    }
+}
+
+// parses "UNION [ALL]", "INTERSECT" or "EXCEPT"
+impl<'a> Parse<'a> for SetOperator {
+    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+        alt((
+            map(
+                pair(keyword("union"), opt(preceded(multispace1, keyword("all")))),
+                |(_, all)| match all {
+                    Some(_) => SetOperator::UnionAll,
+                    None => SetOperator::Union,
+                },
+            ),
+            map(keyword("intersect"), |_| SetOperator::Intersect),
+            map(keyword("except"), |_| SetOperator::Except),
+        ))(input)
+    }
+}
+
+/// Selects whose rows are combined by set operations, e.g. `<select> UNION ALL <select>`.
+/// The operations all bind equally and apply from left to right, and the ORDER BY, LIMIT and
+/// OFFSET following the last select apply to the combined rows
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct CompoundSelect {
+    pub first: SelectStatementCondition,
+    pub rest: Vec<(SetOperator, SelectStatementCondition)>,
+    /// sort keys naming the output columns of the first select
+    pub order_by: Vec<OrderBy>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+impl fmt::Display for CompoundSelect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.first)?;
+
+        for (op, select) in self.rest.iter() {
+            write!(f, " {} {}", op, select)?;
+        }
+
+        if !self.order_by.is_empty() {
+            let order_by: Vec<String> = self.order_by.iter().map(ToString::to_string).collect();
+            write!(f, " ORDER BY {}", order_by.join(", "))?;
+        }
+
+        if let Some(limit) = self.limit {
+            write!(f, " LIMIT {}", limit)?;
+        }
+
+        if let Some(offset) = self.offset {
+            write!(f, " OFFSET {}", offset)?;
+        }
+
+        Ok(())
+    }
+}
+
+// whether the select ends with an ORDER BY, LIMIT or OFFSET, which only the last select of a compound may
+fn is_ordered(select: &SelectStatementCondition) -> bool {
+    !select.order_by.is_empty() || select.limit.is_some() || select.offset.is_some()
+}
+
+// parses "<select> [UNION [ALL] | INTERSECT | EXCEPT <select>] ...", a single select is a
+// compound without set operations that keeps its own ORDER BY, LIMIT and OFFSET
+impl<'a> Parse<'a> for CompoundSelect {
+    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+        let (remaining_input, (first, mut rest)) = context(
+            "Compound Select",
+            verify(
+                pair(
+                    SelectStatementCondition::parse,
+                    many0(pair(
+                        preceded(multispace0, SetOperator::parse),
+                        preceded(multispace1, cut(SelectStatementCondition::parse)),
+                    )),
+                ),
+                |(first, rest): &(SelectStatementCondition, Vec<(SetOperator, SelectStatementCondition)>)| {
+                    let selects = std::iter::once(first).chain(rest.iter().map(|(_, select)| select));
+                    selects.take(rest.len()).all(|select| !is_ordered(select))
+                },
+            ),
+        )(input)?;
+
+        let mut compound = CompoundSelect {
+            first,
+            rest: Vec::new(),
+            order_by: Vec::new(),
+            limit: None,
+            offset: None,
+        };
+        if let Some((_, last)) = rest.last_mut() {
+            compound.order_by = std::mem::take(&mut last.order_by);
+            compound.limit = last.limit.take();
+            compound.offset = last.offset.take();
+        }
+        compound.rest = rest;
+        Ok((remaining_input, compound))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compound_select() {
+        let input = "select id, name from jan union all select id, name from feb where id > 1 union select a, b from mar except select x, y from apr order by name desc limit 5";
+        let actual = CompoundSelect::parse_from_raw(input).unwrap().1;
+        let ops: Vec<SetOperator> = actual.rest.iter().map(|(op, _)| *op).collect();
+        assert_eq!(ops, vec![SetOperator::UnionAll, SetOperator::Union, SetOperator::Except]);
+        // the trailing ORDER BY and LIMIT belong to the compound rather than its last select
+        assert_eq!(actual.limit, Some(5));
+        assert_eq!(actual.rest[2].1.limit, None);
+        assert_eq!(
+            actual.to_string(),
+            "SELECT id, name FROM jan UNION ALL SELECT id, name FROM feb WHERE (id > 1) UNION SELECT a, b FROM mar EXCEPT SELECT x, y FROM apr ORDER BY name DESC LIMIT 5"
+        );
+
+        let single = CompoundSelect::parse_from_raw("select a from t intersect select a from u").unwrap().1;
+        assert_eq!(single.rest[0].0, SetOperator::Intersect);
+        let single = CompoundSelect::parse_from_raw("select a from t limit 1").unwrap().1;
+        assert!(single.rest.is_empty());
+        assert_eq!(single.first.limit, Some(1));
+
+        assert!(CompoundSelect::parse_from_raw("select a from t limit 1 union select a from u").is_err());
+        assert!(CompoundSelect::parse_from_raw("select a from t union update").is_err());
+    }
+}
@@ -1,70 +1,231 @@
-use nom::{branch::alt, bytes::complete::{tag, take_while1}, character::complete::{multispace1,char}, combinator::map, error::context, multi::separated_list1, sequence::{preceded, separated_pair, tuple}, IResult};
-use nom_locate::LocatedSpan;
+use nom::{branch::alt, character::complete::{multispace0, multispace1, char, digit1}, combinator::{cut, map, map_res}, error::context, multi::many0, sequence::{delimited, pair, preceded, separated_pair, tuple}};
 use serde::{Deserialize, Serialize};
-use nom_supreme::{parser_ext, tag::complete::tag_no_case, ParserExt}; // Added ParserExt here
-use crate::parser::{RawSpan, ParseResult, Parse, identifier,comma_sep};
+use nom_supreme::{tag::complete::tag_no_case, ParserExt}; // Added ParserExt here
+use crate::{commands::select_condition::SelectStatementCondition, parser::{RawSpan, ParseResult, Parse, identifier, keyword, comma_sep}, value::Value};
 
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize, derive_more::Display)]
-pub enum SqlTypeInfo { 
+pub enum SqlTypeInfo {
     String,
-    Int
+    /// a 64 bit signed integer
+    Int,
+    /// a double precision floating point number
+    Float,
+    Boolean,
+    Date,
+    Timestamp,
+    Blob,
+    /// a string of at most the given number of characters
+    #[display(fmt = "Varchar({})", _0)]
+    Varchar(usize),
 }
 
 impl<'a> Parse<'a> for SqlTypeInfo {
     fn parse(input : RawSpan<'a>) -> ParseResult<'a, Self> {
         context(
-            "Column_Type", 
+            "Column_Type",
             alt((
-                map(tag_no_case("string"), |_| Self::String),
-                map(tag_no_case("int"), |_| Self::Int)
+                map(keyword("string"), |_| Self::String),
+                map(keyword("int"), |_| Self::Int),
+                map(keyword("float"), |_| Self::Float),
+                map(keyword("boolean"), |_| Self::Boolean),
+                map(keyword("date"), |_| Self::Date),
+                map(keyword("timestamp"), |_| Self::Timestamp),
+                map(keyword("blob"), |_| Self::Blob),
+                map(
+                    preceded(
+                        pair(keyword("varchar"), multispace0),
+                        cut(delimited(
+                            pair(char('('), multispace0),
+                            map_res(digit1, |digits: RawSpan| digits.fragment().parse::<usize>()),
+                            pair(multispace0, char(')')),
+                        ))
+                        .context("Varchar Length"),
+                    ),
+                    Self::Varchar,
+                ),
             ))
         )(input)
     }
 }
 
+/// A constraint declared after the type of a column
+#[derive(Debug, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum ColumnConstraint {
+    PrimaryKey,
+    NotNull,
+    Unique,
+    Default(Value),
+}
+
+impl<'a> Parse<'a> for ColumnConstraint {
+    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+        context(
+            "Column Constraint",
+            alt((
+                map(tuple((keyword("primary"), multispace1, keyword("key"))), |_| Self::PrimaryKey),
+                map(tuple((keyword("not"), multispace1, keyword("null"))), |_| Self::NotNull),
+                map(keyword("unique"), |_| Self::Unique),
+                map(
+                    preceded(pair(keyword("default"), multispace1), Value::parse.context("Default Value")),
+                    Self::Default,
+                ),
+            )),
+        )(input)
+    }
+}
+
 #[derive(Debug, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct Column {
     pub name: String,
     pub type_info: SqlTypeInfo,
+    #[serde(default)]
+    pub constraints: Vec<ColumnConstraint>,
+}
+
+impl Column {
+    pub fn has_constraint(&self, constraint: &ColumnConstraint) -> bool {
+        self.constraints.contains(constraint)
+    }
+
+    /// The value used when an insert does not provide one for this column
+    pub fn default(&self) -> Option<&Value> {
+        self.constraints.iter().find_map(|constraint| match constraint {
+            ColumnConstraint::Default(value) => Some(value),
+            _ => None,
+        })
+    }
 }
 
-// parses "<colName> <colType>"
+// parses "<colName> <colType> [<constraint> ...]"
 impl<'a> Parse<'a> for Column {
     fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
         context(
             "Create Column",
             map(
-                separated_pair(
-                    identifier.context("Column Name"),
-                    multispace1,
-                    SqlTypeInfo::parse,
+                pair(
+                    separated_pair(
+                        identifier.context("Column Name"),
+                        multispace1,
+                        SqlTypeInfo::parse,
+                    ),
+                    many0(preceded(multispace0, ColumnConstraint::parse)),
                 ),
-                |(name, type_info)| Self { name, type_info },
+                |((name, type_info), constraints)| Self { name, type_info, constraints },
             ),
         )(input)
     }
 }
 
+/// A constraint spanning one or more columns, declared alongside the column definitions
+#[derive(Debug, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum TableConstraint {
+    PrimaryKey(Vec<String>),
+    Unique(Vec<String>),
+}
+
+// parses "PRIMARY KEY (<colName>, ...)" or "UNIQUE (<colName>, ...)"
+impl<'a> Parse<'a> for TableConstraint {
+    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+        let columns = || {
+            map(
+                tuple((multispace0, char('('), multispace0, comma_sep(identifier), multispace0, char(')'))),
+                |(_, _, _, columns, _, _)| columns,
+            )
+        };
+        context(
+            "Table Constraint",
+            alt((
+                map(
+                    preceded(tuple((keyword("primary"), multispace1, keyword("key"))), columns()),
+                    Self::PrimaryKey,
+                ),
+                map(preceded(keyword("unique"), columns()), Self::Unique),
+            )),
+        )(input)
+    }
+}
+
 /// The table and its columns to create
 #[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct CreateStatement {
     pub table: String,
     pub columns: Vec<Column>,
+    #[serde(default)]
+    pub constraints: Vec<TableConstraint>,
+    /// the select the table is created from, `CREATE TABLE <table> AS <select>`, its columns
+    /// are then those of the select's output
+    #[serde(default)]
+    pub as_select: Option<Box<SelectStatementCondition>>,
 }
 
-// parses a comma seperated list of column definitions contained in parens
-fn column_definitions(input: RawSpan<'_>) -> ParseResult<'_, Vec<Column>> {
+impl CreateStatement {
+    /// The primary key columns declared either on a column or as a table constraint
+    pub fn primary_key(&self) -> Vec<Vec<String>> {
+        let column_keys = self
+            .columns
+            .iter()
+            .filter(|col| col.has_constraint(&ColumnConstraint::PrimaryKey))
+            .map(|col| vec![col.name.clone()]);
+        let table_keys = self.constraints.iter().filter_map(|constraint| match constraint {
+            TableConstraint::PrimaryKey(columns) => Some(columns.clone()),
+            _ => None,
+        });
+        column_keys.chain(table_keys).collect()
+    }
+
+    /// The column sets declared unique either on a column or as a table constraint
+    pub fn unique_keys(&self) -> Vec<Vec<String>> {
+        let column_keys = self
+            .columns
+            .iter()
+            .filter(|col| col.has_constraint(&ColumnConstraint::Unique))
+            .map(|col| vec![col.name.clone()]);
+        let table_keys = self.constraints.iter().filter_map(|constraint| match constraint {
+            TableConstraint::Unique(columns) => Some(columns.clone()),
+            _ => None,
+        });
+        column_keys.chain(table_keys).collect()
+    }
+}
+
+// a single entry of the parenthesized definitions list
+enum Definition {
+    Column(Column),
+    Constraint(TableConstraint),
+}
+
+// parses a comma seperated list of column definitions and table constraints contained in parens
+fn column_definitions(input: RawSpan<'_>) -> ParseResult<'_, (Vec<Column>, Vec<TableConstraint>)> {
     context(
         "Column Definitions",
         map(
-            tuple((char('('), comma_sep(Column::parse), char(')'))),
-            |(_, cols, _)| cols,
+            tuple((
+                char('('),
+                multispace0,
+                comma_sep(alt((
+                    map(TableConstraint::parse, Definition::Constraint),
+                    map(Column::parse, Definition::Column),
+                ))),
+                multispace0,
+                char(')'),
+            )),
+            |(_, _, definitions, _, _)| {
+                let mut columns = Vec::new();
+                let mut constraints = Vec::new();
+                for definition in definitions {
+                    match definition {
+                        Definition::Column(column) => columns.push(column),
+                        Definition::Constraint(constraint) => constraints.push(constraint),
+                    }
+                }
+                (columns, constraints)
+            },
         ),
     )(input)
 }
 
-// parses "CREATE TABLE <table name> <column defs>
+// parses "CREATE TABLE <table name> <column defs>" or "CREATE TABLE <table name> AS <select>"
 impl<'a> Parse<'a> for CreateStatement {
     fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
         map(
@@ -80,11 +241,17 @@ impl<'a> Parse<'a> for CreateStatement {
                     identifier.context("Table Name"),
                 ),
                 multispace1,
-                // column defs
-                column_definitions,
+                alt((
+                    // column defs
+                    map(column_definitions, |(columns, constraints)| (columns, constraints, None)),
+                    map(
+                        preceded(pair(keyword("as"), multispace1), SelectStatementCondition::parse),
+                        |select| (Vec::new(), Vec::new(), Some(Box::new(select))),
+                    ),
+                )),
             )
             .context("Create Table"),
-            |(table, columns)| Self { table, columns },
+            |(table, (columns, constraints, as_select))| Self { table, columns, constraints, as_select },
         )(input)
     }
-}
\ No newline at end of file
+}
@@ -2,7 +2,9 @@
 #[cfg(test)]
 mod CreateTest { 
 
-    use crate::{commands::create::{Column, CreateStatement, SqlTypeInfo}, parser::Parse};
+    use bigdecimal::{BigDecimal, FromPrimitive};
+
+    use crate::{commands::create::{Column, ColumnConstraint, CreateStatement, SqlTypeInfo, TableConstraint}, parser::Parse, value::Value};
 
     use super::*;
     
@@ -11,12 +13,67 @@ mod CreateTest {
         let expected = CreateStatement {
             table: "foo".to_string(),
             columns : vec![
-                Column{name: "col1".to_string(), type_info: SqlTypeInfo::Int},
-                Column{name: "col2".to_string(), type_info: SqlTypeInfo::String}
-            ]
+                Column{name: "col1".to_string(), type_info: SqlTypeInfo::Int, constraints: vec![]},
+                Column{name: "col2".to_string(), type_info: SqlTypeInfo::String, constraints: vec![]}
+            ],
+            constraints: vec![],
+            as_select: None
         };
         let actual = CreateStatement::parse_from_raw("CREATE TABLE foo (col1 int, col2 string)").unwrap().1;
         println!("actual is {:#?} expected is {:#?}", actual, expected);
         assert_eq!(actual, expected);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_create_with_constraints() { 
+        let input = "CREATE TABLE foo (a int not null, b string UNIQUE default 'x', c int, PRIMARY KEY (a, c))";
+        let expected = CreateStatement {
+            table: "foo".to_string(),
+            columns : vec![
+                Column{name: "a".to_string(), type_info: SqlTypeInfo::Int, constraints: vec![ColumnConstraint::NotNull]},
+                Column{name: "b".to_string(), type_info: SqlTypeInfo::String, constraints: vec![
+                    ColumnConstraint::Unique,
+                    ColumnConstraint::Default(Value::String("x".to_string()))
+                ]},
+                Column{name: "c".to_string(), type_info: SqlTypeInfo::Int, constraints: vec![]}
+            ],
+            constraints: vec![TableConstraint::PrimaryKey(vec!["a".to_string(), "c".to_string()])],
+            as_select: None
+        };
+        let actual = CreateStatement::parse_from_raw(input).unwrap().1;
+        assert_eq!(actual, expected);
+        assert_eq!(actual.primary_key(), vec![vec!["a".to_string(), "c".to_string()]]);
+        assert_eq!(actual.unique_keys(), vec![vec!["b".to_string()]]);
+
+        let actual = CreateStatement::parse_from_raw("create table foo (id int primary key, n int default 1 not null)").unwrap().1;
+        assert_eq!(actual.primary_key(), vec![vec!["id".to_string()]]);
+        assert_eq!(actual.columns[1].default(), Some(&Value::Number(BigDecimal::from_i32(1).unwrap())));
+        assert!(actual.columns[1].has_constraint(&ColumnConstraint::NotNull));
+    }
+
+    #[test]
+    fn test_create_column_types() { 
+        let actual = CreateStatement::parse_from_raw("create table foo (a float, b boolean, c date, d timestamp, e blob, f varchar( 12 ))").unwrap().1;
+        let types : Vec<SqlTypeInfo> = actual.columns.into_iter().map(|col| col.type_info).collect();
+        assert_eq!(types, vec![
+            SqlTypeInfo::Float,
+            SqlTypeInfo::Boolean,
+            SqlTypeInfo::Date,
+            SqlTypeInfo::Timestamp,
+            SqlTypeInfo::Blob,
+            SqlTypeInfo::Varchar(12)
+        ]);
+        assert!(CreateStatement::parse_from_raw("create table foo (a varchar)").is_err());
+    }
+
+    #[test]
+    fn test_create_as_select() { 
+        let actual = CreateStatement::parse_from_raw("create table rich as select id, name from users where balance > 100").unwrap().1;
+        assert_eq!(actual.table, "rich".to_string());
+        assert!(actual.columns.is_empty());
+        let select = actual.as_select.expect("a select");
+        assert_eq!(select.table, "users".to_string());
+        assert_eq!(select.fields.len(), 2);
+        assert!(CreateStatement::parse_from_raw("create table rich as").is_err());
+    }
+}
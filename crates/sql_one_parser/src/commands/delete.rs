@@ -0,0 +1,84 @@
+use core::fmt;
+
+use nom::{
+    character::complete::multispace1,
+    error::context,
+    sequence::{preceded, tuple},
+};
+use nom_supreme::{tag::complete::tag_no_case, ParserExt};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commands::select_condition::where_clause,
+    expr::Expr,
+    parser::{identifier, Parse, ParseResult, RawSpan},
+};
+
+/// The table to delete from and the rows to delete (all of them when there is no where clause)
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct DeleteStatement {
+    pub table: String,
+    pub where_clause: Option<Expr>,
+}
+
+impl fmt::Display for DeleteStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DELETE FROM {}", self.table)?;
+
+        if let Some(where_clause) = &self.where_clause {
+            write!(f, " WHERE {}", where_clause)?;
+        }
+
+        Ok(())
+    }
+}
+
+// parses "DELETE FROM <table name> [WHERE <expr>]"
+impl<'a> Parse<'a> for DeleteStatement {
+    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+        let (remaining_input, (_, _, table, where_clause)) = context(
+            "Delete Statement",
+            tuple((
+                tag_no_case("delete"),
+                preceded(multispace1, tag_no_case("from")),
+                preceded(multispace1, identifier.context("Table Name")),
+                where_clause,
+            )),
+        )(input)?;
+
+        Ok((remaining_input, DeleteStatement { table, where_clause }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::{BigDecimal, FromPrimitive};
+
+    use super::*;
+    use crate::{expr::BinaryOp, value::Value};
+
+    #[test]
+    fn test_delete_all() {
+        let expected = DeleteStatement {
+            table: "foo".to_string(),
+            where_clause: None,
+        };
+        assert_eq!(DeleteStatement::parse_from_raw("DELETE FROM foo;").unwrap().1, expected);
+    }
+
+    #[test]
+    fn test_delete_with_where() {
+        let expected = DeleteStatement {
+            table: "foo".to_string(),
+            where_clause: Some(Expr::binary(
+                Expr::Column("id".to_string()),
+                BinaryOp::GtEq,
+                Expr::Literal(Value::Number(BigDecimal::from_i32(2).unwrap())),
+            )),
+        };
+        assert_eq!(
+            DeleteStatement::parse_from_raw("delete from foo where id >= 2;").unwrap().1,
+            expected
+        );
+    }
+}
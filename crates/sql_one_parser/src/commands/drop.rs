@@ -0,0 +1,116 @@
+use core::fmt;
+
+use nom::{
+    character::complete::multispace1,
+    combinator::{map, opt},
+    error::context,
+    sequence::{preceded, tuple},
+};
+use nom_supreme::{tag::complete::tag_no_case, ParserExt};
+use serde::{Deserialize, Serialize};
+
+use crate::parser::{identifier, keyword, Parse, ParseResult, RawSpan};
+
+/// The table to remove along with all of its rows
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct DropStatement {
+    pub table: String,
+    pub if_exists: bool,
+}
+
+impl fmt::Display for DropStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DROP TABLE ")?;
+
+        if self.if_exists {
+            write!(f, "IF EXISTS ")?;
+        }
+
+        write!(f, "{}", self.table)
+    }
+}
+
+// parses "DROP TABLE [IF EXISTS] <table name>"
+impl<'a> Parse<'a> for DropStatement {
+    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+        let (remaining_input, (_, _, if_exists, table)) = context(
+            "Drop Statement",
+            tuple((
+                tag_no_case("drop"),
+                preceded(multispace1, tag_no_case("table")),
+                map(
+                    opt(preceded(
+                        multispace1,
+                        tuple((keyword("if"), multispace1, keyword("exists"))),
+                    )),
+                    |if_exists| if_exists.is_some(),
+                ),
+                preceded(multispace1, identifier.context("Table Name")),
+            )),
+        )(input)?;
+
+        Ok((remaining_input, DropStatement { table, if_exists }))
+    }
+}
+
+/// The table to remove all rows from, keeping its definition
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct TruncateStatement {
+    pub table: String,
+}
+
+impl fmt::Display for TruncateStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TRUNCATE TABLE {}", self.table)
+    }
+}
+
+// parses "TRUNCATE TABLE <table name>"
+impl<'a> Parse<'a> for TruncateStatement {
+    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+        let (remaining_input, (_, _, table)) = context(
+            "Truncate Statement",
+            tuple((
+                tag_no_case("truncate"),
+                preceded(multispace1, tag_no_case("table")),
+                preceded(multispace1, identifier.context("Table Name")),
+            )),
+        )(input)?;
+
+        Ok((remaining_input, TruncateStatement { table }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop() {
+        let expected = DropStatement {
+            table: "foo".to_string(),
+            if_exists: false,
+        };
+        assert_eq!(DropStatement::parse_from_raw("DROP TABLE foo;").unwrap().1, expected);
+
+        let expected = DropStatement {
+            table: "ifoo".to_string(),
+            if_exists: true,
+        };
+        assert_eq!(
+            DropStatement::parse_from_raw("drop table if exists ifoo;").unwrap().1,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_truncate() {
+        let expected = TruncateStatement {
+            table: "foo".to_string(),
+        };
+        assert_eq!(
+            TruncateStatement::parse_from_raw("truncate table foo;").unwrap().1,
+            expected
+        );
+    }
+}
@@ -1,35 +1,216 @@
 use nom::{
-    character::complete::multispace1,
+    branch::alt,
+    character::complete::{char, multispace0, multispace1},
+    combinator::{map, opt},
     error::context,
-    sequence::{preceded, tuple},
+    sequence::{delimited, pair, preceded, tuple},
 };
 use nom_supreme::{tag::complete::tag_no_case, ParserExt};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    parser::{comma_sep, identifier, Parse, ParseResult, RawSpan},
+    commands::{select_condition::SelectStatementCondition, update::Assignment},
+    parser::{comma_sep, identifier, keyword, Parse, ParseResult, RawSpan},
     value::Value,
 };
 
-#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct InsertStatement {
     pub table: String,
-    pub values: Vec<Value>,
+    /// the columns the values are given for, empty when they follow the table's column order
+    pub columns: Vec<String>,
+    pub source: InsertSource,
+    pub on_conflict: Option<OnConflict>,
 } // TODO: impl display
 
+/// Where the inserted rows come from
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum InsertSource {
+    /// `VALUES`, one list of values per row
+    Values(Vec<Vec<Value>>),
+    /// the output rows of a select, its columns in order
+    Select(Box<SelectStatementCondition>),
+}
+
+/// What to do when the inserted row collides with an existing one
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct OnConflict {
+    /// the conflicting columns, empty when any conflict is meant
+    pub columns: Vec<String>,
+    pub action: ConflictAction,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum ConflictAction {
+    DoNothing,
+    /// update the existing row, the proposed row is available as `excluded.<column>`
+    DoUpdate(Vec<Assignment>),
+}
+
+// parses "ON CONFLICT [(<col>, ...)] DO NOTHING | DO UPDATE SET <assignments>"
+impl<'a> Parse<'a> for OnConflict {
+    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+        let (remaining_input, (_, _, _, columns, _, _, action)) = context(
+            "On Conflict",
+            tuple((
+                keyword("on"),
+                multispace1,
+                keyword("conflict"),
+                map(
+                    opt(preceded(
+                        multispace0,
+                        delimited(
+                            char('('),
+                            comma_sep(identifier).context("Conflict Columns"),
+                            char(')'),
+                        ),
+                    )),
+                    Option::unwrap_or_default,
+                ),
+                preceded(multispace0, keyword("do")),
+                multispace1,
+                alt((
+                    map(keyword("nothing"), |_| ConflictAction::DoNothing),
+                    map(
+                        preceded(
+                            tuple((keyword("update"), multispace1, keyword("set"), multispace1)),
+                            comma_sep(Assignment::parse).context("Assignments"),
+                        ),
+                        ConflictAction::DoUpdate,
+                    ),
+                )),
+            )),
+        )(input)?;
+
+        Ok((remaining_input, OnConflict { columns, action }))
+    }
+}
+
+// parses the rows after VALUES, either "(<value>, ...), (<value>, ...)" or a single
+// row given without parentheses, "<value>, ..."
+fn rows(input: RawSpan<'_>) -> ParseResult<'_, Vec<Vec<Value>>> {
+    alt((
+        comma_sep(delimited(
+            pair(char('('), multispace0),
+            comma_sep(Value::parse),
+            pair(multispace0, char(')')),
+        )),
+        map(comma_sep(Value::parse), |row| vec![row]),
+    ))(input)
+}
+
+// parses "INSERT INTO <table> [(<col>, ...)] VALUES <rows> | <select> [ON CONFLICT ...]"
 impl<'a> Parse<'a> for InsertStatement {
     fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
-        let (remaining_input, (_, _, table, _, values)) = context(
+        let (remaining_input, (_, _, table, columns, source, on_conflict)) = context(
             "Insert Statement",
             tuple((
                 tag_no_case("insert"),
                 preceded(multispace1, tag_no_case("into")),
                 preceded(multispace1, identifier.context("Table Name")),
-                preceded(multispace1, tag_no_case("values")),
-                preceded(multispace1, comma_sep(Value::parse).context("Values")),
+                map(
+                    opt(preceded(
+                        multispace0,
+                        delimited(
+                            pair(char('('), multispace0),
+                            comma_sep(identifier).context("Insert Columns"),
+                            pair(multispace0, char(')')),
+                        ),
+                    )),
+                    Option::unwrap_or_default,
+                ),
+                preceded(
+                    multispace0,
+                    alt((
+                        map(
+                            preceded(pair(keyword("values"), multispace0), rows.context("Values")),
+                            InsertSource::Values,
+                        ),
+                        map(SelectStatementCondition::parse, |select| {
+                            InsertSource::Select(Box::new(select))
+                        }),
+                    )),
+                ),
+                opt(preceded(multispace0, OnConflict::parse)),
             )),
         )(input)?;
 
-        Ok((remaining_input, InsertStatement { table, values }))
+        Ok((remaining_input, InsertStatement { table, columns, source, on_conflict }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::{BigDecimal, FromPrimitive};
+
+    use super::*;
+    use crate::expr::Expr;
+
+    #[test]
+    fn test_insert_on_conflict() {
+        let input = "insert into foo values 1,'raja' on conflict (id) do update set name = excluded.name;";
+        let expected = InsertStatement {
+            table: "foo".to_string(),
+            columns: Vec::new(),
+            source: InsertSource::Values(vec![vec![
+                Value::Number(BigDecimal::from_i32(1).unwrap()),
+                Value::String("raja".to_string()),
+            ]]),
+            on_conflict: Some(OnConflict {
+                columns: vec!["id".to_string()],
+                action: ConflictAction::DoUpdate(vec![Assignment {
+                    column: "name".to_string(),
+                    value: Expr::Column("excluded.name".to_string()),
+                }]),
+            }),
+        };
+        assert_eq!(InsertStatement::parse_from_raw(input).unwrap().1, expected);
+
+        let on_conflict = InsertStatement::parse_from_raw("insert into foo values 1 ON CONFLICT DO NOTHING;")
+            .unwrap()
+            .1
+            .on_conflict;
+        assert_eq!(
+            on_conflict,
+            Some(OnConflict {
+                columns: Vec::new(),
+                action: ConflictAction::DoNothing
+            })
+        );
+    }
+
+    #[test]
+    fn test_insert_columns_and_rows() {
+        let number = |n| Value::Number(BigDecimal::from_i32(n).unwrap());
+        let input = "INSERT INTO foo (id, name) VALUES (1, 'raja'), ( 2, 'neha' );";
+        let expected = InsertStatement {
+            table: "foo".to_string(),
+            columns: vec!["id".to_string(), "name".to_string()],
+            source: InsertSource::Values(vec![
+                vec![number(1), Value::String("raja".to_string())],
+                vec![number(2), Value::String("neha".to_string())],
+            ]),
+            on_conflict: None,
+        };
+        assert_eq!(InsertStatement::parse_from_raw(input).unwrap().1, expected);
+
+        let single = InsertStatement::parse_from_raw("insert into foo(id) values (3);").unwrap().1;
+        assert_eq!(single.columns, vec!["id".to_string()]);
+        assert_eq!(single.source, InsertSource::Values(vec![vec![number(3)]]));
+
+        assert!(InsertStatement::parse_from_raw("insert into foo (id values (3);").is_err());
+    }
+
+    #[test]
+    fn test_insert_select() {
+        let input = "insert into archive (id, name) select id, name from users where id > 10 on conflict do nothing";
+        let actual = InsertStatement::parse_from_raw(input).unwrap().1;
+        let InsertSource::Select(select) = actual.source else {
+            panic!("expected a select, got {:?}", actual.source);
+        };
+        assert_eq!(actual.columns, vec!["id".to_string(), "name".to_string()]);
+        assert_eq!(select.table, "users".to_string());
+        assert_eq!(select.where_clause, Some(Expr::parse_from_raw("id > 10").unwrap().1));
+        assert!(actual.on_conflict.is_some());
     }
-}
\ No newline at end of file
+}
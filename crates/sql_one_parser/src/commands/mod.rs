@@ -2,4 +2,10 @@ pub mod create;
 mod create_test;
 pub mod select;
 pub mod insert;
-pub mod select_condition;
\ No newline at end of file
+pub mod select_condition;
+pub mod delete;
+pub mod update;
+pub mod drop;
+pub mod alter;
+pub mod compound;
+pub mod with;
\ No newline at end of file
@@ -1,30 +1,220 @@
 use core::fmt;
 
-use nom::{bytes::complete::{tag, take_until, take_while}, character::complete::{multispace0, multispace1}, combinator::{cond, map, map_res, opt}, error::context, sequence::{pair, preceded, terminated, tuple}};
-use nom_supreme::{tag::{ complete::tag_no_case}, ParserExt};
+use nom::{
+    branch::alt,
+    character::complete::{char, digit1, multispace0, multispace1},
+    combinator::{cut, map, map_res, opt},
+    error::context,
+    multi::many0,
+    sequence::{pair, preceded, terminated, tuple},
+};
+use nom_supreme::{tag::complete::tag_no_case, ParserExt};
 use serde::{Deserialize, Serialize};
 
-use crate::{error::MyParseError, parser::{comma_sep, identifier, Parse, ParseResult, RawSpan}};
+use crate::{
+    expr::{column_ref, Expr},
+    parser::{comma_sep, identifier, keyword, Parse, ParseResult, RawSpan},
+};
 
 
 #[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct SelectStatementCondition {
     pub table: String,
-    pub fields: Vec<String>,
-    pub where_clause: Option<Condition>, // Add a where_clause field
+    /// the name the columns of `table` are qualified by, `FROM <table> AS <alias>`
+    pub alias: Option<String>,
+    pub joins: Vec<Join>,
+    /// whether duplicate output rows are dropped, `SELECT DISTINCT`
+    #[serde(default)]
+    pub distinct: bool,
+    pub fields: Vec<SelectItem>,
+    pub where_clause: Option<Expr>,
+    pub group_by: Vec<String>,
+    pub having: Option<Expr>,
+    pub order_by: Vec<OrderBy>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
 }
 
+/// An entry of the select list
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum SelectItem {
+    /// `*`, or `<table>.*` for the columns of a single table
+    Wildcard(Option<String>),
+    /// an expression whose output column is named by the alias, or otherwise by the expression's text
+    Expr { expr: Expr, alias: Option<String> },
+}
+
+impl SelectItem {
+    pub fn column(name: &str) -> Self {
+        SelectItem::Expr {
+            expr: Expr::Column(name.to_string()),
+            alias: None,
+        }
+    }
+
+    /// The expression of the item, None for a wildcard
+    pub fn expr(&self) -> Option<&Expr> {
+        match self {
+            SelectItem::Wildcard(_) => None,
+            SelectItem::Expr { expr, .. } => Some(expr),
+        }
+    }
+}
+
+impl fmt::Display for SelectItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SelectItem::Wildcard(None) => write!(f, "*"),
+            SelectItem::Wildcard(Some(table)) => write!(f, "{}.*", table),
+            SelectItem::Expr { expr, alias: None } => write!(f, "{}", expr),
+            SelectItem::Expr {
+                expr,
+                alias: Some(alias),
+            } => write!(f, "{} AS {}", expr, alias),
+        }
+    }
+}
+
+// parses "*", "<table>.*" or "<expr> [AS <alias>]"
+impl<'a> Parse<'a> for SelectItem {
+    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+        context(
+            "Select Item",
+            alt((
+                map(char('*'), |_| SelectItem::Wildcard(None)),
+                map(terminated(identifier, pair(char('.'), char('*'))), |table| {
+                    SelectItem::Wildcard(Some(table))
+                }),
+                map(
+                    pair(
+                        Expr::parse,
+                        opt(preceded(
+                            tuple((multispace0, keyword("as"), multispace1)),
+                            identifier.context("Column Alias"),
+                        )),
+                    ),
+                    |(expr, alias)| SelectItem::Expr { expr, alias },
+                ),
+            )),
+        )(input)
+    }
+}
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
-pub struct Condition { 
-    pub first : String, 
-    pub second : String,
-    pub token : String
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum JoinKind {
+    Inner,
+    /// keeps the left rows without a match, their right columns being NULL
+    Left,
 }
 
-impl fmt::Display for Condition {
+/// A table joined to the ones before it, `[INNER | LEFT [OUTER]] JOIN <table> [AS <alias>] ON <expr>`
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct Join {
+    pub kind: JoinKind,
+    pub table: String,
+    pub alias: Option<String>,
+    pub on: Expr,
+}
+
+impl fmt::Display for Join {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        todo!()
+        match self.kind {
+            JoinKind::Inner => write!(f, "JOIN {}", self.table)?,
+            JoinKind::Left => write!(f, "LEFT JOIN {}", self.table)?,
+        }
+        if let Some(alias) = &self.alias {
+            write!(f, " AS {}", alias)?;
+        }
+        write!(f, " ON {}", self.on)
+    }
+}
+
+// parses an optional "AS <alias>" following a table name
+fn table_alias(input: RawSpan<'_>) -> ParseResult<'_, Option<String>> {
+    opt(preceded(
+        tuple((multispace1, keyword("as"), multispace1)),
+        identifier.context("Table Alias"),
+    ))(input)
+}
+
+impl<'a> Parse<'a> for Join {
+    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+        let kind = alt((
+            map(keyword("join"), |_| JoinKind::Inner),
+            map(tuple((keyword("inner"), multispace1, keyword("join"))), |_| JoinKind::Inner),
+            map(
+                tuple((
+                    keyword("left"),
+                    multispace1,
+                    opt(pair(keyword("outer"), multispace1)),
+                    keyword("join"),
+                )),
+                |_| JoinKind::Left,
+            ),
+        ));
+        context(
+            "Join",
+            map(
+                pair(
+                    preceded(multispace0, kind),
+                    // a join keyword has to be followed by the table and its condition
+                    cut(tuple((
+                        multispace1,
+                        identifier.context("Join Table"),
+                        table_alias,
+                        preceded(
+                            tuple((multispace1, keyword("on"), multispace1)),
+                            Expr::parse.context("Join Condition"),
+                        ),
+                    ))),
+                ),
+                |(kind, (_, table, alias, on))| Join {
+                    kind,
+                    table,
+                    alias,
+                    on,
+                },
+            ),
+        )(input)
+    }
+}
+
+/// A sort key of the ORDER BY clause
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct OrderBy {
+    pub column: String,
+    pub descending: bool,
+}
+
+impl fmt::Display for OrderBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.column)?;
+        if self.descending {
+            write!(f, " DESC")?;
+        }
+        Ok(())
+    }
+}
+
+// parses "<column> [ASC | DESC]"
+impl<'a> Parse<'a> for OrderBy {
+    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+        map(
+            pair(
+                column_ref.context("Order Column"),
+                opt(preceded(
+                    multispace1,
+                    alt((
+                        map(keyword("asc"), |_| false),
+                        map(keyword("desc"), |_| true),
+                    )),
+                )),
+            ),
+            |(column, descending)| OrderBy {
+                column,
+                descending: descending.unwrap_or(false),
+            },
+        )(input)
     }
 }
 
@@ -32,137 +222,142 @@ impl fmt::Display for SelectStatementCondition {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "SELECT ")?;
 
-        write!(f, "{}", self.fields.join(", "))?;
+        if self.distinct {
+            write!(f, "DISTINCT ")?;
+        }
+
+        let fields: Vec<String> = self.fields.iter().map(ToString::to_string).collect();
+        write!(f, "{}", fields.join(", "))?;
 
         write!(f, " FROM ")?;
 
         write!(f, "{}", self.table)?;
 
+        if let Some(alias) = &self.alias {
+            write!(f, " AS {}", alias)?;
+        }
+
+        for join in self.joins.iter() {
+            write!(f, " {}", join)?;
+        }
+
         if let Some(where_clause) = &self.where_clause {
             write!(f, " WHERE {}", where_clause)?;
         }
 
+        if !self.group_by.is_empty() {
+            write!(f, " GROUP BY {}", self.group_by.join(", "))?;
+        }
+
+        if let Some(having) = &self.having {
+            write!(f, " HAVING {}", having)?;
+        }
+
+        if !self.order_by.is_empty() {
+            let order_by: Vec<String> = self.order_by.iter().map(ToString::to_string).collect();
+            write!(f, " ORDER BY {}", order_by.join(", "))?;
+        }
+
+        if let Some(limit) = self.limit {
+            write!(f, " LIMIT {}", limit)?;
+        }
+
+        if let Some(offset) = self.offset {
+            write!(f, " OFFSET {}", offset)?;
+        }
+
         Ok(())
     }
 }
 
+/// Parse an optional "WHERE <expr>" clause
+pub(crate) fn where_clause(input: RawSpan<'_>) -> ParseResult<'_, Option<Expr>> {
+    opt(preceded(
+        tuple((multispace0, keyword("where"), multispace1)),
+        Expr::parse.context("Where Clause"),
+    ))(input)
+}
+
+/// Parse an optional "GROUP BY <column>, ..." clause
+fn group_by_clause(input: RawSpan<'_>) -> ParseResult<'_, Vec<String>> {
+    map(
+        opt(preceded(
+            tuple((multispace0, keyword("group"), multispace1, keyword("by"), multispace1)),
+            comma_sep(column_ref).context("Group By Clause"),
+        )),
+        Option::unwrap_or_default,
+    )(input)
+}
+
+/// Parse an optional "HAVING <expr>" clause, the expression may use aggregates
+fn having_clause(input: RawSpan<'_>) -> ParseResult<'_, Option<Expr>> {
+    opt(preceded(
+        tuple((multispace0, keyword("having"), multispace1)),
+        Expr::parse.context("Having Clause"),
+    ))(input)
+}
+
+/// Parse an optional "ORDER BY <column> [ASC | DESC], ..." clause
+pub(crate) fn order_by_clause(input: RawSpan<'_>) -> ParseResult<'_, Vec<OrderBy>> {
+    map(
+        opt(preceded(
+            tuple((multispace0, keyword("order"), multispace1, keyword("by"), multispace1)),
+            comma_sep(OrderBy::parse).context("Order By Clause"),
+        )),
+        Option::unwrap_or_default,
+    )(input)
+}
+
+// parses an optional "<keyword> <count>" clause, as used by LIMIT and OFFSET
+fn count_clause<'a>(kw: &'static str) -> impl FnMut(RawSpan<'a>) -> ParseResult<'a, Option<usize>> {
+    opt(preceded(
+        tuple((multispace0, keyword(kw), multispace1)),
+        map_res(digit1, |digits: RawSpan| digits.fragment().parse::<usize>()).context("Row Count"),
+    ))
+}
+
 impl<'a> Parse<'a> for SelectStatementCondition {
     fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
-        let (remaining_input, (_, _, fields, _, _, _, table, where_clause)) = context(
+        let (remaining_input, (_, _, distinct, fields, _, _, _, table, alias, joins, where_clause, group_by, having)) = context(
             "Select Statement",
             tuple((
                 tag_no_case("select"),
                 multispace1,
-                comma_sep(identifier).context("Select Columns"),
-                multispace1,
-                tag_no_case("from"),
+                opt(terminated(keyword("distinct"), multispace1)),
+                comma_sep(SelectItem::parse).context("Select Columns"),
+                multispace0,
+                keyword("from"),
                 multispace1,
                 identifier.context("From Table"),
-                //multispace1,
-                opt(preceded(
-                    multispace1,
-                    pair(
-                        tag_no_case("where"),
-                        map_res(
-                            preceded(multispace1, take_until(";")),
-                            |clause: RawSpan<'a>| Ok::<String, String>(clause.fragment().to_string()), // Return clause as string
-                        ),
-                    ),
-                )),
+                table_alias,
+                many0(Join::parse),
+                where_clause,
+                group_by_clause,
+                having_clause,
             )),
         )(input)?;
+        let (remaining_input, (order_by, limit, offset)) = tuple((
+            order_by_clause,
+            count_clause("limit"),
+            count_clause("offset"),
+        ))(remaining_input)?;
 
-        //let where_clause = where_clause.map(|span| span.to_string());
-        let where_clause_condition: Option<String>  = where_clause.map(|(_, clause)| clause);
-        
-        if let Some(clause) = where_clause_condition { 
-            if clause.contains("!=") { 
-
-                let parts : Vec<&str>  = clause.split("!=").collect();
-                let moved_parts : Vec<String>= parts.iter().map(|part|  {
-                    let trimmed = part.trim();
-                    trimmed.to_string()
-                }).collect();
-                let condition = Condition { 
-                    first: moved_parts.get(0).unwrap().to_string(),
-                    second: moved_parts.get(1).unwrap().to_string(),
-                    token : "!=".to_string()
-                };
-                return Ok((
-                    remaining_input,
-                    SelectStatementCondition {
-                        fields,
-                        table,
-                        where_clause: Some(condition),
-                    },
-                ));
-            }
-            else if clause.contains("=") { 
-                println!("Clause is {}", clause);
-                let parts : Vec<String>  = clause.split("=").map(|part| part.trim().to_string()).collect();
-
-                let condition = Condition { 
-                    first: parts.get(0).unwrap().to_string(),
-                    second: parts.get(1).unwrap().to_string(),
-                    token : "=".to_string()
-                };
-                return Ok((
-                    remaining_input,
-                    SelectStatementCondition {
-                        fields,
-                        table,
-                        where_clause: Some(condition),
-                    },
-                ));
-            }
-            
-            else if clause.contains("<") { 
-                let parts : Vec<String>  = clause.split("<").map(|part| part.trim().to_string()).collect();
-                let condition = Condition { 
-                    first: parts.get(0).unwrap().to_string(),
-                    second: parts.get(1).unwrap().to_string(),
-                    token : "<".to_string()
-                };
-                return Ok((
-                    remaining_input,
-                    SelectStatementCondition {
-                        fields,
-                        table,
-                        where_clause: Some(condition),
-                    },
-                ));
-            }
-            else if clause.contains(">") { 
-                let parts : Vec<String>  = clause.split(">").map(|part| part.trim().to_string()).collect();
-                let condition = Condition { 
-                    first: parts.get(0).unwrap().to_string(),
-                    second: parts.get(1).unwrap().to_string(),
-                    token : ">".to_string()
-                };
-                return Ok((
-                    remaining_input,
-                    SelectStatementCondition {
-                        fields,
-                        table,
-                        where_clause: Some(condition),
-                    },
-                ));
-            }
-        }
         Ok((
             remaining_input,
             SelectStatementCondition {
+                distinct: distinct.is_some(),
                 fields,
                 table,
-                where_clause: None,
+                alias,
+                joins,
+                where_clause,
+                group_by,
+                having,
+                order_by,
+                limit,
+                offset,
             },
         ))
-
-        // Trim the trailing whitespace before semicolon
-        //let where_clause = where_clause.map(|clause| clause.trim_end_matches(|c| c == ';' || c == ' ').to_string());
-
-
-        
     }
 }
 
@@ -171,31 +366,58 @@ impl<'a> Parse<'a> for SelectStatementCondition {
 
 #[cfg(test)]
 mod tests {
+    use bigdecimal::{BigDecimal, FromPrimitive};
+
     use super::*;
+    use crate::{expr::{AggregateFunc, BinaryOp}, value::Value};
 
     #[test]
-    fn test_with_where() { 
-        let input = "select foo from boo where name = srinia;";
-        let expected = SelectStatementCondition { 
+    fn test_with_where() {
+        let input = "select foo from boo where name = 'srinia';";
+        let expected = SelectStatementCondition {
             table : "boo".to_string(),
-            fields : vec!["foo".to_string()],
-            where_clause: Some(Condition { 
-                first: "name".to_string(),
-                second : "srinia".to_string(),
-                token: "=".to_string()
-            })
+            fields : vec![SelectItem::column("foo")],
+            where_clause: Some(Expr::binary(
+                Expr::Column("name".to_string()),
+                BinaryOp::Eq,
+                Expr::Literal(Value::String("srinia".to_string())),
+            )),
+            ..Default::default()
         };
         assert_eq!(SelectStatementCondition::parse_from_raw(input).unwrap().1, expected);
     }
 
+    #[test]
+    fn test_with_compound_where() {
+        let input = "select foo from boo where a = 1 and b = 2;";
+        let expected = Some(Expr::binary(
+            Expr::binary(
+                Expr::Column("a".to_string()),
+                BinaryOp::Eq,
+                Expr::Literal(Value::Number(BigDecimal::from_i32(1).unwrap())),
+            ),
+            BinaryOp::And,
+            Expr::binary(
+                Expr::Column("b".to_string()),
+                BinaryOp::Eq,
+                Expr::Literal(Value::Number(BigDecimal::from_i32(2).unwrap())),
+            ),
+        ));
+        assert_eq!(
+            SelectStatementCondition::parse_from_raw(input).unwrap().1.where_clause,
+            expected
+        );
+    }
+
 
     #[test]
     fn test_select_without_where() {
         let input = "SELECT foo, bar FROM t1;";
         let expected = SelectStatementCondition {
             table: "t1".to_string(),
-            fields: vec!["foo".to_string(), "bar".to_string()],
+            fields: vec![SelectItem::column("foo"), SelectItem::column("bar")],
             where_clause: None,
+            ..Default::default()
         };
 
         assert_eq!(
@@ -203,4 +425,125 @@ mod tests {
             expected
         );
     }
+
+    #[test]
+    fn test_order_by_limit_offset() {
+        let input = "select foo from boo where a = 1 order by b desc, t.c ASC, d limit 10 offset 20;";
+        let actual = SelectStatementCondition::parse_from_raw(input).unwrap().1;
+        let order_by = |column: &str, descending| OrderBy {
+            column: column.to_string(),
+            descending,
+        };
+        assert_eq!(
+            actual.order_by,
+            vec![order_by("b", true), order_by("t.c", false), order_by("d", false)]
+        );
+        assert_eq!((actual.limit, actual.offset), (Some(10), Some(20)));
+        assert_eq!(
+            actual.to_string(),
+            "SELECT foo FROM boo WHERE (a = 1) ORDER BY b DESC, t.c, d LIMIT 10 OFFSET 20"
+        );
+
+        let actual = SelectStatementCondition::parse_from_raw("select foo from boo offset 5").unwrap().1;
+        assert_eq!((actual.limit, actual.offset), (None, Some(5)));
+    }
+
+    #[test]
+    fn test_select_distinct() {
+        let actual = SelectStatementCondition::parse_from_raw("select distinct city, count(distinct name) from users").unwrap().1;
+        assert!(actual.distinct);
+        assert_eq!(actual.to_string(), "SELECT DISTINCT city, COUNT(DISTINCT name) FROM users");
+
+        let actual = SelectStatementCondition::parse_from_raw("select distinctive from users").unwrap().1;
+        assert!(!actual.distinct);
+        assert_eq!(actual.fields, vec![SelectItem::column("distinctive")]);
+    }
+
+    #[test]
+    fn test_group_by_having() {
+        let input = "select dept, count(*), avg(salary) from emp where age > 30 group by dept having count(*) > 1 order by dept;";
+        let actual = SelectStatementCondition::parse_from_raw(input).unwrap().1;
+        let count = Expr::Aggregate { func: AggregateFunc::Count, arg: None, distinct: false };
+        assert_eq!(
+            actual.fields.iter().map(|field| field.expr().unwrap()).collect::<Vec<_>>(),
+            vec![
+                &Expr::Column("dept".to_string()),
+                &count,
+                &Expr::Aggregate {
+                    func: AggregateFunc::Avg,
+                    arg: Some(Box::new(Expr::Column("salary".to_string()))),
+                    distinct: false,
+                },
+            ]
+        );
+        assert_eq!(actual.group_by, vec!["dept".to_string()]);
+        assert_eq!(
+            actual.having,
+            Some(Expr::binary(count, BinaryOp::Gt, Expr::Literal(Value::Number(BigDecimal::from_i32(1).unwrap()))))
+        );
+        assert_eq!(
+            actual.to_string(),
+            "SELECT dept, COUNT(*), AVG(salary) FROM emp WHERE (age > 30) GROUP BY dept HAVING (COUNT(*) > 1) ORDER BY dept"
+        );
+    }
+
+    #[test]
+    fn test_joins() {
+        let input = "select e.name, d.name from emp AS e join dept as d on e.deptid = d.id left outer join badge on badge.empid = e.id where d.id > 1;";
+        let actual = SelectStatementCondition::parse_from_raw(input).unwrap().1;
+        let column = |name: &str| Expr::Column(name.to_string());
+        assert_eq!(actual.alias, Some("e".to_string()));
+        assert_eq!(
+            actual.joins,
+            vec![
+                Join {
+                    kind: JoinKind::Inner,
+                    table: "dept".to_string(),
+                    alias: Some("d".to_string()),
+                    on: Expr::binary(column("e.deptid"), BinaryOp::Eq, column("d.id")),
+                },
+                Join {
+                    kind: JoinKind::Left,
+                    table: "badge".to_string(),
+                    alias: None,
+                    on: Expr::binary(column("badge.empid"), BinaryOp::Eq, column("e.id")),
+                },
+            ]
+        );
+        assert!(actual.where_clause.is_some());
+        assert_eq!(
+            actual.to_string(),
+            "SELECT e.name, d.name FROM emp AS e JOIN dept AS d ON (e.deptid = d.id) LEFT JOIN badge ON (badge.empid = e.id) WHERE (d.id > 1)"
+        );
+        assert!(SelectStatementCondition::parse_from_raw("select a from t join u").is_err());
+    }
+
+    #[test]
+    fn test_wildcards_and_aliases() {
+        let input = "select *, e.*, price * qty AS total, user_id as id from orders as e;";
+        let actual = SelectStatementCondition::parse_from_raw(input).unwrap().1;
+        assert_eq!(
+            actual.fields,
+            vec![
+                SelectItem::Wildcard(None),
+                SelectItem::Wildcard(Some("e".to_string())),
+                SelectItem::Expr {
+                    expr: Expr::binary(
+                        Expr::Column("price".to_string()),
+                        BinaryOp::Mul,
+                        Expr::Column("qty".to_string()),
+                    ),
+                    alias: Some("total".to_string()),
+                },
+                SelectItem::Expr {
+                    expr: Expr::Column("user_id".to_string()),
+                    alias: Some("id".to_string()),
+                },
+            ]
+        );
+        assert_eq!(
+            actual.to_string(),
+            "SELECT *, e.*, (price * qty) AS total, user_id AS id FROM orders AS e"
+        );
+    }
 }
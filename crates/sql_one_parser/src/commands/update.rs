@@ -0,0 +1,130 @@
+use core::fmt;
+
+use nom::{
+    character::complete::{char, multispace0, multispace1},
+    combinator::map,
+    error::context,
+    sequence::{preceded, separated_pair, tuple},
+};
+use nom_supreme::{tag::complete::tag_no_case, ParserExt};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commands::select_condition::where_clause,
+    expr::Expr,
+    parser::{comma_sep, identifier, Parse, ParseResult, RawSpan},
+};
+
+/// A single "<column> = <expr>" of a SET list
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct Assignment {
+    pub column: String,
+    pub value: Expr,
+}
+
+impl fmt::Display for Assignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {}", self.column, self.value)
+    }
+}
+
+// parses "<colName> = <expr>"
+impl<'a> Parse<'a> for Assignment {
+    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+        context(
+            "Assignment",
+            map(
+                separated_pair(
+                    identifier.context("Column Name"),
+                    tuple((multispace0, char('='))),
+                    Expr::parse,
+                ),
+                |(column, value)| Self { column, value },
+            ),
+        )(input)
+    }
+}
+
+/// The table to update, the new column values and the rows to apply them to
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct UpdateStatement {
+    pub table: String,
+    pub assignments: Vec<Assignment>,
+    pub where_clause: Option<Expr>,
+}
+
+impl fmt::Display for UpdateStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UPDATE {} SET ", self.table)?;
+
+        let assignments: Vec<String> = self.assignments.iter().map(|a| a.to_string()).collect();
+        write!(f, "{}", assignments.join(", "))?;
+
+        if let Some(where_clause) = &self.where_clause {
+            write!(f, " WHERE {}", where_clause)?;
+        }
+
+        Ok(())
+    }
+}
+
+// parses "UPDATE <table name> SET <assignments> [WHERE <expr>]"
+impl<'a> Parse<'a> for UpdateStatement {
+    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+        let (remaining_input, (_, table, _, assignments, where_clause)) = context(
+            "Update Statement",
+            tuple((
+                tag_no_case("update"),
+                preceded(multispace1, identifier.context("Table Name")),
+                preceded(multispace1, tag_no_case("set")),
+                preceded(multispace1, comma_sep(Assignment::parse).context("Assignments")),
+                where_clause,
+            )),
+        )(input)?;
+
+        Ok((
+            remaining_input,
+            UpdateStatement {
+                table,
+                assignments,
+                where_clause,
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::{BigDecimal, FromPrimitive};
+
+    use super::*;
+    use crate::{expr::BinaryOp, value::Value};
+
+    #[test]
+    fn test_update() {
+        let expected = UpdateStatement {
+            table: "foo".to_string(),
+            assignments: vec![
+                Assignment {
+                    column: "name".to_string(),
+                    value: Expr::Literal(Value::String("raja".to_string())),
+                },
+                Assignment {
+                    column: "id".to_string(),
+                    value: Expr::Literal(Value::Number(BigDecimal::from_i32(7).unwrap())),
+                },
+            ],
+            where_clause: Some(Expr::binary(
+                Expr::Column("id".to_string()),
+                BinaryOp::Eq,
+                Expr::Literal(Value::Number(BigDecimal::from_i32(2).unwrap())),
+            )),
+        };
+        assert_eq!(
+            UpdateStatement::parse_from_raw("UPDATE foo SET name = 'raja', id=7 WHERE id = 2;")
+                .unwrap()
+                .1,
+            expected
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use core::fmt;
+
+use nom::{
+    character::complete::{char, multispace0, multispace1},
+    combinator::{cut, opt},
+    error::context,
+    sequence::{delimited, pair, preceded, terminated, tuple},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commands::compound::CompoundSelect,
+    parser::{comma_sep, identifier, keyword, Parse, ParseResult, RawSpan},
+};
+
+/// A named result defined by a WITH clause, `<name> [(<column>, ...)] AS (<select>)`
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct CommonTableExpr {
+    pub name: String,
+    /// names given to the output columns of the query in their order, its own names when empty
+    pub columns: Vec<String>,
+    pub query: CompoundSelect,
+}
+
+impl fmt::Display for CommonTableExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+
+        if !self.columns.is_empty() {
+            write!(f, " ({})", self.columns.join(", "))?;
+        }
+
+        write!(f, " AS ({})", self.query)
+    }
+}
+
+// parses "<name> [(<column>, ...)] AS (<select>)"
+impl<'a> Parse<'a> for CommonTableExpr {
+    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+        let (remaining_input, (name, columns, _, _, query)) = context(
+            "Common Table Expression",
+            tuple((
+                identifier,
+                opt(preceded(
+                    multispace0,
+                    delimited(
+                        pair(char('('), multispace0),
+                        comma_sep(identifier),
+                        pair(multispace0, char(')')),
+                    ),
+                )),
+                preceded(multispace0, keyword("as")),
+                multispace0,
+                cut(delimited(
+                    pair(char('('), multispace0),
+                    CompoundSelect::parse,
+                    pair(multispace0, char(')')),
+                )),
+            )),
+        )(input)?;
+
+        Ok((
+            remaining_input,
+            CommonTableExpr {
+                name,
+                columns: columns.unwrap_or_default(),
+                query,
+            },
+        ))
+    }
+}
+
+/// A select preceded by a WITH clause, `WITH [RECURSIVE] <cte>, ... <select>`. Every common
+/// table expression can be read by those following it and by the select, and with RECURSIVE
+/// a common table expression of the form `<select> UNION [ALL] <select>` can also read itself
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct WithQuery {
+    pub recursive: bool,
+    pub ctes: Vec<CommonTableExpr>,
+    pub query: CompoundSelect,
+}
+
+impl fmt::Display for WithQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "WITH ")?;
+
+        if self.recursive {
+            write!(f, "RECURSIVE ")?;
+        }
+
+        let ctes: Vec<String> = self.ctes.iter().map(ToString::to_string).collect();
+        write!(f, "{} {}", ctes.join(", "), self.query)
+    }
+}
+
+// parses "WITH [RECURSIVE] <cte>, ... <select>"
+impl<'a> Parse<'a> for WithQuery {
+    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+        let (remaining_input, (_, _, recursive, ctes, _, query)) = context(
+            "With Query",
+            tuple((
+                keyword("with"),
+                multispace1,
+                opt(terminated(keyword("recursive"), multispace1)),
+                cut(comma_sep(CommonTableExpr::parse)),
+                multispace0,
+                cut(CompoundSelect::parse),
+            )),
+        )(input)?;
+
+        Ok((
+            remaining_input,
+            WithQuery {
+                recursive: recursive.is_some(),
+                ctes,
+                query,
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::compound::SetOperator;
+
+    #[test]
+    fn test_with_query() {
+        let input = "with recursive tree (id, depth) as (select id, 0 from nodes where parent is null union all select n.id, t.depth + 1 from nodes as n join tree as t on n.parent = t.id), leaves as (select id from tree) select id from leaves order by id";
+        let actual = WithQuery::parse_from_raw(input).unwrap().1;
+        assert!(actual.recursive);
+        assert_eq!(actual.ctes.len(), 2);
+        assert_eq!(actual.ctes[0].name, "tree");
+        assert_eq!(actual.ctes[0].columns, vec!["id".to_string(), "depth".to_string()]);
+        assert_eq!(actual.ctes[0].query.rest[0].0, SetOperator::UnionAll);
+        assert!(actual.ctes[1].columns.is_empty());
+        assert_eq!(actual.query.first.order_by.len(), 1);
+        assert_eq!(
+            actual.to_string(),
+            "WITH RECURSIVE tree (id, depth) AS (SELECT id, 0 FROM nodes WHERE parent IS NULL UNION ALL SELECT n.id, (t.depth + 1) FROM nodes AS n JOIN tree AS t ON (n.parent = t.id)), leaves AS (SELECT id FROM tree) SELECT id FROM leaves ORDER BY id"
+        );
+
+        let actual = WithQuery::parse_from_raw("WITH x AS ( select a from t ) select a from x").unwrap().1;
+        assert!(!actual.recursive);
+        assert_eq!(actual.ctes[0].query.first.table, "t");
+
+        assert!(WithQuery::parse_from_raw("with x as select a from t select a from x").is_err());
+        assert!(WithQuery::parse_from_raw("with x as (select a from t)").is_err());
+    }
+}
@@ -0,0 +1,857 @@
+use core::fmt;
+
+use nom::{
+    branch::alt,
+    character::complete::{char, multispace0, multispace1},
+    combinator::{map, opt},
+    error::context,
+    multi::many0,
+    sequence::{delimited, pair, preceded, terminated, tuple},
+};
+use nom_supreme::tag::complete::tag;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commands::select_condition::SelectStatementCondition,
+    parser::{comma_sep, identifier, keyword, Parse, ParseResult, RawSpan},
+    value::Value,
+};
+
+/// A boolean or scalar expression, e.g. the predicate of a WHERE clause
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Expr {
+    /// Reference to a column of the row the expression is evaluated against,
+    /// qualified references keep their qualifier, e.g. `excluded.name`
+    Column(String),
+    Literal(Value),
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expr>,
+    },
+    Binary {
+        left: Box<Expr>,
+        op: BinaryOp,
+        right: Box<Expr>,
+    },
+    /// `<expr> IS NULL`, or `<expr> IS NOT NULL` when negated
+    IsNull {
+        expr: Box<Expr>,
+        negated: bool,
+    },
+    /// An aggregate over the rows of a group, `arg` is None for `COUNT(*)`. A distinct aggregate
+    /// only takes each value of its argument once, e.g. `COUNT(DISTINCT city)`
+    Aggregate {
+        func: AggregateFunc,
+        arg: Option<Box<Expr>>,
+        distinct: bool,
+    },
+    /// A call of a scalar function, its name upper cased, e.g. `UPPER(name)`
+    Function {
+        name: String,
+        args: Vec<Expr>,
+    },
+    /// `<expr> BETWEEN <low> AND <high>` with both bounds included, or `NOT BETWEEN` when negated
+    Between {
+        expr: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+        negated: bool,
+    },
+    /// `<expr> IN (<expr>, ...)`, or `NOT IN` when negated
+    InList {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+        negated: bool,
+    },
+    /// `<expr> LIKE <pattern>`, where `%` matches any run of characters, `_` any single
+    /// character and a backslash escapes the character after it. ILIKE ignores case
+    Like {
+        expr: Box<Expr>,
+        pattern: Box<Expr>,
+        negated: bool,
+        case_insensitive: bool,
+    },
+    /// A select in parentheses used as a value, giving the single column of its single row,
+    /// or NULL when it gives no row
+    Subquery(Box<SelectStatementCondition>),
+    /// `<expr> IN (<select>)` against the single column of the select, or `NOT IN` when negated
+    InSubquery {
+        expr: Box<Expr>,
+        query: Box<SelectStatementCondition>,
+        negated: bool,
+    },
+    /// `EXISTS (<select>)`, true when the select gives at least one row
+    Exists(Box<SelectStatementCondition>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AggregateFunc {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum UnaryOp {
+    Not,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BinaryOp {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    And,
+    Or,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    /// string concatenation, `||`
+    Concat,
+}
+
+impl BinaryOp {
+    /// Whether the operator computes a number rather than a truth value
+    pub fn is_arithmetic(&self) -> bool {
+        matches!(self, BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div)
+    }
+
+    /// Whether the operator gives a truth value, a comparison or a logical operator
+    pub fn is_predicate(&self) -> bool {
+        !self.is_arithmetic() && *self != BinaryOp::Concat
+    }
+}
+
+impl Expr {
+    pub fn binary(left: Expr, op: BinaryOp, right: Expr) -> Self {
+        Expr::Binary {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        }
+    }
+
+    pub fn unary(op: UnaryOp, expr: Expr) -> Self {
+        Expr::Unary {
+            op,
+            expr: Box::new(expr),
+        }
+    }
+
+    /// The expressions directly below this one, the expressions of a subquery belong to the subquery
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Column(_) | Expr::Literal(_) | Expr::Subquery(_) | Expr::Exists(_) => Vec::new(),
+            Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } | Expr::InSubquery { expr, .. } => vec![expr],
+            Expr::Aggregate { arg, .. } => arg.iter().map(Box::as_ref).collect(),
+            Expr::Function { args, .. } => args.iter().collect(),
+            Expr::Binary { left, right, .. } => vec![left, right],
+            Expr::Between { expr, low, high, .. } => vec![expr, low, high],
+            Expr::InList { expr, list, .. } => std::iter::once(expr.as_ref()).chain(list.iter()).collect(),
+            Expr::Like { expr, pattern, .. } => vec![expr, pattern],
+        }
+    }
+
+    /// All the column names referenced anywhere in the expression
+    pub fn columns(&self) -> Vec<&String> {
+        match self {
+            Expr::Column(name) => vec![name],
+            expr => expr.children().into_iter().flat_map(Expr::columns).collect(),
+        }
+    }
+
+    /// All the aggregates used anywhere in the expression
+    pub fn aggregates(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Aggregate { .. } => vec![self],
+            expr => expr.children().into_iter().flat_map(Expr::aggregates).collect(),
+        }
+    }
+
+    /// All the subqueries used anywhere in the expression, those an expression contains coming
+    /// before it
+    pub fn subqueries(&self) -> Vec<&Expr> {
+        let mut all: Vec<&Expr> = self.children().into_iter().flat_map(Expr::subqueries).collect();
+        if matches!(self, Expr::Subquery(_) | Expr::InSubquery { .. } | Expr::Exists(_)) {
+            all.push(self);
+        }
+        all
+    }
+
+    /// The select of a subquery, IN subquery or EXISTS
+    pub fn query(&self) -> Option<&SelectStatementCondition> {
+        match self {
+            Expr::Subquery(query) | Expr::InSubquery { query, .. } | Expr::Exists(query) => Some(query),
+            _ => None,
+        }
+    }
+
+    /// The conjuncts of the predicate, `a AND b AND c` gives a, b and c
+    pub fn conjuncts(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Binary { left, op: BinaryOp::And, right } => {
+                let mut all = left.conjuncts();
+                all.extend(right.conjuncts());
+                all
+            }
+            expr => vec![expr],
+        }
+    }
+}
+
+impl fmt::Display for AggregateFunc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let func = match self {
+            AggregateFunc::Count => "COUNT",
+            AggregateFunc::Sum => "SUM",
+            AggregateFunc::Avg => "AVG",
+            AggregateFunc::Min => "MIN",
+            AggregateFunc::Max => "MAX",
+        };
+        write!(f, "{func}")
+    }
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnaryOp::Not => write!(f, "NOT"),
+        }
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            BinaryOp::Eq => "=",
+            BinaryOp::NotEq => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::LtEq => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::GtEq => ">=",
+            BinaryOp::And => "AND",
+            BinaryOp::Or => "OR",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Concat => "||",
+        };
+        write!(f, "{op}")
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Column(name) => write!(f, "{name}"),
+            Expr::Literal(Value::String(str)) => write!(f, "'{str}'"),
+            Expr::Literal(Value::Date(date)) => write!(f, "DATE '{date}'"),
+            Expr::Literal(Value::Timestamp(ts)) => write!(f, "TIMESTAMP '{ts}'"),
+            Expr::Literal(value) => write!(f, "{value}"),
+            Expr::Unary { op, expr } => write!(f, "{op} {expr}"),
+            Expr::Binary { left, op, right } => write!(f, "({left} {op} {right})"),
+            Expr::IsNull { expr, negated: false } => write!(f, "{expr} IS NULL"),
+            Expr::IsNull { expr, negated: true } => write!(f, "{expr} IS NOT NULL"),
+            Expr::Aggregate { func, arg: None, .. } => write!(f, "{func}(*)"),
+            Expr::Aggregate { func, arg: Some(arg), distinct: false } => write!(f, "{func}({arg})"),
+            Expr::Aggregate { func, arg: Some(arg), distinct: true } => write!(f, "{func}(DISTINCT {arg})"),
+            Expr::Function { name, args } => {
+                let args: Vec<String> = args.iter().map(Expr::to_string).collect();
+                write!(f, "{name}({})", args.join(", "))
+            }
+            Expr::Between { expr, low, high, negated } => {
+                let not = if *negated { "NOT " } else { "" };
+                write!(f, "{expr} {not}BETWEEN {low} AND {high}")
+            }
+            Expr::InList { expr, list, negated } => {
+                let not = if *negated { "NOT " } else { "" };
+                let list: Vec<String> = list.iter().map(Expr::to_string).collect();
+                write!(f, "{expr} {not}IN ({})", list.join(", "))
+            }
+            Expr::Like { expr, pattern, negated, case_insensitive } => {
+                let not = if *negated { "NOT " } else { "" };
+                let like = if *case_insensitive { "ILIKE" } else { "LIKE" };
+                write!(f, "{expr} {not}{like} {pattern}")
+            }
+            Expr::Subquery(query) => write!(f, "({query})"),
+            Expr::InSubquery { expr, query, negated } => {
+                let not = if *negated { "NOT " } else { "" };
+                write!(f, "{expr} {not}IN ({query})")
+            }
+            Expr::Exists(query) => write!(f, "EXISTS ({query})"),
+        }
+    }
+}
+
+// folds `first op e1 op e2 ...` into a left associative tree
+fn fold_binary(first: Expr, rest: Vec<(BinaryOp, Expr)>) -> Expr {
+    rest.into_iter()
+        .fold(first, |left, (op, right)| Expr::binary(left, op, right))
+}
+
+// parses "<expr> OR <expr> ..."
+fn or_expr(input: RawSpan<'_>) -> ParseResult<'_, Expr> {
+    map(
+        pair(
+            and_expr,
+            many0(pair(
+                map(preceded(multispace0, keyword("or")), |_| BinaryOp::Or),
+                and_expr,
+            )),
+        ),
+        |(first, rest)| fold_binary(first, rest),
+    )(input)
+}
+
+// parses "<expr> AND <expr> ...", binds tighter than OR
+fn and_expr(input: RawSpan<'_>) -> ParseResult<'_, Expr> {
+    map(
+        pair(
+            not_expr,
+            many0(pair(
+                map(preceded(multispace0, keyword("and")), |_| BinaryOp::And),
+                not_expr,
+            )),
+        ),
+        |(first, rest)| fold_binary(first, rest),
+    )(input)
+}
+
+// parses "NOT <expr>", binds tighter than AND
+fn not_expr(input: RawSpan<'_>) -> ParseResult<'_, Expr> {
+    preceded(
+        multispace0,
+        alt((
+            map(preceded(keyword("not"), not_expr), |expr| {
+                Expr::unary(UnaryOp::Not, expr)
+            }),
+            comparison,
+        )),
+    )(input)
+}
+
+fn comparison_op(input: RawSpan<'_>) -> ParseResult<'_, BinaryOp> {
+    context(
+        "Comparison Operator",
+        preceded(
+            multispace0,
+            alt((
+                map(tag("<="), |_| BinaryOp::LtEq),
+                map(tag(">="), |_| BinaryOp::GtEq),
+                map(tag("!="), |_| BinaryOp::NotEq),
+                map(tag("<>"), |_| BinaryOp::NotEq),
+                map(tag("="), |_| BinaryOp::Eq),
+                map(tag("<"), |_| BinaryOp::Lt),
+                map(tag(">"), |_| BinaryOp::Gt),
+            )),
+        ),
+    )(input)
+}
+
+// parses "IS [NOT] NULL", returning whether it was negated
+fn is_null(input: RawSpan<'_>) -> ParseResult<'_, bool> {
+    map(
+        tuple((
+            preceded(multispace0, keyword("is")),
+            multispace1,
+            opt(terminated(keyword("not"), multispace1)),
+            keyword("null"),
+        )),
+        |(_, _, not, _)| not.is_some(),
+    )(input)
+}
+
+// the part of a comparison following its left operand
+enum ComparisonRest {
+    Operator(BinaryOp, Expr),
+    IsNull(bool),
+    Between(bool, Expr, Expr),
+    InList(bool, Vec<Expr>),
+    InSubquery(bool, SelectStatementCondition),
+    Like(bool, bool, Expr),
+}
+
+// parses "[NOT] BETWEEN <operand> AND <operand>", "[NOT] IN (<operand>, ...)", "[NOT] IN (<select>)"
+// or "[NOT] [I]LIKE <operand>"
+fn membership(input: RawSpan<'_>) -> ParseResult<'_, ComparisonRest> {
+    let (input, negated) = map(
+        preceded(multispace0, opt(terminated(keyword("not"), multispace1))),
+        |not| not.is_some(),
+    )(input)?;
+    alt((
+        map(
+            tuple((
+                keyword("between"),
+                additive,
+                preceded(multispace0, keyword("and")),
+                additive,
+            )),
+            move |(_, low, _, high)| ComparisonRest::Between(negated, low, high),
+        ),
+        map(
+            preceded(pair(keyword("in"), multispace0), subquery),
+            move |query| ComparisonRest::InSubquery(negated, query),
+        ),
+        map(
+            preceded(
+                pair(keyword("in"), multispace0),
+                delimited(
+                    pair(char('('), multispace0),
+                    comma_sep(additive),
+                    pair(multispace0, char(')')),
+                ),
+            ),
+            move |list| ComparisonRest::InList(negated, list),
+        ),
+        map(
+            pair(
+                alt((
+                    map(keyword("like"), |_| false),
+                    map(keyword("ilike"), |_| true),
+                )),
+                additive,
+            ),
+            move |(case_insensitive, pattern)| ComparisonRest::Like(negated, case_insensitive, pattern),
+        ),
+    ))(input)
+}
+
+// parses "<term> + <term> ...", "<term> - <term> ..." or "<term> || <term> ...", binds tighter than the comparisons
+fn additive(input: RawSpan<'_>) -> ParseResult<'_, Expr> {
+    map(
+        pair(
+            multiplicative,
+            many0(pair(
+                preceded(
+                    multispace0,
+                    alt((
+                        map(tag("+"), |_| BinaryOp::Add),
+                        map(tag("-"), |_| BinaryOp::Sub),
+                        map(tag("||"), |_| BinaryOp::Concat),
+                    )),
+                ),
+                multiplicative,
+            )),
+        ),
+        |(first, rest)| fold_binary(first, rest),
+    )(input)
+}
+
+// parses "<operand> * <operand> ..." or "<operand> / <operand> ...", binds tighter than + and -
+fn multiplicative(input: RawSpan<'_>) -> ParseResult<'_, Expr> {
+    map(
+        pair(
+            primary,
+            many0(pair(
+                preceded(
+                    multispace0,
+                    alt((
+                        map(tag("*"), |_| BinaryOp::Mul),
+                        map(tag("/"), |_| BinaryOp::Div),
+                    )),
+                ),
+                primary,
+            )),
+        ),
+        |(first, rest)| fold_binary(first, rest),
+    )(input)
+}
+
+// parses "<operand> [<comparison op> <operand> | IS [NOT] NULL]"
+fn comparison(input: RawSpan<'_>) -> ParseResult<'_, Expr> {
+    map(
+        pair(
+            additive,
+            opt(alt((
+                map(pair(comparison_op, additive), |(op, right)| {
+                    ComparisonRest::Operator(op, right)
+                }),
+                map(is_null, ComparisonRest::IsNull),
+                membership,
+            ))),
+        ),
+        |(left, rest)| match rest {
+            Some(ComparisonRest::Operator(op, right)) => Expr::binary(left, op, right),
+            Some(ComparisonRest::IsNull(negated)) => Expr::IsNull {
+                expr: Box::new(left),
+                negated,
+            },
+            Some(ComparisonRest::Between(negated, low, high)) => Expr::Between {
+                expr: Box::new(left),
+                low: Box::new(low),
+                high: Box::new(high),
+                negated,
+            },
+            Some(ComparisonRest::InList(negated, list)) => Expr::InList {
+                expr: Box::new(left),
+                list,
+                negated,
+            },
+            Some(ComparisonRest::InSubquery(negated, query)) => Expr::InSubquery {
+                expr: Box::new(left),
+                query: Box::new(query),
+                negated,
+            },
+            Some(ComparisonRest::Like(negated, case_insensitive, pattern)) => Expr::Like {
+                expr: Box::new(left),
+                pattern: Box::new(pattern),
+                negated,
+                case_insensitive,
+            },
+            None => left,
+        },
+    )(input)
+}
+
+// parses a column name, optionally qualified as "<qualifier>.<column>"
+pub(crate) fn column_ref(input: RawSpan<'_>) -> ParseResult<'_, String> {
+    map(
+        pair(identifier, opt(preceded(char('.'), identifier))),
+        |(first, second)| match second {
+            Some(column) => format!("{first}.{column}"),
+            None => first,
+        },
+    )(input)
+}
+
+// parses "COUNT(*)" or "<aggregate>([DISTINCT] <expr>)", a name not followed by a paren is left to be a column
+fn aggregate(input: RawSpan<'_>) -> ParseResult<'_, Expr> {
+    let func = alt((
+        map(keyword("count"), |_| AggregateFunc::Count),
+        map(keyword("sum"), |_| AggregateFunc::Sum),
+        map(keyword("avg"), |_| AggregateFunc::Avg),
+        map(keyword("min"), |_| AggregateFunc::Min),
+        map(keyword("max"), |_| AggregateFunc::Max),
+    ));
+    let count_star = tuple((
+        keyword("count"),
+        multispace0,
+        char('('),
+        multispace0,
+        char('*'),
+        multispace0,
+        char(')'),
+    ));
+    context(
+        "Aggregate",
+        alt((
+            map(count_star, |_| Expr::Aggregate {
+                func: AggregateFunc::Count,
+                arg: None,
+                distinct: false,
+            }),
+            map(
+                pair(
+                    func,
+                    delimited(
+                        pair(multispace0, char('(')),
+                        pair(
+                            opt(preceded(multispace0, terminated(keyword("distinct"), multispace1))),
+                            Expr::parse,
+                        ),
+                        preceded(multispace0, char(')')),
+                    ),
+                ),
+                |(func, (distinct, arg))| Expr::Aggregate {
+                    func,
+                    arg: Some(Box::new(arg)),
+                    distinct: distinct.is_some(),
+                },
+            ),
+        )),
+    )(input)
+}
+
+// parses "<name>(<expr>, ...)", a call of a scalar function. Which functions exist is left to
+// the execution, so any name directly followed by a paren is a call
+fn function(input: RawSpan<'_>) -> ParseResult<'_, Expr> {
+    context(
+        "Function",
+        map(
+            pair(
+                terminated(identifier, pair(multispace0, char('('))),
+                terminated(
+                    opt(comma_sep(Expr::parse)),
+                    pair(multispace0, char(')')),
+                ),
+            ),
+            |(name, args)| Expr::Function {
+                name: name.to_uppercase(),
+                args: args.unwrap_or_default(),
+            },
+        ),
+    )(input)
+}
+
+// parses "(<select>)", a select nested in an expression
+fn subquery(input: RawSpan<'_>) -> ParseResult<'_, SelectStatementCondition> {
+    context(
+        "Subquery",
+        delimited(
+            pair(char('('), multispace0),
+            SelectStatementCondition::parse,
+            pair(multispace0, char(')')),
+        ),
+    )(input)
+}
+
+// parses a subquery, a parenthesized expression, a literal, an aggregate, "EXISTS (<select>)",
+// a function call or a column name
+fn primary(input: RawSpan<'_>) -> ParseResult<'_, Expr> {
+    context(
+        "Operand",
+        preceded(
+            multispace0,
+            alt((
+                map(subquery, |query| Expr::Subquery(Box::new(query))),
+                delimited(char('('), Expr::parse, preceded(multispace0, char(')'))),
+                map(Value::parse, Expr::Literal),
+                aggregate,
+                map(preceded(pair(keyword("exists"), multispace0), subquery), |query| {
+                    Expr::Exists(Box::new(query))
+                }),
+                function,
+                map(column_ref, Expr::Column),
+            )),
+        ),
+    )(input)
+}
+
+impl<'a> Parse<'a> for Expr {
+    fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
+        context("Expression", or_expr)(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::{BigDecimal, FromPrimitive};
+
+    use super::*;
+
+    fn number(n: i32) -> Expr {
+        Expr::Literal(Value::Number(BigDecimal::from_i32(n).unwrap()))
+    }
+
+    fn column(name: &str) -> Expr {
+        Expr::Column(name.to_string())
+    }
+
+    #[test]
+    fn test_and_binds_tighter_than_or() {
+        let expected = Expr::binary(
+            Expr::binary(column("a"), BinaryOp::Eq, number(1)),
+            BinaryOp::Or,
+            Expr::binary(
+                Expr::binary(column("b"), BinaryOp::GtEq, number(2)),
+                BinaryOp::And,
+                Expr::binary(
+                    column("c"),
+                    BinaryOp::NotEq,
+                    Expr::Literal(Value::String("x".to_string())),
+                ),
+            ),
+        );
+        assert_eq!(
+            Expr::parse_from_raw("a = 1 or b >= 2 and c <> 'x'").unwrap().1,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_not_and_parentheses() {
+        let expected = Expr::binary(
+            Expr::unary(
+                UnaryOp::Not,
+                Expr::binary(
+                    Expr::binary(column("a"), BinaryOp::Lt, number(1)),
+                    BinaryOp::Or,
+                    Expr::binary(column("b"), BinaryOp::Gt, number(2)),
+                ),
+            ),
+            BinaryOp::And,
+            column("flag"),
+        );
+        assert_eq!(
+            Expr::parse_from_raw("not (a < 1 OR b > 2) AND flag").unwrap().1,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_keyword_prefix_is_not_an_operator() {
+        let (rest, expr) = Expr::parse_from_raw("id = 4 order by id").unwrap();
+        assert_eq!(expr, Expr::binary(column("id"), BinaryOp::Eq, number(4)));
+        assert_eq!(*rest.fragment(), "order by id");
+    }
+
+    #[test]
+    fn test_is_null() {
+        let expected = Expr::binary(
+            Expr::IsNull {
+                expr: Box::new(column("a")),
+                negated: false,
+            },
+            BinaryOp::Or,
+            Expr::IsNull {
+                expr: Box::new(column("b")),
+                negated: true,
+            },
+        );
+        let expr = Expr::parse_from_raw("a is null or b IS NOT NULL").unwrap().1;
+        assert_eq!(expr, expected);
+        assert_eq!(expr.to_string(), "(a IS NULL OR b IS NOT NULL)");
+        assert_eq!(
+            Expr::parse_from_raw("a = null").unwrap().1,
+            Expr::binary(column("a"), BinaryOp::Eq, Expr::Literal(Value::Null))
+        );
+    }
+
+    #[test]
+    fn test_aggregates() {
+        let count = Expr::Aggregate {
+            func: AggregateFunc::Count,
+            arg: None,
+            distinct: false,
+        };
+        let expected = Expr::binary(count.clone(), BinaryOp::Gt, number(1));
+        let expr = Expr::parse_from_raw("count( * ) > 1").unwrap().1;
+        assert_eq!(expr, expected);
+        assert_eq!(expr.aggregates(), vec![&count]);
+
+        let sum = Expr::parse_from_raw("Sum(price)").unwrap().1;
+        assert_eq!(sum.to_string(), "SUM(price)");
+        assert_eq!(sum.columns(), vec!["price"]);
+        // without parens an aggregate name is an ordinary column
+        assert_eq!(Expr::parse_from_raw("max").unwrap().1, column("max"));
+        assert!(column("count").aggregates().is_empty());
+
+        let distinct = Expr::parse_from_raw("count( distinct city )").unwrap().1;
+        assert!(matches!(distinct, Expr::Aggregate { distinct: true, .. }));
+        assert_eq!(distinct.to_string(), "COUNT(DISTINCT city)");
+        assert_eq!(distinct.columns(), vec!["city"]);
+        // a column named distinct is still a column
+        assert!(matches!(Expr::parse_from_raw("count(distinct)").unwrap().1, Expr::Aggregate { distinct: false, .. }));
+    }
+
+    #[test]
+    fn test_arithmetic_precedence() {
+        let expected = Expr::binary(
+            Expr::binary(
+                column("a"),
+                BinaryOp::Add,
+                Expr::binary(column("b"), BinaryOp::Mul, number(2)),
+            ),
+            BinaryOp::Gt,
+            Expr::binary(
+                Expr::binary(column("c"), BinaryOp::Sub, number(1)),
+                BinaryOp::Div,
+                column("unit_price"),
+            ),
+        );
+        let expr = Expr::parse_from_raw("a + b*2 > (c -1) / unit_price").unwrap().1;
+        assert_eq!(expr, expected);
+        assert_eq!(expr.to_string(), "((a + (b * 2)) > ((c - 1) / unit_price))");
+        assert_eq!(
+            Expr::parse_from_raw("a - -1").unwrap().1,
+            Expr::binary(column("a"), BinaryOp::Sub, number(-1))
+        );
+    }
+
+    #[test]
+    fn test_between_in_like() {
+        let expr = Expr::parse_from_raw("a between 1 and b + 1 and c not in (1, 2) or name ilike 'r%'").unwrap().1;
+        let expected = Expr::binary(
+            Expr::binary(
+                Expr::Between {
+                    expr: Box::new(column("a")),
+                    low: Box::new(number(1)),
+                    high: Box::new(Expr::binary(column("b"), BinaryOp::Add, number(1))),
+                    negated: false,
+                },
+                BinaryOp::And,
+                Expr::InList {
+                    expr: Box::new(column("c")),
+                    list: vec![number(1), number(2)],
+                    negated: true,
+                },
+            ),
+            BinaryOp::Or,
+            Expr::Like {
+                expr: Box::new(column("name")),
+                pattern: Box::new(Expr::Literal(Value::String("r%".to_string()))),
+                negated: false,
+                case_insensitive: true,
+            },
+        );
+        assert_eq!(expr, expected);
+        assert_eq!(
+            expr.to_string(),
+            "((a BETWEEN 1 AND (b + 1) AND c NOT IN (1, 2)) OR name ILIKE 'r%')"
+        );
+        assert_eq!(expr.columns(), vec!["a", "b", "c", "name"]);
+        assert!(Expr::parse_from_raw("indexed not like 'x'").is_ok());
+        assert!(Expr::parse_format_error("a in ()").is_err());
+    }
+
+    #[test]
+    fn test_functions_and_concat() {
+        let expr = Expr::parse_from_raw("upper (trim(name)) || '-' || coalesce(nick, 'none') = lower()").unwrap().1;
+        let call = |name: &str, args: Vec<Expr>| Expr::Function {
+            name: name.to_string(),
+            args,
+        };
+        let string = |str: &str| Expr::Literal(Value::String(str.to_string()));
+        let expected = Expr::binary(
+            Expr::binary(
+                Expr::binary(
+                    call("UPPER", vec![call("TRIM", vec![column("name")])]),
+                    BinaryOp::Concat,
+                    string("-"),
+                ),
+                BinaryOp::Concat,
+                call("COALESCE", vec![column("nick"), string("none")]),
+            ),
+            BinaryOp::Eq,
+            call("LOWER", Vec::new()),
+        );
+        assert_eq!(expr, expected);
+        assert_eq!(
+            expr.to_string(),
+            "(((UPPER(TRIM(name)) || '-') || COALESCE(nick, 'none')) = LOWER())"
+        );
+        assert_eq!(expr.columns(), vec!["name", "nick"]);
+        // an aggregate name is still an aggregate
+        assert!(matches!(Expr::parse_from_raw("max(a)").unwrap().1, Expr::Aggregate { .. }));
+    }
+
+    #[test]
+    fn test_subqueries() {
+        let input = "id in (select user_id from orders where total > 10) and not exists ( select * from bans where bans.id = u.id ) or (select max(total) from orders) > 2";
+        let expr = Expr::parse_from_raw(input).unwrap().1;
+        assert_eq!(
+            expr.to_string(),
+            "((id IN (SELECT user_id FROM orders WHERE (total > 10)) AND NOT EXISTS (SELECT * FROM bans WHERE (bans.id = u.id))) OR ((SELECT MAX(total) FROM orders) > 2))"
+        );
+        let subqueries: Vec<String> = expr.subqueries().iter().map(ToString::to_string).collect();
+        assert_eq!(subqueries.len(), 3);
+        assert!(subqueries[1].starts_with("EXISTS"));
+        // the columns of a subquery are not the columns of the expression using it
+        assert_eq!(expr.columns(), vec!["id"]);
+
+        let expr = Expr::parse_from_raw("a not in ((select b from t))").unwrap().1;
+        let Expr::InList { list, negated: true, .. } = expr else {
+            panic!("expected a list holding a scalar subquery, got {expr:?}");
+        };
+        assert!(matches!(list.as_slice(), [Expr::Subquery(_)]));
+        // a parenthesized expression is still an expression
+        assert_eq!(Expr::parse_from_raw("(a + 1)").unwrap().1, Expr::binary(column("a"), BinaryOp::Add, number(1)));
+    }
+}
@@ -1,5 +1,7 @@
 pub mod parser;
 pub mod commands;
 pub mod value;
+pub mod temporal;
+pub mod expr;
 pub mod ast;
 pub mod error;
\ No newline at end of file
@@ -1,10 +1,10 @@
 use nom::{
     self,
     bytes::complete::take_while1,
-    character::complete::{char, multispace0},
-    combinator::{all_consuming, map, peek},
+    character::complete::{char, multispace0, satisfy},
+    combinator::{all_consuming, map, not, peek},
     multi::separated_list1,
-    sequence::{pair, tuple},
+    sequence::{pair, terminated, tuple},
     Finish, IResult,
 };
 use nom_locate::LocatedSpan;
@@ -37,13 +37,22 @@ pub trait Parse<'a>: Sized {
     }
 }
 
-/// Parse a unquoted sql identifier
+/// Parse a unquoted sql identifier, made of letters, digits and underscores
 pub(crate) fn identifier(i: RawSpan) -> ParseResult<String> {
-    map(take_while1(|c: char| c.is_alphanumeric()), |s: RawSpan| {
+    map(take_while1(|c: char| c.is_alphanumeric() || c == '_'), |s: RawSpan| {
         s.fragment().to_string()
     })(i)
 }
 
+/// Parse a sql keyword (case insensitive), making sure it is not just the
+/// prefix of a longer identifier, e.g. `or` must not match the start of `order`
+pub(crate) fn keyword<'a>(kw: &'static str) -> impl FnMut(RawSpan<'a>) -> ParseResult<'a, RawSpan<'a>> {
+    terminated(
+        tag_no_case(kw),
+        not(peek(satisfy(|c: char| c.is_alphanumeric() || c == '_'))),
+    )
+}
+
 /// Check if the input has the passed in tag
 /// if so run the parser supplied (with the peeked tag still expected)
 /// and cut on error
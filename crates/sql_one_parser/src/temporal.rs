@@ -0,0 +1,174 @@
+use core::fmt;
+use std::str::FromStr;
+
+/// A calendar date in ISO-8601 form, `YYYY-MM-DD`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i32,
+    pub month: u8,
+    pub day: u8,
+}
+
+/// A date and time of day without a time zone, `YYYY-MM-DD HH:MM:SS[.ffffff]`,
+/// a `T` is accepted in place of the space
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Timestamp {
+    pub date: Date,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub micros: u32,
+}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i32, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// parses a field made of exactly `width` ascii digits
+fn fixed_digits<T: FromStr>(field: &str, width: usize) -> Option<T> {
+    if field.len() != width || !field.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    field.parse().ok()
+}
+
+impl Date {
+    pub fn new(year: i32, month: u8, day: u8) -> Option<Self> {
+        let valid = (1..=12).contains(&month) && day >= 1 && day <= days_in_month(year, month);
+        valid.then_some(Self { year, month, day })
+    }
+}
+
+impl Timestamp {
+    pub fn new(date: Date, hour: u8, minute: u8, second: u8, micros: u32) -> Option<Self> {
+        let valid = hour < 24 && minute < 60 && second < 60 && micros < 1_000_000;
+        valid.then_some(Self {
+            date,
+            hour,
+            minute,
+            second,
+            micros,
+        })
+    }
+
+    /// The first instant of the given day
+    pub fn midnight(date: Date) -> Self {
+        Self {
+            date,
+            hour: 0,
+            minute: 0,
+            second: 0,
+            micros: 0,
+        }
+    }
+}
+
+impl FromStr for Date {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("{s} is not a valid date, expected YYYY-MM-DD");
+        let mut fields = s.split('-');
+        let (Some(year), Some(month), Some(day), None) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            return Err(invalid());
+        };
+        let (Some(year), Some(month), Some(day)) = (
+            fixed_digits(year, 4),
+            fixed_digits(month, 2),
+            fixed_digits(day, 2),
+        ) else {
+            return Err(invalid());
+        };
+        Date::new(year, month, day).ok_or_else(invalid)
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("{s} is not a valid timestamp, expected YYYY-MM-DD HH:MM:SS");
+        let Some((date, time)) = s.split_once([' ', 'T']) else {
+            return Err(invalid());
+        };
+        let date = Date::from_str(date).map_err(|_| invalid())?;
+        let (time, fraction) = match time.split_once('.') {
+            Some((time, fraction)) if (1..=6).contains(&fraction.len()) => {
+                // right pad the fraction to microseconds, ".5" is half a second
+                let micros = fixed_digits::<u32>(fraction, fraction.len()).ok_or_else(invalid)?;
+                (time, micros * 10u32.pow(6 - fraction.len() as u32))
+            }
+            Some(_) => return Err(invalid()),
+            None => (time, 0),
+        };
+        let mut fields = time.split(':');
+        let (Some(hour), Some(minute), Some(second), None) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            return Err(invalid());
+        };
+        let (Some(hour), Some(minute), Some(second)) = (
+            fixed_digits(hour, 2),
+            fixed_digits(minute, 2),
+            fixed_digits(second, 2),
+        ) else {
+            return Err(invalid());
+        };
+        Timestamp::new(date, hour, minute, second, fraction).ok_or_else(invalid)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:02}:{:02}:{:02}",
+            self.date, self.hour, self.minute, self.second
+        )?;
+        if self.micros != 0 {
+            write!(f, ".{:06}", self.micros)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(Date::from_str("2024-02-29"), Ok(Date::new(2024, 2, 29).unwrap()));
+        assert!(Date::from_str("2023-02-29").is_err());
+        assert!(Date::from_str("2024-13-01").is_err());
+        assert!(Date::from_str("2024-1-01").is_err());
+        assert_eq!(Date::new(2024, 1, 5).unwrap().to_string(), "2024-01-05");
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let ts = Timestamp::from_str("2024-01-05T10:30:00.5").unwrap();
+        assert_eq!(ts.micros, 500_000);
+        assert_eq!(ts.to_string(), "2024-01-05 10:30:00.500000");
+        assert!(Timestamp::from_str("2024-01-05 24:00:00").is_err());
+        assert!(Timestamp::from_str("2024-01-05").is_err());
+        assert!(Timestamp::midnight(ts.date) < ts);
+    }
+}
@@ -1,26 +1,70 @@
+use core::fmt;
 use std::str::FromStr;
 
 use bigdecimal::BigDecimal;
-use derive_more::Display;
 use nom::{
     branch::alt,
     bytes::complete::{take_until, take_while},
-    character::complete::multispace0,
+    character::complete::{char, digit1, multispace0, one_of},
+    combinator::{cut, map, map_opt, opt, peek, recognize},
     error::context,
-    sequence::{preceded, terminated, tuple},
+    sequence::{pair, preceded, terminated, tuple},
     Parser,
 };
-use nom_supreme::tag::complete::tag;
-use serde::{Deserialize, Serialize};
+use nom_supreme::tag::complete::{tag, tag_no_case};
+use serde::{
+    de::{self, MapAccess, Visitor},
+    ser::SerializeMap,
+    Deserialize, Serialize,
+};
 
-use crate::parser::{peek_then_cut, Parse, ParseResult, RawSpan};
+use crate::{
+    parser::{keyword, peek_then_cut, Parse, ParseResult, RawSpan},
+    temporal::{Date, Timestamp},
+};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash,  Display, Ord, PartialOrd)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Ord, PartialOrd)]
 pub enum Value {
-    Number(BigDecimal), // TODO: should we make literals for ints vs floats?
+    /// integer and floating point literals alike, the column type decides the range
+    Number(BigDecimal),
     String(String),
+    Boolean(bool),
+    Date(Date),
+    Timestamp(Timestamp),
+    Blob(Vec<u8>),
+    /// the absence of a value, ordered after every other value
+    Null,
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Number(number) => write!(f, "{number}"),
+            Value::String(str) => write!(f, "{str}"),
+            Value::Boolean(bool) => write!(f, "{bool}"),
+            Value::Date(date) => write!(f, "{date}"),
+            Value::Timestamp(ts) => write!(f, "{ts}"),
+            Value::Blob(bytes) => write!(f, "x'{}'", to_hex(bytes)),
+            Value::Null => write!(f, "NULL"),
+        }
+    }
 }
 
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+
 impl Serialize for Value {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -31,6 +75,61 @@ impl Serialize for Value {
                 serializer.serialize_str(&string_value)
             },
             Value::String(str) => serializer.serialize_str(str),
+            Value::Boolean(bool) => serializer.serialize_bool(*bool),
+            // the remaining kinds would read back as plain strings, so they are tagged
+            Value::Date(date) => tagged(serializer, "date", date.to_string()),
+            Value::Timestamp(ts) => tagged(serializer, "timestamp", ts.to_string()),
+            Value::Blob(bytes) => tagged(serializer, "blob", to_hex(bytes)),
+            Value::Null => serializer.serialize_none(),
+        }
+    }
+}
+
+// writes the value as a single entry map, e.g. `{"date":"2024-01-31"}`
+fn tagged<S: serde::Serializer>(serializer: S, tag: &str, value: String) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(1))?;
+    map.serialize_entry(tag, &value)?;
+    map.end()
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string, a boolean, null or a tagged value")
+    }
+
+    fn visit_str<E: de::Error>(self, value_str: &str) -> Result<Value, E> {
+        if let Some(big_int) = BigDecimal::parse_bytes(value_str.as_bytes(), 10) {
+            return Ok(Value::Number(big_int));
+        }
+        // Try to parse as a string
+        Ok(Value::String(value_str.to_string()))
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Value, E> {
+        Ok(Value::Boolean(value))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let Some((tag, value)) = map.next_entry::<String, String>()? else {
+            return Err(de::Error::custom("empty tagged value"));
+        };
+        match tag.as_str() {
+            "date" => Date::from_str(&value).map(Value::Date).map_err(de::Error::custom),
+            "timestamp" => Timestamp::from_str(&value).map(Value::Timestamp).map_err(de::Error::custom),
+            "blob" => from_hex(&value).map(Value::Blob).ok_or_else(|| de::Error::custom("invalid hex blob")),
+            _ => Err(de::Error::unknown_variant(&tag, &["date", "timestamp", "blob"])),
         }
     }
 }
@@ -41,13 +140,7 @@ impl<'de> Deserialize<'de> for Value {
     where
         D: serde::Deserializer<'de>,
     {
-        let value_str = String::deserialize(deserializer)?;
-        if let Some(big_int) = BigDecimal::parse_bytes(value_str.as_bytes(), 10) {
-            return Ok(Value::Number(big_int));
-        }
-        // Try to parse as a string
-        Ok(Value::String(value_str))
-        // Add deserialization for other variants if needed
+        deserializer.deserialize_any(ValueVisitor)
     }
 }
 
@@ -61,6 +154,10 @@ impl Value {
             },
         }
     }
+
+    pub fn is_null(&self) -> bool { 
+        matches!(self, Value::Null)
+    }
 }
 
 /// Parse a single quoted string value
@@ -79,10 +176,16 @@ fn parse_string_value(input: RawSpan<'_>) -> ParseResult<'_, Value> {
     Ok((remaining, str_value))
 }
 
-/// Parse a numeric literal
+/// Parse a numeric literal, optionally signed and with a fractional part
 fn parse_number_value(input: RawSpan<'_>) -> ParseResult<'_, Value> {
-    let (remaining, digits) =
-        context("Number Literal", take_while(|c: char| c.is_numeric()))(input)?; // TODO: handle floats
+    let (remaining, digits) = context(
+        "Number Literal",
+        recognize(tuple((
+            opt(one_of("+-")),
+            digit1,
+            opt(pair(char('.'), digit1)),
+        ))),
+    )(input)?;
 
     let digits = digits.fragment();
 
@@ -92,6 +195,46 @@ fn parse_number_value(input: RawSpan<'_>) -> ParseResult<'_, Value> {
     ))
 }
 
+/// Parse a hex blob literal, e.g. x'0aff'
+fn parse_blob_value(input: RawSpan<'_>) -> ParseResult<'_, Value> {
+    context(
+        "Blob Literal",
+        preceded(
+            pair(tag_no_case("x"), tag("'")),
+            cut(terminated(
+                map_opt(take_while(|c: char| c.is_ascii_hexdigit()), |hex: RawSpan| {
+                    from_hex(hex.fragment()).map(Value::Blob)
+                }),
+                tag("'"),
+            )),
+        ),
+    )(input)
+}
+
+/// Parse a typed literal such as DATE '2024-01-31' or TIMESTAMP '2024-01-31 10:00:00'
+fn parse_temporal_value(input: RawSpan<'_>) -> ParseResult<'_, Value> {
+    // the literal is quoted like a string, the quote is what tells it from a column named date
+    fn quoted<'a, T: FromStr>(
+        kw: &'static str,
+        ctx: &'static str,
+    ) -> impl FnMut(RawSpan<'a>) -> ParseResult<'a, T> {
+        preceded(
+            tuple((keyword(kw), multispace0, peek(tag("'")))),
+            cut(context(
+                ctx,
+                map_opt(parse_string_value, |value| match value {
+                    Value::String(str) => T::from_str(&str).ok(),
+                    _ => None,
+                }),
+            )),
+        )
+    }
+    alt((
+        map(quoted("date", "Date Literal"), Value::Date),
+        map(quoted("timestamp", "Timestamp Literal"), Value::Timestamp),
+    ))(input)
+}
+
 impl<'a> Parse<'a> for Value {
     fn parse(input: RawSpan<'a>) -> ParseResult<'a, Self> {
         context(
@@ -99,7 +242,15 @@ impl<'a> Parse<'a> for Value {
             preceded(
                 multispace0,
                 terminated(
-                    alt((peek_then_cut("'", parse_string_value), parse_number_value)),
+                    alt((
+                        peek_then_cut("'", parse_string_value),
+                        parse_number_value,
+                        parse_blob_value,
+                        parse_temporal_value,
+                        map(keyword("true"), |_| Value::Boolean(true)),
+                        map(keyword("false"), |_| Value::Boolean(false)),
+                        map(keyword("null"), |_| Value::Null),
+                    )),
                     multispace0,
                 ),
             ),
@@ -111,7 +262,10 @@ impl<'a> Parse<'a> for Value {
 mod tests {
     use bigdecimal::{BigDecimal, FromPrimitive};
 
+    use std::str::FromStr;
+
     use super::Value;
+    use crate::{parser::Parse, temporal::{Date, Timestamp}};
 
 
     #[test] 
@@ -129,4 +283,28 @@ mod tests {
         assert_eq!(Value::value(str), expected)
         
     }
+
+    #[test]
+    fn test_value_literals() { 
+        let parse = |input| Value::parse_from_raw(input).unwrap().1;
+        assert_eq!(parse("-12.50"), Value::Number(BigDecimal::from_str("-12.5").unwrap()));
+        assert_eq!(parse("+3"), Value::Number(BigDecimal::from_i32(3).unwrap()));
+        assert_eq!(parse("TRUE"), Value::Boolean(true));
+        assert_eq!(parse("x'0aFF'"), Value::Blob(vec![0x0a, 0xff]));
+        assert_eq!(parse("date '2024-01-31'"), Value::Date(Date::new(2024, 1, 31).unwrap()));
+        assert_eq!(
+            parse("TIMESTAMP '2024-01-31 10:00:00'"),
+            Value::Timestamp(Timestamp::new(Date::new(2024, 1, 31).unwrap(), 10, 0, 0, 0).unwrap())
+        );
+        assert!(Value::parse_from_raw("x'abc'").is_err());
+        assert!(Value::parse_from_raw("date '2024-02-30'").is_err());
+        assert!(Value::parse_from_raw("truely").is_err());
+    }
+
+    #[test]
+    fn test_value_null() { 
+        assert_eq!(Value::parse_from_raw("NULL").unwrap().1, Value::Null);
+        assert_eq!(Value::parse_from_raw("'null'").unwrap().1, Value::String("null".to_string()));
+        assert!(Value::parse_from_raw("nullable").is_err());
+    }
 }
@@ -1,9 +1,10 @@
 use rustyline::error::ReadlineError;
 use rustyline::{Editor, Result};
-use sql_one_parser::parser::Parse;
 use sql_one_execution::execution::{ExecResponse, Execution};
 
 const HISTORY_FILE: &str = "./history.txt";
+/// The environment variable giving the number of buffer pool frames the tables' pages are read into
+const POOL_FRAMES: &str = "SQL_ONE_POOL_FRAMES";
 
 fn main() -> Result<()>{
     // let mut exec = Execution::new();
@@ -34,7 +35,10 @@ fn main() -> Result<()>{
     if rl.load_history(HISTORY_FILE).is_err() { 
         println!("no previous history");
     }
-    let mut exec = Execution::new();
+    let mut exec = match std::env::var(POOL_FRAMES).ok().and_then(|frames| frames.parse().ok()) { 
+        Some(frames) => Execution::with_pool_frames(frames),
+        None => Execution::new(),
+    };
     loop { 
         let readline = rl.readline(">> ");
         match readline { 
@@ -50,8 +54,13 @@ fn main() -> Result<()>{
                                     println!("row is {:#?}", row);
                                 }
                             },
-                            ExecResponse::Insert => println!("insert"),
+                            ExecResponse::Insert { rows_affected } => println!("inserted {} rows", rows_affected),
                             ExecResponse::Crete => println!("create"),
+                            ExecResponse::Delete { rows_affected } => println!("deleted {} rows", rows_affected),
+                            ExecResponse::Update { rows_affected } => println!("updated {} rows", rows_affected),
+                            ExecResponse::Drop => println!("drop"),
+                            ExecResponse::Truncate { rows_affected } => println!("truncated {} rows", rows_affected),
+                            ExecResponse::Alter => println!("alter"),
                         }
                     
                     },